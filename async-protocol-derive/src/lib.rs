@@ -0,0 +1,256 @@
+//! Derive macro for the `MessageWithId` trait of the `async-protocol` crate.
+//!
+//! The field with the message's ID is marked with `#[id]`. In enums, every
+//! variant must have an `#[id]` field, and all of them must have the same
+//! type.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::ToTokens;
+use syn::{Data, DataEnum, DeriveInput, Error, Field, Fields, Index, Member};
+
+#[proc_macro_derive(MessageWithId, attributes(id))]
+pub fn derive_message_with_id(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) =
+        input.generics.split_for_impl();
+
+    let (id_type, id) = match input.data {
+        Data::Struct(ref data) => {
+            let (member, field) = id_field(&data.fields)?.ok_or_else(|| {
+                Error::new_spanned(name, "no field is marked with #[id]")
+            })?;
+
+            (field.ty.clone(), quote! { &self.#member })
+        }
+        Data::Enum(ref data) => expand_enum(input, data)?,
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                input,
+                "MessageWithId can't be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::async_protocol::MessageWithId
+            for #name #type_generics #where_clause
+        {
+            type Id = #id_type;
+
+            fn id(&self) -> Self::Id {
+                ::std::clone::Clone::clone(#id)
+            }
+        }
+    })
+}
+
+/// Builds a `match` expression that borrows the ID of each variant.
+///
+/// Errors for all variants without an ID, or with an ID of a different type
+/// than the first variant's, are reported together.
+fn expand_enum(
+    input: &DeriveInput,
+    data: &DataEnum,
+) -> syn::Result<(syn::Type, TokenStream2)> {
+    let name = &input.ident;
+    let mut id_type = None;
+    let mut arms = Vec::new();
+    let mut errors: Option<Error> = None;
+
+    for variant in &data.variants {
+        let variant_name = &variant.ident;
+
+        match id_field(&variant.fields)? {
+            Some((member, field)) => {
+                let first_type =
+                    id_type.get_or_insert_with(|| field.ty.clone());
+
+                if !same_type(first_type, &field.ty) {
+                    let message = format!(
+                        "variant `{}` has an ID of type `{}` instead of `{}`",
+                        variant_name,
+                        field.ty.to_token_stream(),
+                        first_type.to_token_stream(),
+                    );
+
+                    let error = Error::new_spanned(variant, message);
+
+                    add_error(&mut errors, error);
+                }
+
+                arms.push(quote! {
+                    #name::#variant_name { #member: ref id, .. } => id
+                });
+            }
+            None => {
+                let message = format!(
+                    "variant `{}` has no field marked with #[id]",
+                    variant_name
+                );
+
+                let error = Error::new_spanned(variant, message);
+
+                add_error(&mut errors, error);
+            }
+        }
+    }
+
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let id_type = id_type.ok_or_else(|| {
+        Error::new_spanned(
+            input,
+            "MessageWithId can't be derived for enums without variants",
+        )
+    })?;
+
+    Ok((id_type, quote! { match *self { #(#arms,)* } }))
+}
+
+fn add_error(errors: &mut Option<Error>, error: Error) {
+    match *errors {
+        Some(ref mut errors) => errors.combine(error),
+        None => *errors = Some(error),
+    }
+}
+
+/// Compares types by their tokens, since paths to the same type can't be
+/// resolved by a macro.
+fn same_type(first: &syn::Type, second: &syn::Type) -> bool {
+    first.to_token_stream().to_string() == second.to_token_stream().to_string()
+}
+
+/// Finds the field marked with `#[id]`, and how to access it.
+fn id_field(fields: &Fields) -> syn::Result<Option<(Member, &Field)>> {
+    let mut id_fields = fields.iter().enumerate().filter(|&(_, field)| {
+        field.attrs.iter().any(|attribute| attribute.path().is_ident("id"))
+    });
+
+    let (index, field) = match id_fields.next() {
+        Some(id_field) => id_field,
+        None => return Ok(None),
+    };
+
+    if let Some((_, duplicate)) = id_fields.next() {
+        return Err(Error::new_spanned(
+            duplicate,
+            "only one field can be marked with #[id]",
+        ));
+    }
+
+    let member = match field.ident {
+        Some(ref ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(index)),
+    };
+
+    Ok(Some((member, field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_the_type_of_the_id_field() {
+        let input: DeriveInput = parse_quote! {
+            struct Request {
+                body: String,
+                #[id]
+                request_id: u64,
+            }
+        };
+
+        let output = expand(&input).unwrap().to_string();
+
+        assert!(output.contains("type Id = u64"));
+        assert!(output.contains("self . request_id"));
+    }
+
+    #[test]
+    fn rejects_structs_without_an_id_field() {
+        let input: DeriveInput = parse_quote! {
+            struct Request {
+                body: String,
+            }
+        };
+
+        let error = expand(&input).unwrap_err();
+
+        assert_eq!(error.to_string(), "no field is marked with #[id]");
+    }
+
+    #[test]
+    fn rejects_more_than_one_id_field() {
+        let input: DeriveInput = parse_quote! {
+            struct Request(#[id] u32, #[id] u32);
+        };
+
+        let error = expand(&input).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "only one field can be marked with #[id]"
+        );
+    }
+
+    #[test]
+    fn reports_every_variant_without_an_id_field() {
+        let input: DeriveInput = parse_quote! {
+            enum Request {
+                Get { #[id] id: u32, key: String },
+                Clear,
+                Set(String, String),
+            }
+        };
+
+        let messages: Vec<_> = expand(&input)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                "variant `Clear` has no field marked with #[id]",
+                "variant `Set` has no field marked with #[id]",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_variants_with_different_id_types() {
+        let input: DeriveInput = parse_quote! {
+            enum Request {
+                Get { #[id] id: u32, key: String },
+                Clear(#[id] u64),
+                Set { #[id] id: u32, key: String, value: String },
+            }
+        };
+
+        let error = expand(&input).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "variant `Clear` has an ID of type `u64` instead of `u32`"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::time::{self, Sleep};
+
+/// How long to wait before accepting again after an error that isn't caused
+/// by a single connection, like running out of file descriptors.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_secs(1);
+
+/// Pauses a listener after accept errors that don't belong to a single
+/// connection, so that the same error isn't produced over and over again.
+#[derive(Default)]
+pub struct AcceptBackoff {
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl AcceptBackoff {
+    /// Waits until the listener can accept again.
+    pub fn poll_ready(&mut self, context: &mut Context) -> Poll<()> {
+        if let Some(ref mut delay) = self.delay {
+            ready!(delay.as_mut().poll(context));
+        }
+
+        self.delay = None;
+
+        Poll::Ready(())
+    }
+
+    /// Pauses the listener if the `result` of accepting failed for a reason
+    /// other than the connection itself.
+    pub fn record<T>(&mut self, result: &io::Result<T>) {
+        if let Err(ref error) = *result {
+            if !is_connection_error(error) {
+                self.delay = Some(Box::pin(time::sleep(ACCEPT_ERROR_DELAY)));
+            }
+        }
+    }
+}
+
+fn is_connection_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+    )
+}
@@ -0,0 +1,265 @@
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::{self, Either, MapOk, Ready};
+use futures::{Sink, TryFutureExt, TryStream};
+use tower_service::Service;
+
+use super::client_error::ClientError;
+use super::id_error_policy::IdErrorPolicy;
+use super::message_with_id::MessageWithId;
+use super::multiplex_client::{MultiplexClient, MultiplexClientFuture};
+use super::sequential_id::SequentialId;
+
+pub type AutoIdMultiplexClientFuture<T, I, Q, R> = Either<
+    MapOk<MultiplexClientFuture<T, (I, Q)>, fn((I, R)) -> R>,
+    Ready<Result<R, ErrorAlias<T, I, Q>>>,
+>;
+
+type ErrorAlias<T, I, Q> =
+    ClientError<<T as TryStream>::Error, <T as Sink<(I, Q)>>::Error>;
+
+/// A multiplexed client that allocates the request IDs itself.
+///
+/// Requests and responses are sent and received as `(id, body)` pairs, but
+/// only the bodies are seen by the caller. An ID is only reused after the
+/// response for its previous request has arrived or has been abandoned.
+pub struct AutoIdMultiplexClient<T, I, Q, R>
+where
+    T: TryStream<Ok = (I, R)> + Sink<(I, Q)> + Unpin,
+    (I, R): MessageWithId<Id = I>,
+    (I, Q): MessageWithId<Id = I>,
+    I: SequentialId + Hash,
+{
+    client: MultiplexClient<T>,
+    next_id: Mutex<I>,
+    _bodies: PhantomData<fn(Q) -> R>,
+}
+
+impl<T, I, Q, R> AutoIdMultiplexClient<T, I, Q, R>
+where
+    T: TryStream<Ok = (I, R)> + Sink<(I, Q)> + Unpin,
+    (I, R): MessageWithId<Id = I>,
+    (I, Q): MessageWithId<Id = I>,
+    I: SequentialId + Hash,
+{
+    pub fn new(transport: T) -> Self {
+        Self::from_client(MultiplexClient::new(transport))
+    }
+
+    pub fn with_timeout(transport: T, timeout: Duration) -> Self {
+        Self::from_client(MultiplexClient::with_timeout(transport, timeout))
+    }
+
+    pub fn call(&self, request: Q) -> AutoIdMultiplexClientFuture<T, I, Q, R> {
+        self.send(request, |client, request| client.call(request))
+    }
+
+    /// Waits until the transport can accept another request.
+    pub fn poll_ready(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), ErrorAlias<T, I, Q>>> {
+        self.client.poll_ready::<(I, Q)>(context)
+    }
+
+    pub fn call_with_timeout(
+        &self,
+        request: Q,
+        timeout: Duration,
+    ) -> AutoIdMultiplexClientFuture<T, I, Q, R> {
+        self.send(request, |client, request| {
+            client.call_with_timeout(request, timeout)
+        })
+    }
+
+    pub fn discarded_responses(&self) -> usize {
+        self.client.discarded_responses()
+    }
+
+    pub fn set_id_error_policy(&self, policy: IdErrorPolicy) {
+        self.client.set_id_error_policy(policy);
+    }
+
+    fn from_client(client: MultiplexClient<T>) -> Self {
+        AutoIdMultiplexClient {
+            client,
+            next_id: Mutex::new(I::first()),
+            _bodies: PhantomData,
+        }
+    }
+
+    fn send<F>(
+        &self,
+        request: Q,
+        call: F,
+    ) -> AutoIdMultiplexClientFuture<T, I, Q, R>
+    where
+        F: FnOnce(
+            &MultiplexClient<T>,
+            (I, Q),
+        ) -> MultiplexClientFuture<T, (I, Q)>,
+    {
+        let mut next_id = self.next_id.lock().expect(
+            "a thread panicked while holding AutoIdMultiplexClient locked",
+        );
+
+        // The lock is held until the ID is registered by the call, so that no
+        // other request can be given the same ID.
+        match self.allocate_id(&next_id) {
+            Some(id) => {
+                *next_id = id.next();
+
+                let response = call(&self.client, (id, request));
+
+                Either::Left(response.map_ok(take_body as fn((I, R)) -> R))
+            }
+            None => Either::Right(future::err(ClientError::NoFreeRequestId)),
+        }
+    }
+
+    fn allocate_id(&self, first_candidate: &I) -> Option<I> {
+        let mut candidate = first_candidate.clone();
+
+        while self.client.is_waiting_for(&candidate) {
+            candidate = candidate.next();
+
+            if candidate == *first_candidate {
+                return None;
+            }
+        }
+
+        Some(candidate)
+    }
+}
+
+impl<T, I, Q, R> Service<Q> for AutoIdMultiplexClient<T, I, Q, R>
+where
+    T: TryStream<Ok = (I, R)> + Sink<(I, Q)> + Unpin,
+    (I, R): MessageWithId<Id = I>,
+    (I, Q): MessageWithId<Id = I>,
+    I: SequentialId + Hash,
+{
+    type Response = R;
+    type Error = ErrorAlias<T, I, Q>;
+    type Future = AutoIdMultiplexClientFuture<T, I, Q, R>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        AutoIdMultiplexClient::poll_ready(self, context)
+    }
+
+    fn call(&mut self, request: Q) -> Self::Future {
+        AutoIdMultiplexClient::call(self, request)
+    }
+}
+
+fn take_body<I, R>(response: (I, R)) -> R {
+    response.1
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::{FutureExt, Stream, StreamExt};
+
+    use super::*;
+    use crate::tests::common::SinkStream;
+
+    #[test]
+    fn ids_are_allocated_and_removed() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, mut out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = AutoIdMultiplexClient::new(transport);
+        let client_service = &client;
+
+        let first_call = client_service.call("first request".to_owned());
+        let second_call = client_service.call("second request".to_owned());
+
+        in_tx.try_send((1u32, "second response".to_owned())).unwrap();
+        in_tx.try_send((0u32, "first response".to_owned())).unwrap();
+
+        let calls = future::try_join(first_call, second_call);
+        let (first_result, second_result) = block_on(calls).unwrap();
+
+        assert_eq!(receive(&mut out_rx), (0, "first request".to_owned()));
+        assert_eq!(receive(&mut out_rx), (1, "second request".to_owned()));
+
+        assert_eq!(first_result, "first response");
+        assert_eq!(second_result, "second response");
+    }
+
+    #[test]
+    fn ids_in_use_are_skipped() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, mut out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = AutoIdMultiplexClient::new(transport);
+        let client_service = &client;
+
+        let first_call = client_service.call("first request".to_owned());
+
+        *client.next_id.lock().unwrap() = 0u8;
+
+        let second_call = client_service.call("second request".to_owned());
+
+        in_tx.try_send((0, "first response".to_owned())).unwrap();
+        in_tx.try_send((1, "second response".to_owned())).unwrap();
+
+        let calls = future::try_join(first_call, second_call);
+        let (first_result, second_result) = block_on(calls).unwrap();
+
+        assert_eq!(receive(&mut out_rx), (0, "first request".to_owned()));
+        assert_eq!(receive(&mut out_rx), (1, "second request".to_owned()));
+
+        assert_eq!(first_result, "first response");
+        assert_eq!(second_result, "second response");
+    }
+
+    #[test]
+    fn running_out_of_ids() {
+        let (_in_tx, in_rx) = mpsc::channel::<(u8, String)>(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = AutoIdMultiplexClient::new(transport);
+        let client_service = &client;
+
+        let pending_calls: Vec<_> = (0..256)
+            .map(|_| client_service.call("request".to_owned()))
+            .collect();
+
+        match block_on(client_service.call("extra request".to_owned())) {
+            Err(ClientError::NoFreeRequestId) => {}
+            _ => panic!("request was sent without a free ID"),
+        }
+
+        drop(pending_calls);
+
+        assert!(!client.client.is_waiting_for(&0));
+    }
+
+    fn receive<S>(stream: &mut S) -> S::Item
+    where
+        S: Stream + Unpin,
+    {
+        match stream.next().now_or_never() {
+            Some(Some(item)) => item,
+            Some(None) => {
+                panic!("failed to receive item from stream: Stream is empty");
+            }
+            None => {
+                panic!("failed to receive item from stream: Not Ready");
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// The delay to wait before the given attempt, which starts at
+/// `initial_delay` before the second attempt and doubles up to `max_delay`.
+///
+/// The delay is then randomly shortened by up to the `jitter` fraction, so
+/// that many clients don't all retry at once. A jitter that isn't a number
+/// is ignored. The first attempt is made immediately.
+pub fn exponential_backoff(
+    initial_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    attempt: u32,
+) -> Duration {
+    if attempt == 0 {
+        return Duration::from_secs(0);
+    }
+
+    let factor = 1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX);
+    let delay = initial_delay
+        .checked_mul(factor)
+        .map(|delay| delay.min(max_delay))
+        .unwrap_or(max_delay);
+
+    let jitter = if jitter.is_nan() { 0.0 } else { jitter.clamp(0.0, 1.0) };
+
+    delay.mul_f64(1.0 - jitter * random_fraction())
+}
+
+/// A random number in `[0, 1)`, which is good enough to spread clients apart.
+pub fn random_fraction() -> f64 {
+    let random_bits = RandomState::new().build_hasher().finish();
+
+    (random_bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_the_delay_up_to_the_maximum() {
+        let delays: Vec<_> = (0..5)
+            .map(|attempt| {
+                exponential_backoff(
+                    Duration::from_millis(10),
+                    Duration::from_millis(30),
+                    0.0,
+                    attempt,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            delays,
+            vec![0, 10, 20, 30, 30]
+                .into_iter()
+                .map(Duration::from_millis)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn ignores_jitter_that_is_not_a_number() {
+        let delay = exponential_backoff(
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+            f64::NAN,
+            1,
+        );
+
+        assert_eq!(delay, Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use tokio::time::Instant;
+use tower_service::Service;
+
+use super::circuit_breaker_policy::{CircuitBreakerPolicy, TripCondition};
+use super::circuit_state::CircuitState;
+use super::client_error::ClientError;
+
+type StateChangeHandler = Box<dyn FnMut(CircuitState, CircuitState) + Send>;
+
+struct Circuit {
+    policy: CircuitBreakerPolicy,
+    state: CircuitState,
+    generation: u64,
+    failures: VecDeque<bool>,
+    opened_at: Instant,
+    trial_calls: usize,
+    successful_trials: usize,
+    changes: Vec<(CircuitState, CircuitState)>,
+}
+
+impl Circuit {
+    fn new(policy: CircuitBreakerPolicy) -> Self {
+        Circuit {
+            policy,
+            state: CircuitState::Closed,
+            generation: 0,
+            failures: VecDeque::new(),
+            opened_at: Instant::now(),
+            trial_calls: 0,
+            successful_trials: 0,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Checks if a call can be sent, returning the generation of the state
+    /// it was sent in.
+    fn admit(&mut self) -> Option<u64> {
+        self.try_half_open();
+
+        match self.state {
+            CircuitState::Closed => Some(self.generation),
+            CircuitState::Open => None,
+            CircuitState::HalfOpen => {
+                if self.trial_calls < self.policy.half_open_calls.get() {
+                    self.trial_calls += 1;
+                    Some(self.generation)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Records the result of a call, unless the state changed since it was
+    /// sent.
+    fn record(&mut self, generation: u64, failed: bool) {
+        if generation != self.generation {
+            return;
+        }
+
+        match self.state {
+            CircuitState::Closed => {
+                let window_size = match self.policy.trip_condition {
+                    TripCondition::ConsecutiveFailures(failures) => {
+                        self.policy.window_size.max(failures).get()
+                    }
+                    TripCondition::ErrorRate(_) => {
+                        self.policy.window_size.get()
+                    }
+                };
+
+                self.failures.push_back(failed);
+
+                while self.failures.len() > window_size {
+                    self.failures.pop_front();
+                }
+
+                if self.should_trip() {
+                    self.change_to(CircuitState::Open);
+                }
+            }
+            CircuitState::HalfOpen if failed => {
+                self.change_to(CircuitState::Open);
+            }
+            CircuitState::HalfOpen => {
+                self.successful_trials += 1;
+
+                if self.successful_trials >= self.policy.half_open_calls.get() {
+                    self.change_to(CircuitState::Closed);
+                }
+            }
+            CircuitState::Open => {}
+        }
+    }
+
+    /// Frees the trial slot of a call that was dropped before finishing.
+    fn abandon(&mut self, generation: u64) {
+        if generation == self.generation
+            && self.state == CircuitState::HalfOpen
+        {
+            self.trial_calls -= 1;
+        }
+    }
+
+    fn is_open(&mut self) -> bool {
+        self.try_half_open();
+        self.state == CircuitState::Open
+    }
+
+    fn try_half_open(&mut self) {
+        let open_until = self.opened_at + self.policy.open_duration;
+
+        if self.state == CircuitState::Open && Instant::now() >= open_until {
+            self.change_to(CircuitState::HalfOpen);
+        }
+    }
+
+    fn should_trip(&self) -> bool {
+        match self.policy.trip_condition {
+            TripCondition::ConsecutiveFailures(failures) => {
+                let failures = failures.get();
+
+                self.failures.len() >= failures
+                    && self.failures.iter().rev().take(failures).all(|&f| f)
+            }
+            TripCondition::ErrorRate(rate) => {
+                let calls = self.failures.len();
+                let failures = self.failures.iter().filter(|&&f| f).count();
+
+                calls > 0
+                    && calls >= self.policy.window_size.get()
+                    && failures as f64 >= rate.get() * calls as f64
+            }
+        }
+    }
+
+    fn change_to(&mut self, state: CircuitState) {
+        self.changes.push((self.state, state));
+        self.state = state;
+        self.generation += 1;
+        self.failures.clear();
+        self.trial_calls = 0;
+        self.successful_trials = 0;
+
+        if state == CircuitState::Open {
+            self.opened_at = Instant::now();
+        }
+    }
+}
+
+struct Shared {
+    circuit: Mutex<Circuit>,
+    on_state_change: Mutex<Option<StateChangeHandler>>,
+}
+
+impl Shared {
+    /// Updates the circuit, and then reports any state changes to the
+    /// handler.
+    ///
+    /// The circuit stays locked while the handler is called, so that the
+    /// changes of concurrent updates are reported in the order they happen.
+    fn update<F, T>(&self, update: F) -> T
+    where
+        F: FnOnce(&mut Circuit) -> T,
+    {
+        let mut circuit = self.lock_circuit();
+        let result = update(&mut circuit);
+
+        if !circuit.changes.is_empty() {
+            let changes = circuit.changes.split_off(0);
+            let mut handler = self.lock_handler();
+
+            if let Some(ref mut handler) = *handler {
+                for (old_state, new_state) in changes {
+                    handler(old_state, new_state);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn lock_circuit(&self) -> MutexGuard<'_, Circuit> {
+        self.circuit
+            .lock()
+            .expect("a thread panicked while holding CircuitBreaker locked")
+    }
+
+    fn lock_handler(&self) -> MutexGuard<'_, Option<StateChangeHandler>> {
+        self.on_state_change.lock().expect(
+            "a thread panicked while handling a CircuitBreaker state change",
+        )
+    }
+}
+
+/// Stops sending calls to a service that keeps failing.
+///
+/// The circuit opens once the recent calls meet the `TripCondition`, and
+/// calls then fail with `ClientError::CircuitOpen` without reaching the
+/// service. After a while, a few trial calls are let through to decide if
+/// the circuit should close again. Any error counts as a failure. Clones
+/// share the same circuit.
+///
+/// A call is only sent if the service was polled ready, so calls that were
+/// readied while the circuit was open still fail after it half opens.
+pub struct CircuitBreaker<S> {
+    service: S,
+    service_ready: bool,
+    shared: Arc<Shared>,
+}
+
+impl<S> CircuitBreaker<S> {
+    pub fn new(service: S, policy: CircuitBreakerPolicy) -> Self {
+        CircuitBreaker {
+            service,
+            service_ready: false,
+            shared: Arc::new(Shared {
+                circuit: Mutex::new(Circuit::new(policy)),
+                on_state_change: Mutex::new(None),
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.shared.update(|circuit| {
+            circuit.try_half_open();
+            circuit.state
+        })
+    }
+
+    /// Sets a `handler` that is called with the old and the new state
+    /// whenever the circuit changes state.
+    ///
+    /// The handler is called while the circuit is locked, so it must not use
+    /// the circuit breaker or any of its clones.
+    pub fn on_state_change<F>(&mut self, handler: F)
+    where
+        F: FnMut(CircuitState, CircuitState) + Send + 'static,
+    {
+        *self.shared.lock_handler() = Some(Box::new(handler));
+    }
+}
+
+impl<S> Clone for CircuitBreaker<S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        CircuitBreaker {
+            service: self.service.clone(),
+            service_ready: false,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S, R, I, O> Service<R> for CircuitBreaker<S>
+where
+    S: Service<R, Error = ClientError<I, O>>,
+{
+    type Response = S::Response;
+    type Error = ClientError<I, O>;
+    type Future = CircuitBreakerFuture<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        // An open circuit is always ready, so that its calls fail right away.
+        if self.service_ready || self.shared.update(Circuit::is_open) {
+            return Poll::Ready(Ok(()));
+        }
+
+        ready!(self.service.poll_ready(context))?;
+
+        self.service_ready = true;
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let shared = self.shared.clone();
+        let admission = if self.service_ready {
+            shared.update(Circuit::admit)
+        } else {
+            None
+        };
+
+        match admission {
+            Some(generation) => {
+                self.service_ready = false;
+
+                CircuitBreakerFuture {
+                    response: Some(Box::pin(self.service.call(request))),
+                    generation: Some(generation),
+                    shared,
+                }
+            }
+            None => CircuitBreakerFuture {
+                response: None,
+                generation: None,
+                shared,
+            },
+        }
+    }
+}
+
+/// The response to a call made through a `CircuitBreaker`, which records
+/// its result in the circuit.
+pub struct CircuitBreakerFuture<F> {
+    response: Option<Pin<Box<F>>>,
+    generation: Option<u64>,
+    shared: Arc<Shared>,
+}
+
+impl<F, T, I, O> Future for CircuitBreakerFuture<F>
+where
+    F: Future<Output = Result<T, ClientError<I, O>>>,
+{
+    type Output = F::Output;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let result = match self.response {
+            Some(ref mut response) => ready!(response.as_mut().poll(context)),
+            None => return Poll::Ready(Err(ClientError::CircuitOpen)),
+        };
+
+        if let Some(generation) = self.generation.take() {
+            let failed = result.is_err();
+
+            self.shared
+                .update(|circuit| circuit.record(generation, failed));
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+impl<F> Drop for CircuitBreakerFuture<F> {
+    fn drop(&mut self) {
+        if let Some(generation) = self.generation.take() {
+            self.shared.update(|circuit| circuit.abandon(generation));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use futures::future;
+    use tokio::time;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+    use crate::error_rate::ErrorRate;
+    use crate::tests::common::{failing_service, FailingServiceError};
+
+    type TestError = FailingServiceError;
+
+    #[tokio::test]
+    async fn opens_after_consecutive_failures() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut breaker = CircuitBreaker::new(
+            failing_service(attempts.clone(), usize::MAX),
+            policy(TripCondition::ConsecutiveFailures(non_zero(3))),
+        );
+
+        for _ in 0..3 {
+            match call(&mut breaker, "request").await {
+                Err(ClientError::ConnectionLost) => {}
+                _ => panic!("call did not reach the service"),
+            }
+        }
+
+        match call(&mut breaker, "request").await {
+            Err(ClientError::CircuitOpen) => {}
+            _ => panic!("call did not fail fast"),
+        }
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(attempts.load(Ordering::Acquire), 3);
+    }
+
+    #[tokio::test]
+    async fn opens_when_the_error_rate_is_reached() {
+        let mut breaker = CircuitBreaker::new(
+            service_fn(|request: &str| match request {
+                "fail" => future::err(TestError::Timeout),
+                _ => future::ok(request.to_uppercase()),
+            }),
+            CircuitBreakerPolicy {
+                window_size: non_zero(4),
+                ..policy(TripCondition::ErrorRate(error_rate(0.5)))
+            },
+        );
+
+        for request in &["fail", "ok", "fail"] {
+            let _ = call(&mut breaker, request).await;
+
+            assert_eq!(breaker.state(), CircuitState::Closed);
+        }
+
+        assert_eq!(call(&mut breaker, "ok").await.unwrap(), "OK");
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn successful_trials_close_the_circuit() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut breaker = CircuitBreaker::new(
+            failing_service(attempts.clone(), 2),
+            policy(TripCondition::ConsecutiveFailures(non_zero(2))),
+        );
+
+        let _ = call(&mut breaker, "first").await;
+        let _ = call(&mut breaker, "second").await;
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(call(&mut breaker, "third").await.unwrap(), "THIRD");
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn failed_trials_open_the_circuit_again() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut breaker = CircuitBreaker::new(
+            failing_service(attempts.clone(), usize::MAX),
+            policy(TripCondition::ConsecutiveFailures(non_zero(1))),
+        );
+
+        let _ = call(&mut breaker, "first").await;
+
+        time::sleep(Duration::from_millis(20)).await;
+
+        let trial = breaker.ready().await.unwrap().call("trial");
+
+        match call(&mut breaker, "second").await {
+            Err(ClientError::CircuitOpen) => {}
+            _ => panic!("more calls than allowed were sent while half open"),
+        }
+
+        assert!(trial.await.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(attempts.load(Ordering::Acquire), 2);
+    }
+
+    #[tokio::test]
+    async fn calls_readied_while_open_fail_after_half_opening() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut breaker = CircuitBreaker::new(
+            failing_service(attempts.clone(), usize::MAX),
+            policy(TripCondition::ConsecutiveFailures(non_zero(1))),
+        );
+
+        let _ = call(&mut breaker, "first").await;
+
+        breaker.ready().await.unwrap();
+        time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        match breaker.call("readied while open").await {
+            Err(ClientError::CircuitOpen) => {}
+            _ => panic!("call was sent to a service that wasn't ready"),
+        }
+
+        assert_eq!(attempts.load(Ordering::Acquire), 1);
+
+        match call(&mut breaker, "trial").await {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("trial call did not reach the service"),
+        }
+
+        assert_eq!(attempts.load(Ordering::Acquire), 2);
+    }
+
+    #[tokio::test]
+    async fn notifies_state_changes() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded_changes = changes.clone();
+        let mut breaker = CircuitBreaker::new(
+            failing_service(Arc::new(AtomicUsize::new(0)), 1),
+            policy(TripCondition::ConsecutiveFailures(non_zero(1))),
+        );
+
+        breaker.on_state_change(move |old_state, new_state| {
+            recorded_changes.lock().unwrap().push((old_state, new_state));
+        });
+
+        let _ = call(&mut breaker, "first").await;
+
+        time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(call(&mut breaker, "second").await.unwrap(), "SECOND");
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                (CircuitState::Closed, CircuitState::Open),
+                (CircuitState::Open, CircuitState::HalfOpen),
+                (CircuitState::HalfOpen, CircuitState::Closed),
+            ]
+        );
+    }
+
+    async fn call<S>(
+        breaker: &mut CircuitBreaker<S>,
+        request: &'static str,
+    ) -> Result<String, TestError>
+    where
+        S: Service<&'static str, Response = String, Error = TestError>,
+    {
+        breaker.ready().await?.call(request).await
+    }
+
+    fn policy(trip_condition: TripCondition) -> CircuitBreakerPolicy {
+        CircuitBreakerPolicy {
+            trip_condition,
+            window_size: non_zero(10),
+            open_duration: Duration::from_millis(10),
+            half_open_calls: non_zero(1),
+        }
+    }
+
+    fn non_zero(value: usize) -> NonZeroUsize {
+        NonZeroUsize::new(value).unwrap()
+    }
+
+    fn error_rate(rate: f64) -> ErrorRate {
+        ErrorRate::new(rate).unwrap()
+    }
+}
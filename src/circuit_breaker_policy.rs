@@ -0,0 +1,45 @@
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use super::error_rate::ErrorRate;
+
+/// When a `CircuitBreaker` opens, based on the results of the most recent
+/// calls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TripCondition {
+    /// The given number of calls failed in a row.
+    ConsecutiveFailures(NonZeroUsize),
+
+    /// At least the given fraction of the calls in the window failed. The
+    /// rate is only checked once the window is full.
+    ErrorRate(ErrorRate),
+}
+
+/// How a `CircuitBreaker` opens and recovers.
+///
+/// The results of the last `window_size` calls are kept to check the
+/// `trip_condition`. Once open, the circuit stays open for `open_duration`,
+/// and is then half open until `half_open_calls` trial calls succeed, which
+/// closes it, or one of them fails, which opens it again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CircuitBreakerPolicy {
+    pub trip_condition: TripCondition,
+    pub window_size: NonZeroUsize,
+    pub open_duration: Duration,
+    pub half_open_calls: NonZeroUsize,
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        CircuitBreakerPolicy {
+            trip_condition: TripCondition::ConsecutiveFailures(non_zero(5)),
+            window_size: non_zero(20),
+            open_duration: Duration::from_secs(10),
+            half_open_calls: non_zero(1),
+        }
+    }
+}
+
+fn non_zero(value: usize) -> NonZeroUsize {
+    NonZeroUsize::new(value).expect("default circuit breaker limit is zero")
+}
@@ -0,0 +1,12 @@
+/// The state of a `CircuitBreaker`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CircuitState {
+    /// Calls are sent to the service, and their results are recorded.
+    Closed,
+
+    /// Calls fail immediately with `ClientError::CircuitOpen`.
+    Open,
+
+    /// A few trial calls are sent to the service, to check if it recovered.
+    HalfOpen,
+}
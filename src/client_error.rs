@@ -5,4 +5,25 @@ pub enum ClientError<I, O> {
 
     #[fail(display = "failed to send request: {}", _0)]
     SendError(#[cause] O),
+
+    #[fail(display = "request ID is already in use by another request")]
+    DuplicateRequestId,
+
+    #[fail(display = "received a response with an unexpected ID")]
+    UnexpectedResponseId,
+
+    #[fail(display = "connection was lost while waiting for a response")]
+    ConnectionLost,
+
+    #[fail(display = "not connected to the server")]
+    NotConnected,
+
+    #[fail(display = "all request IDs are in use")]
+    NoFreeRequestId,
+
+    #[fail(display = "timed out while waiting for a response")]
+    Timeout,
+
+    #[fail(display = "circuit breaker is open, so the request was not sent")]
+    CircuitOpen,
 }
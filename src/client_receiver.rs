@@ -1,6 +1,8 @@
-use std::sync::Arc;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Async, Future, Poll};
+use futures::TryFuture;
 
 use super::client_error::ClientError;
 use super::dispatcher::Dispatcher;
@@ -10,42 +12,38 @@ use super::receiver::Receiver;
 pub struct ClientReceiver<D, S>
 where
     D: Dispatcher,
-    S: Future<Item = D::Seed>,
+    S: TryFuture<Ok = Receiver<D>> + Unpin,
 {
-    dispatcher: Arc<D>,
     sender: S,
 }
 
 impl<D, S> ClientReceiver<D, S>
 where
     D: Dispatcher,
-    S: Future<Item = D::Seed>,
+    S: TryFuture<Ok = Receiver<D>> + Unpin,
 {
-    pub fn new(dispatcher: Arc<D>, sender: S) -> Self {
-        ClientReceiver {
-            dispatcher,
-            sender,
-        }
+    pub fn new(sender: S) -> Self {
+        ClientReceiver { sender }
     }
 }
 
 impl<D, S> Future for ClientReceiver<D, S>
 where
     D: Dispatcher,
-    S: Future<Item = D::Seed>,
+    S: TryFuture<Ok = Receiver<D>> + Unpin,
 {
-    type Item = MapToClientReceiveError<Receiver<D>, D::Error, S::Error>;
-    type Error = ClientError<D::Error, S::Error>;
+    type Output = Result<
+        MapToClientReceiveError<Receiver<D>, D::Error, S::Error>,
+        ClientError<D::Error, S::Error>,
+    >;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let seed = try_ready!(
-            self.sender
-                .poll()
-                .map_err(ClientError::SendError)
-        );
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let receiver = ready!(Pin::new(&mut self.sender).try_poll(context))
+            .map_err(ClientError::SendError)?;
 
-        Ok(Async::Ready(
-            Dispatcher::spawn_receiver(self.dispatcher.clone(), seed).into(),
-        ))
+        Poll::Ready(Ok(receiver.into()))
     }
 }
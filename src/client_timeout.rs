@@ -0,0 +1,68 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::time::{self, Sleep};
+
+use super::client_error::ClientError;
+
+pub struct ClientTimeout<F> {
+    future: F,
+    duration: Option<Duration>,
+    timeout: Option<Pin<Box<Sleep>>>,
+}
+
+impl<F> ClientTimeout<F> {
+    pub fn new(future: F, duration: Duration) -> Self {
+        ClientTimeout {
+            future,
+            duration: Some(duration),
+            timeout: None,
+        }
+    }
+
+    pub fn without_timeout(future: F) -> Self {
+        ClientTimeout {
+            future,
+            duration: None,
+            timeout: None,
+        }
+    }
+
+    /// Starts the timer on the first poll, so that it runs on the runtime of
+    /// the task waiting for the response.
+    fn start_timeout(&mut self) {
+        if let Some(duration) = self.duration.take() {
+            self.timeout = Some(Box::pin(time::sleep(duration)));
+        }
+    }
+}
+
+impl<F, T, I, O> Future for ClientTimeout<F>
+where
+    F: Future<Output = Result<T, ClientError<I, O>>> + Unpin,
+{
+    type Output = F::Output;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        if let Poll::Ready(response) = Pin::new(&mut self.future).poll(context)
+        {
+            return Poll::Ready(response);
+        }
+
+        self.start_timeout();
+
+        match self.timeout {
+            Some(ref mut timeout) => {
+                ready!(timeout.as_mut().poll(context));
+
+                Poll::Ready(Err(ClientError::Timeout))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
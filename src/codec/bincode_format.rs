@@ -0,0 +1,26 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::serde_format::SerdeFormat;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bincode;
+
+impl SerdeFormat for Bincode {
+    type SerializeError = bincode::Error;
+    type DeserializeError = bincode::Error;
+
+    fn serialize<T>(value: &T) -> Result<Vec<u8>, Self::SerializeError>
+    where
+        T: Serialize,
+    {
+        bincode::serialize(value)
+    }
+
+    fn deserialize<T>(bytes: &[u8]) -> Result<T, Self::DeserializeError>
+    where
+        T: DeserializeOwned,
+    {
+        bincode::deserialize(bytes)
+    }
+}
@@ -0,0 +1,33 @@
+use std::io;
+
+use ciborium::{de, ser};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::serde_format::SerdeFormat;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cbor;
+
+impl SerdeFormat for Cbor {
+    type SerializeError = ser::Error<io::Error>;
+    type DeserializeError = de::Error<io::Error>;
+
+    fn serialize<T>(value: &T) -> Result<Vec<u8>, Self::SerializeError>
+    where
+        T: Serialize,
+    {
+        let mut bytes = Vec::new();
+
+        ciborium::into_writer(value, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize<T>(bytes: &[u8]) -> Result<T, Self::DeserializeError>
+    where
+        T: DeserializeOwned,
+    {
+        ciborium::from_reader(bytes)
+    }
+}
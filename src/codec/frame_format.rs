@@ -0,0 +1,56 @@
+/// The width of the length prefix of each frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LengthField {
+    U16,
+    U32,
+
+    /// An unsigned LEB128 variable-length integer, which has no byte order.
+    Varint,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ByteOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+/// How frames are delimited by a `LengthDelimitedCodec`.
+///
+/// Each frame starts with its length in bytes, encoded as a `length_field`
+/// in `byte_order`, followed by the frame's contents. Frames larger than
+/// `max_frame_size` are rejected both when encoding and decoding, so that a
+/// peer can't make the codec buffer an arbitrary amount of data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FrameFormat {
+    pub length_field: LengthField,
+    pub byte_order: ByteOrder,
+    pub max_frame_size: usize,
+}
+
+impl FrameFormat {
+    /// The size limit imposed by the format, which is the smallest of the
+    /// `max_frame_size` and the largest length the `length_field` can hold.
+    pub fn frame_size_limit(&self) -> usize {
+        let field_limit = match self.length_field {
+            LengthField::U16 => u64::from(u16::MAX),
+            LengthField::U32 => u64::from(u32::MAX),
+            LengthField::Varint => u64::MAX,
+        };
+
+        if field_limit < self.max_frame_size as u64 {
+            field_limit as usize
+        } else {
+            self.max_frame_size
+        }
+    }
+}
+
+impl Default for FrameFormat {
+    fn default() -> Self {
+        FrameFormat {
+            length_field: LengthField::U32,
+            byte_order: ByteOrder::BigEndian,
+            max_frame_size: 8 * 1024 * 1024,
+        }
+    }
+}
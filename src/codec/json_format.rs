@@ -0,0 +1,26 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::serde_format::SerdeFormat;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+impl SerdeFormat for Json {
+    type SerializeError = serde_json::Error;
+    type DeserializeError = serde_json::Error;
+
+    fn serialize<T>(value: &T) -> Result<Vec<u8>, Self::SerializeError>
+    where
+        T: Serialize,
+    {
+        serde_json::to_vec(value)
+    }
+
+    fn deserialize<T>(bytes: &[u8]) -> Result<T, Self::DeserializeError>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(bytes)
+    }
+}
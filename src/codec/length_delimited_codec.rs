@@ -0,0 +1,381 @@
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::frame_format::{ByteOrder, FrameFormat, LengthField};
+use super::length_delimited_error::LengthDelimitedError;
+
+const MAX_VARINT_SIZE: usize = 10;
+
+/// A codec for frames prefixed by their length, whose contents are decoded
+/// into messages by an inner codec.
+///
+/// The inner codec receives a buffer with the whole frame, so it must decode
+/// a message from it without waiting for more data, and it must consume all
+/// of the frame's bytes. `RawFrames` can be used as the inner codec to receive
+/// the frames' bytes unchanged.
+#[derive(Clone, Debug)]
+pub struct LengthDelimitedCodec<C> {
+    inner: C,
+    format: FrameFormat,
+}
+
+impl<C> LengthDelimitedCodec<C> {
+    pub fn new(inner: C) -> Self {
+        Self::with_format(inner, FrameFormat::default())
+    }
+
+    pub fn with_format(inner: C, format: FrameFormat) -> Self {
+        LengthDelimitedCodec { inner, format }
+    }
+
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+
+    pub fn set_format(&mut self, format: FrameFormat) {
+        self.format = format;
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    /// Reads the length prefix at the start of the `buffer`, returning its
+    /// size and the length it holds, or `None` if it isn't complete yet.
+    fn read_length<E>(
+        &self,
+        buffer: &[u8],
+    ) -> Result<Option<(usize, u64)>, LengthDelimitedError<E>> {
+        let (size, length) = match self.format.length_field {
+            LengthField::U16 => match read_fixed(buffer, 2, self.format) {
+                Some(length) => (2, length),
+                None => return Ok(None),
+            },
+            LengthField::U32 => match read_fixed(buffer, 4, self.format) {
+                Some(length) => (4, length),
+                None => return Ok(None),
+            },
+            LengthField::Varint => match read_varint(buffer)? {
+                Some(prefix) => prefix,
+                None => return Ok(None),
+            },
+        };
+
+        let limit = self.format.frame_size_limit();
+
+        if length > limit as u64 {
+            Err(LengthDelimitedError::FrameTooLarge { size: length, limit })
+        } else {
+            Ok(Some((size, length)))
+        }
+    }
+
+    fn write_length(&self, length: usize, buffer: &mut BytesMut) {
+        buffer.reserve(MAX_VARINT_SIZE + length);
+
+        match (self.format.length_field, self.format.byte_order) {
+            (LengthField::U16, ByteOrder::BigEndian) => {
+                buffer.put_u16(length as u16)
+            }
+            (LengthField::U16, ByteOrder::LittleEndian) => {
+                buffer.put_u16_le(length as u16)
+            }
+            (LengthField::U32, ByteOrder::BigEndian) => {
+                buffer.put_u32(length as u32)
+            }
+            (LengthField::U32, ByteOrder::LittleEndian) => {
+                buffer.put_u32_le(length as u32)
+            }
+            (LengthField::Varint, _) => {
+                let mut remaining = length as u64;
+
+                while remaining >= 0x80 {
+                    buffer.put_u8((remaining as u8 & 0x7f) | 0x80);
+                    remaining >>= 7;
+                }
+
+                buffer.put_u8(remaining as u8);
+            }
+        }
+    }
+}
+
+impl<C> Default for LengthDelimitedCodec<C>
+where
+    C: Default,
+{
+    fn default() -> Self {
+        Self::new(C::default())
+    }
+}
+
+fn read_fixed(buffer: &[u8], size: usize, format: FrameFormat) -> Option<u64> {
+    if buffer.len() < size {
+        return None;
+    }
+
+    let bytes = &buffer[..size];
+    let accumulate = |length, &byte| (length << 8) | u64::from(byte);
+
+    match format.byte_order {
+        ByteOrder::BigEndian => Some(bytes.iter().fold(0, accumulate)),
+        ByteOrder::LittleEndian => Some(bytes.iter().rev().fold(0, accumulate)),
+    }
+}
+
+fn read_varint<E>(
+    buffer: &[u8],
+) -> Result<Option<(usize, u64)>, LengthDelimitedError<E>> {
+    let mut length = 0;
+
+    for (index, &byte) in buffer.iter().take(MAX_VARINT_SIZE).enumerate() {
+        let bits = u64::from(byte & 0x7f);
+
+        if index == MAX_VARINT_SIZE - 1 && bits > 1 {
+            return Err(LengthDelimitedError::MalformedLength);
+        }
+
+        length |= bits << (7 * index);
+
+        if byte & 0x80 == 0 {
+            return Ok(Some((index + 1, length)));
+        }
+    }
+
+    if buffer.len() >= MAX_VARINT_SIZE {
+        Err(LengthDelimitedError::MalformedLength)
+    } else {
+        Ok(None)
+    }
+}
+
+impl<C> Decoder for LengthDelimitedCodec<C>
+where
+    C: Decoder,
+{
+    type Item = C::Item;
+    type Error = LengthDelimitedError<C::Error>;
+
+    fn decode(
+        &mut self,
+        buffer: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        let (prefix_size, frame_size) = match self.read_length(buffer)? {
+            Some((prefix_size, length)) => (prefix_size, length as usize),
+            None => return Ok(None),
+        };
+
+        if buffer.len() < prefix_size + frame_size {
+            let missing = prefix_size + frame_size - buffer.len();
+
+            buffer.reserve(missing);
+            return Ok(None);
+        }
+
+        buffer.advance(prefix_size);
+
+        let mut frame = buffer.split_to(frame_size);
+
+        match self.inner.decode(&mut frame) {
+            Ok(Some(_)) if !frame.is_empty() => {
+                Err(LengthDelimitedError::TrailingBytes(frame.len()))
+            }
+            Ok(Some(message)) => Ok(Some(message)),
+            Ok(None) => Err(LengthDelimitedError::IncompleteMessage),
+            Err(error) => Err(LengthDelimitedError::MessageError(error)),
+        }
+    }
+}
+
+impl<C, I> Encoder<I> for LengthDelimitedCodec<C>
+where
+    C: Encoder<I>,
+{
+    type Error = LengthDelimitedError<C::Error>;
+
+    fn encode(
+        &mut self,
+        message: I,
+        buffer: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let mut frame = BytesMut::new();
+
+        self.inner
+            .encode(message, &mut frame)
+            .map_err(LengthDelimitedError::MessageError)?;
+
+        let limit = self.format.frame_size_limit();
+
+        if frame.len() > limit {
+            return Err(LengthDelimitedError::FrameTooLarge {
+                size: frame.len() as u64,
+                limit,
+            });
+        }
+
+        self.write_length(frame.len(), buffer);
+        buffer.extend_from_slice(&frame);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::codec::raw_frames::RawFrames;
+    use crate::tests::common::LineCodec;
+
+    #[test]
+    fn prefixes_frames_with_their_length() {
+        let cases = vec![
+            (LengthField::U16, ByteOrder::BigEndian, vec![0, 3]),
+            (LengthField::U16, ByteOrder::LittleEndian, vec![3, 0]),
+            (LengthField::U32, ByteOrder::BigEndian, vec![0, 0, 0, 3]),
+            (LengthField::U32, ByteOrder::LittleEndian, vec![3, 0, 0, 0]),
+            (LengthField::Varint, ByteOrder::BigEndian, vec![3]),
+        ];
+
+        for (length_field, byte_order, prefix) in cases {
+            let mut codec = codec_with(length_field, byte_order, 1024);
+            let mut buffer = BytesMut::new();
+
+            codec.encode(Bytes::from("abc"), &mut buffer).unwrap();
+
+            let mut expected = prefix;
+            expected.extend_from_slice(b"abc");
+
+            assert_eq!(buffer.to_vec(), expected);
+            assert_eq!(
+                codec.decode(&mut buffer).unwrap(),
+                Some(Bytes::from("abc"))
+            );
+            assert!(buffer.is_empty());
+        }
+    }
+
+    #[test]
+    fn encodes_multi_byte_varints() {
+        let mut codec =
+            codec_with(LengthField::Varint, ByteOrder::BigEndian, 1024);
+        let frame = Bytes::from(vec![7; 300]);
+        let mut buffer = BytesMut::new();
+
+        codec.encode(frame.clone(), &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..2], &[0xac, 0x02]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame));
+    }
+
+    #[test]
+    fn waits_for_complete_frames() {
+        let mut codec = LengthDelimitedCodec::new(RawFrames);
+        let mut buffer = BytesMut::from(&[0, 0][..]);
+
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+
+        buffer.extend_from_slice(&[0, 5, b'a', b'b']);
+
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+
+        buffer.extend_from_slice(b"cde");
+
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(Bytes::from("abcde"))
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let mut codec = codec_with(LengthField::U32, ByteOrder::BigEndian, 4);
+        let mut buffer = BytesMut::from(&[0, 0, 0, 5][..]);
+
+        match codec.decode(&mut buffer) {
+            Err(LengthDelimitedError::FrameTooLarge { size: 5, limit: 4 }) => {}
+            _ => panic!("decoded an oversized frame"),
+        }
+
+        match codec.encode(Bytes::from("abcde"), &mut BytesMut::new()) {
+            Err(LengthDelimitedError::FrameTooLarge { size: 5, limit: 4 }) => {}
+            _ => panic!("encoded an oversized frame"),
+        }
+    }
+
+    #[test]
+    fn limits_frames_to_what_the_length_field_holds() {
+        let mut codec =
+            codec_with(LengthField::U16, ByteOrder::BigEndian, 1 << 20);
+        let frame = Bytes::from(vec![0; 1 << 16]);
+
+        match codec.encode(frame, &mut BytesMut::new()) {
+            Err(LengthDelimitedError::FrameTooLarge { limit: 65_535, .. }) => {}
+            _ => panic!("encoded a frame with a truncated length"),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_varints() {
+        let mut codec =
+            codec_with(LengthField::Varint, ByteOrder::BigEndian, 1024);
+        let mut buffer = BytesMut::from(&[0xff; 10][..]);
+
+        match codec.decode(&mut buffer) {
+            Err(LengthDelimitedError::MalformedLength) => {}
+            _ => panic!("decoded a malformed length"),
+        }
+    }
+
+    #[test]
+    fn decodes_messages_with_the_inner_codec() {
+        let mut codec = LengthDelimitedCodec::new(LineCodec);
+        let mut buffer = BytesMut::new();
+
+        codec.encode("message".to_owned(), &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..4], &[0, 0, 0, 8]);
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some("message".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_frames_with_incomplete_messages() {
+        let mut codec = LengthDelimitedCodec::new(LineCodec);
+        let mut buffer = BytesMut::from(&[0, 0, 0, 2, b'h', b'i'][..]);
+
+        match codec.decode(&mut buffer) {
+            Err(LengthDelimitedError::IncompleteMessage) => {}
+            _ => panic!("decoded an incomplete message"),
+        }
+    }
+
+    #[test]
+    fn rejects_frames_with_trailing_bytes() {
+        let mut codec = LengthDelimitedCodec::new(LineCodec);
+        let frame = [0, 0, 0, 5, b'h', b'i', b'\n', 0, 0];
+        let mut buffer = BytesMut::from(&frame[..]);
+
+        match codec.decode(&mut buffer) {
+            Err(LengthDelimitedError::TrailingBytes(2)) => {}
+            _ => panic!("ignored the bytes after a message"),
+        }
+    }
+
+    fn codec_with(
+        length_field: LengthField,
+        byte_order: ByteOrder,
+        max_frame_size: usize,
+    ) -> LengthDelimitedCodec<RawFrames> {
+        let format = FrameFormat {
+            length_field,
+            byte_order,
+            max_frame_size,
+        };
+
+        LengthDelimitedCodec::with_format(RawFrames, format)
+    }
+}
@@ -0,0 +1,44 @@
+use std::io;
+
+use super::super::transient_error::TransientError;
+
+#[derive(Debug, Fail)]
+pub enum LengthDelimitedError<E> {
+    #[fail(
+        display = "frame of {} bytes exceeds the limit of {} bytes",
+        size,
+        limit
+    )]
+    FrameTooLarge { size: u64, limit: usize },
+
+    #[fail(display = "frame length prefix is malformed")]
+    MalformedLength,
+
+    #[fail(display = "frame does not contain a complete message")]
+    IncompleteMessage,
+
+    #[fail(display = "frame has {} bytes left after its message", _0)]
+    TrailingBytes(usize),
+
+    #[fail(display = "failed to encode or decode a frame's message: {}", _0)]
+    MessageError(#[cause] E),
+
+    #[fail(display = "I/O error while transferring frames: {}", _0)]
+    IoError(#[cause] io::Error),
+}
+
+impl<E> From<io::Error> for LengthDelimitedError<E> {
+    fn from(error: io::Error) -> Self {
+        LengthDelimitedError::IoError(error)
+    }
+}
+
+// Only failures to transfer the frames are caused by the connection.
+impl<E> TransientError for LengthDelimitedError<E> {
+    fn is_transient(&self) -> bool {
+        match *self {
+            LengthDelimitedError::IoError(ref error) => error.is_transient(),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::serde_format::SerdeFormat;
+
+/// The MessagePack format, with structs encoded as maps so that fields can
+/// be added or reordered without breaking older peers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessagePack;
+
+impl SerdeFormat for MessagePack {
+    type SerializeError = rmp_serde::encode::Error;
+    type DeserializeError = rmp_serde::decode::Error;
+
+    fn serialize<T>(value: &T) -> Result<Vec<u8>, Self::SerializeError>
+    where
+        T: Serialize,
+    {
+        rmp_serde::to_vec_named(value)
+    }
+
+    fn deserialize<T>(bytes: &[u8]) -> Result<T, Self::DeserializeError>
+    where
+        T: DeserializeOwned,
+    {
+        rmp_serde::from_slice(bytes)
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(feature = "bincode")]
+mod bincode_format;
+#[cfg(feature = "cbor")]
+mod cbor_format;
+mod frame_format;
+#[cfg(feature = "json")]
+mod json_format;
+mod length_delimited_codec;
+mod length_delimited_error;
+#[cfg(feature = "msgpack")]
+mod message_pack_format;
+mod multiplex_codec;
+mod raw_frames;
+#[cfg(feature = "serde-codec")]
+mod serde_error;
+#[cfg(feature = "serde-codec")]
+mod serde_format;
+#[cfg(feature = "serde-codec")]
+mod serde_messages;
+mod wire_id;
+
+#[cfg(feature = "bincode")]
+pub use self::bincode_format::Bincode;
+#[cfg(feature = "cbor")]
+pub use self::cbor_format::Cbor;
+pub use self::frame_format::{ByteOrder, FrameFormat, LengthField};
+#[cfg(feature = "json")]
+pub use self::json_format::Json;
+pub use self::length_delimited_codec::LengthDelimitedCodec;
+pub use self::length_delimited_error::LengthDelimitedError;
+#[cfg(feature = "msgpack")]
+pub use self::message_pack_format::MessagePack;
+pub use self::multiplex_codec::MultiplexCodec;
+pub use self::raw_frames::RawFrames;
+#[cfg(feature = "serde-codec")]
+pub use self::serde_error::SerdeError;
+#[cfg(feature = "serde-codec")]
+pub use self::serde_format::SerdeFormat;
+#[cfg(feature = "serde-codec")]
+pub use self::serde_messages::SerdeMessages;
+pub use self::wire_id::WireId;
+
+/// A codec for JSON messages, each in a length-delimited frame.
+#[cfg(feature = "json")]
+pub type JsonCodec<O, I> = LengthDelimitedCodec<SerdeMessages<Json, O, I>>;
+
+/// A codec for bincode messages, each in a length-delimited frame.
+#[cfg(feature = "bincode")]
+pub type BincodeCodec<O, I> =
+    LengthDelimitedCodec<SerdeMessages<Bincode, O, I>>;
+
+/// A codec for MessagePack messages, each in a length-delimited frame.
+#[cfg(feature = "msgpack")]
+pub type MessagePackCodec<O, I> =
+    LengthDelimitedCodec<SerdeMessages<MessagePack, O, I>>;
+
+/// A codec for CBOR messages, each in a length-delimited frame.
+#[cfg(feature = "cbor")]
+pub type CborCodec<O, I> = LengthDelimitedCodec<SerdeMessages<Cbor, O, I>>;
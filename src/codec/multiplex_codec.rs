@@ -0,0 +1,236 @@
+use std::io;
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::wire_id::WireId;
+
+/// Turns a pipeline codec into a multiplexed one, by prefixing each message
+/// of the inner codec with its request ID.
+///
+/// Messages are decoded and encoded as `(id, message)` pairs, which already
+/// implement `MessageWithId`, so the inner codec's message types can be used
+/// as is with the multiplexed clients and servers.
+#[derive(Clone, Debug)]
+pub struct MultiplexCodec<C, I = u32> {
+    inner: C,
+    pending_id: Option<I>,
+}
+
+impl<C, I> MultiplexCodec<C, I> {
+    pub fn new(inner: C) -> Self {
+        MultiplexCodec {
+            inner,
+            pending_id: None,
+        }
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C, I> MultiplexCodec<C, I>
+where
+    I: WireId,
+{
+    /// Takes the ID of the message being decoded, reading it from the
+    /// `buffer` if it hasn't been read yet.
+    ///
+    /// The ID is kept aside while the message is incomplete, so that the
+    /// inner codec never sees the header.
+    fn take_id(&mut self, buffer: &mut BytesMut) -> Option<I> {
+        if self.pending_id.is_none() && buffer.len() >= I::SIZE {
+            let header = buffer.split_to(I::SIZE);
+
+            self.pending_id = Some(I::read_from(&header));
+        }
+
+        self.pending_id.take()
+    }
+}
+
+impl<C, I> Decoder for MultiplexCodec<C, I>
+where
+    C: Decoder,
+    I: WireId,
+{
+    type Item = (I, C::Item);
+    type Error = C::Error;
+
+    fn decode(
+        &mut self,
+        buffer: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        let id = match self.take_id(buffer) {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        match self.inner.decode(buffer)? {
+            Some(message) => Ok(Some((id, message))),
+            None => {
+                self.pending_id = Some(id);
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(
+        &mut self,
+        buffer: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        let id = match self.take_id(buffer) {
+            Some(id) => id,
+            None if buffer.is_empty() => return Ok(None),
+            None => return Err(truncated_message().into()),
+        };
+
+        match self.inner.decode_eof(buffer)? {
+            Some(message) => Ok(Some((id, message))),
+            None => Err(truncated_message().into()),
+        }
+    }
+}
+
+impl<C, I, M> Encoder<(I, M)> for MultiplexCodec<C, I>
+where
+    C: Encoder<M>,
+    I: WireId,
+{
+    type Error = C::Error;
+
+    fn encode(
+        &mut self,
+        (id, message): (I, M),
+        buffer: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        id.write_to(buffer);
+        self.inner.encode(message, buffer)
+    }
+}
+
+fn truncated_message() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed in the middle of a message",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tcp")]
+    use std::net::TcpListener;
+    #[cfg(feature = "tcp")]
+    use std::task::{Context, Poll};
+
+    #[cfg(feature = "tcp")]
+    use futures::future::{self, Ready};
+    #[cfg(feature = "tcp")]
+    use futures::stream;
+    #[cfg(feature = "tcp")]
+    use tower_service::Service;
+
+    use super::*;
+    #[cfg(feature = "tcp")]
+    use crate::tcp::{MultiplexTcpClient, MultiplexTcpListenerServer};
+    use crate::tests::common::LineCodec;
+
+    #[test]
+    fn prefixes_messages_with_their_id() {
+        let mut codec = MultiplexCodec::<_, u16>::new(LineCodec);
+        let mut buffer = BytesMut::new();
+
+        codec.encode((258, "message".to_owned()), &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..], b"\x01\x02message\n");
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some((258, "message".to_owned()))
+        );
+    }
+
+    #[test]
+    fn keeps_the_id_of_incomplete_messages() {
+        let mut codec = MultiplexCodec::<_, u32>::new(LineCodec);
+        let mut buffer = BytesMut::from(&b"\x00\x00"[..]);
+
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+
+        buffer.extend_from_slice(b"\x00\x07mess");
+
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+
+        buffer.extend_from_slice(b"age\n");
+
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some((7, "message".to_owned()))
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn rejects_ids_without_a_message_at_the_end_of_the_stream() {
+        let mut codec = MultiplexCodec::<_, u32>::new(LineCodec);
+        let mut buffer = BytesMut::from(&b"\x00\x00\x00\x07"[..]);
+
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+
+        match codec.decode_eof(&mut buffer) {
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => {}
+            _ => panic!("accepted a truncated message"),
+        }
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn multiplexes_a_pipeline_protocol() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+
+        let services = stream::iter(vec![Ok::<_, ()>(IdToUpperService)]);
+        let server = MultiplexTcpListenerServer::listen(
+            services,
+            &address,
+            MultiplexCodec::new(LineCodec),
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        let codec = MultiplexCodec::new(LineCodec);
+        let client = MultiplexTcpClient::connect(&address, codec);
+
+        let responses = future::try_join(
+            client.call((1, "first".to_owned())),
+            client.call((2, "second".to_owned())),
+        );
+
+        assert_eq!(
+            responses.await.unwrap(),
+            ((1, "FIRST".to_owned()), (2, "SECOND".to_owned()))
+        );
+    }
+
+    #[cfg(feature = "tcp")]
+    struct IdToUpperService;
+
+    #[cfg(feature = "tcp")]
+    impl Service<(u32, String)> for IdToUpperService {
+        type Response = (u32, String);
+        type Error = ();
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(
+            &mut self,
+            _context: &mut Context,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, (id, text): (u32, String)) -> Self::Future {
+            future::ok((id, text.to_uppercase()))
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::io;
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A message codec that hands out the bytes of each frame unchanged, for
+/// using a `LengthDelimitedCodec` without decoding the frames' contents.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RawFrames;
+
+impl Decoder for RawFrames {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn decode(&mut self, frame: &mut BytesMut) -> io::Result<Option<Bytes>> {
+        let length = frame.len();
+
+        Ok(Some(frame.split_to(length).freeze()))
+    }
+}
+
+impl Encoder<Bytes> for RawFrames {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        frame: Bytes,
+        buffer: &mut BytesMut,
+    ) -> io::Result<()> {
+        buffer.extend_from_slice(&frame);
+
+        Ok(())
+    }
+}
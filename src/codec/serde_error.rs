@@ -0,0 +1,27 @@
+use std::io;
+
+use super::super::transient_error::TransientError;
+
+#[derive(Debug, Fail)]
+pub enum SerdeError<E> {
+    #[fail(display = "failed to serialize or deserialize message: {}", _0)]
+    FormatError(#[cause] E),
+
+    #[fail(display = "I/O error while transferring message: {}", _0)]
+    IoError(#[cause] io::Error),
+}
+
+impl<E> From<io::Error> for SerdeError<E> {
+    fn from(error: io::Error) -> Self {
+        SerdeError::IoError(error)
+    }
+}
+
+impl<E> TransientError for SerdeError<E> {
+    fn is_transient(&self) -> bool {
+        match *self {
+            SerdeError::IoError(ref error) => error.is_transient(),
+            SerdeError::FormatError(_) => false,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A serialization format used by `SerdeMessages` to encode the contents of
+/// each frame.
+pub trait SerdeFormat {
+    type SerializeError;
+    type DeserializeError;
+
+    fn serialize<T>(value: &T) -> Result<Vec<u8>, Self::SerializeError>
+    where
+        T: Serialize;
+
+    fn deserialize<T>(bytes: &[u8]) -> Result<T, Self::DeserializeError>
+    where
+        T: DeserializeOwned;
+}
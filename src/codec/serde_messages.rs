@@ -0,0 +1,321 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use bytes::BytesMut;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::serde_error::SerdeError;
+use super::serde_format::SerdeFormat;
+
+/// A message codec that serializes outgoing `O` messages and deserializes
+/// incoming `I` messages in the format `F`.
+///
+/// Each message takes up a whole frame, so this codec is meant to be used
+/// inside a `LengthDelimitedCodec`. A client sends requests and receives
+/// responses, while a server does the opposite, so their codecs have the
+/// two message types swapped.
+pub struct SerdeMessages<F, O, I> {
+    _types: PhantomData<fn(O) -> (F, I)>,
+}
+
+impl<F, O, I> SerdeMessages<F, O, I> {
+    pub fn new() -> Self {
+        SerdeMessages {
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<F, O, I> Clone for SerdeMessages<F, O, I> {
+    fn clone(&self) -> Self {
+        SerdeMessages::new()
+    }
+}
+
+impl<F, O, I> Default for SerdeMessages<F, O, I> {
+    fn default() -> Self {
+        SerdeMessages::new()
+    }
+}
+
+impl<F, O, I> fmt::Debug for SerdeMessages<F, O, I> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("SerdeMessages")
+    }
+}
+
+impl<F, O, I> Decoder for SerdeMessages<F, O, I>
+where
+    F: SerdeFormat,
+    I: DeserializeOwned,
+{
+    type Item = I;
+    type Error = SerdeError<F::DeserializeError>;
+
+    fn decode(
+        &mut self,
+        frame: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        let bytes = frame.split();
+
+        F::deserialize(&bytes)
+            .map(Some)
+            .map_err(SerdeError::FormatError)
+    }
+}
+
+impl<F, O, I> Encoder<O> for SerdeMessages<F, O, I>
+where
+    F: SerdeFormat,
+    O: Serialize,
+{
+    type Error = SerdeError<F::SerializeError>;
+
+    fn encode(
+        &mut self,
+        message: O,
+        buffer: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let bytes =
+            F::serialize(&message).map_err(SerdeError::FormatError)?;
+
+        buffer.extend_from_slice(&bytes);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use std::net::{SocketAddr, TcpListener};
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use std::task::{Context, Poll};
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use futures::future::{self, FutureExt, Ready};
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use futures::stream;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use tower_service::Service;
+
+    use super::*;
+    #[cfg(feature = "bincode")]
+    use crate::codec::bincode_format::Bincode;
+    #[cfg(feature = "cbor")]
+    use crate::codec::cbor_format::Cbor;
+    #[cfg(feature = "json")]
+    use crate::codec::json_format::Json;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use crate::codec::JsonCodec;
+    use crate::codec::length_delimited_codec::LengthDelimitedCodec;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use crate::codec::length_delimited_error::LengthDelimitedError;
+    #[cfg(feature = "msgpack")]
+    use crate::codec::message_pack_format::MessagePack;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use crate::codec::raw_frames::RawFrames;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use crate::client_error::ClientError;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use crate::listening_server_error::ListeningServerError;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use crate::server_error::ServerError;
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    use crate::tcp::{PipelineTcpClient, PipelineTcpListenerServer};
+
+    #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+    struct Greeting {
+        name: String,
+        times: u32,
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn round_trips_json_messages() {
+        assert_round_trip::<Json>();
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn round_trips_bincode_messages() {
+        assert_round_trip::<Bincode>();
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn round_trips_message_pack_messages() {
+        assert_round_trip::<MessagePack>();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn round_trips_cbor_messages() {
+        assert_round_trip::<Cbor>();
+    }
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    #[tokio::test]
+    async fn serves_typed_messages_over_tcp() {
+        let address = free_address();
+
+        let services = stream::iter(vec![Ok::<_, ()>(GreetingService)]);
+        let server = PipelineTcpListenerServer::listen(
+            services,
+            &address,
+            JsonCodec::<String, Greeting>::default(),
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        let client = PipelineTcpClient::connect(
+            &address,
+            JsonCodec::<Greeting, String>::default(),
+        );
+        let greeting = Greeting {
+            name: "world".to_owned(),
+            times: 2,
+        };
+
+        let response = client.call(greeting).await.unwrap();
+
+        assert_eq!(response, "hello world hello world");
+    }
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    #[tokio::test]
+    async fn reports_malformed_responses_to_the_client() {
+        let address = free_address();
+
+        let services = stream::iter(vec![Ok::<_, ()>(MalformedJsonService)]);
+        let server = PipelineTcpListenerServer::listen(
+            services,
+            &address,
+            LengthDelimitedCodec::new(RawFrames),
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        let client = PipelineTcpClient::connect(
+            &address,
+            JsonCodec::<String, Greeting>::default(),
+        );
+
+        match client.call("request".to_owned()).await {
+            Err(ClientError::ReceiveError(
+                LengthDelimitedError::MessageError(SerdeError::FormatError(_)),
+            )) => {}
+            _ => panic!("client accepted a malformed response"),
+        }
+    }
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    #[tokio::test]
+    async fn reports_malformed_requests_to_the_server() {
+        let address = free_address();
+
+        let services = stream::iter(vec![Ok::<_, ()>(GreetingService)]);
+        let mut server = PipelineTcpListenerServer::listen(
+            services,
+            &address,
+            JsonCodec::<String, Greeting>::default(),
+        ).unwrap();
+
+        server.fail_fast();
+
+        let client = PipelineTcpClient::connect(
+            &address,
+            LengthDelimitedCodec::new(RawFrames),
+        );
+        let request = ::bytes::Bytes::from("{not json");
+
+        tokio::spawn(client.call(request).map(|_| ()));
+
+        match server.await {
+            Err(ListeningServerError::ServerError(
+                ServerError::ReceiveError(
+                    LengthDelimitedError::MessageError(
+                        SerdeError::FormatError(_),
+                    ),
+                ),
+            )) => {}
+            _ => panic!("server accepted a malformed request"),
+        }
+    }
+
+    fn assert_round_trip<F>()
+    where
+        F: SerdeFormat,
+        F::SerializeError: fmt::Debug,
+        F::DeserializeError: fmt::Debug,
+    {
+        let mut codec = LengthDelimitedCodec::new(
+            SerdeMessages::<F, Greeting, Greeting>::new(),
+        );
+        let mut buffer = BytesMut::new();
+        let greeting = Greeting {
+            name: "world".to_owned(),
+            times: 3,
+        };
+
+        codec.encode(greeting.clone(), &mut buffer).unwrap();
+
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(greeting));
+        assert!(buffer.is_empty());
+    }
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    struct GreetingService;
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    impl Service<Greeting> for GreetingService {
+        type Response = String;
+        type Error = ();
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(
+            &mut self,
+            _context: &mut Context,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, greeting: Greeting) -> Self::Future {
+            let greetings: Vec<_> = (0..greeting.times)
+                .map(|_| format!("hello {}", greeting.name))
+                .collect();
+
+            future::ok(greetings.join(" "))
+        }
+    }
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    struct MalformedJsonService;
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    impl Service<::bytes::Bytes> for MalformedJsonService {
+        type Response = ::bytes::Bytes;
+        type Error = ();
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(
+            &mut self,
+            _context: &mut Context,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: ::bytes::Bytes) -> Self::Future {
+            future::ok("{not json".into())
+        }
+    }
+
+    #[cfg(all(feature = "json", feature = "tcp"))]
+    fn free_address() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+    }
+}
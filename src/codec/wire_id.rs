@@ -0,0 +1,51 @@
+use std::mem;
+
+use bytes::{BufMut, BytesMut};
+
+/// A request ID that can be written as a fixed-size header on the wire.
+pub trait WireId: Sized {
+    const SIZE: usize;
+
+    /// Writes the ID in big-endian byte order.
+    fn write_to(&self, buffer: &mut BytesMut);
+
+    /// Reads an ID from the first `SIZE` bytes of the `header`.
+    fn read_from(header: &[u8]) -> Self;
+}
+
+macro_rules! impl_for_integers {
+    ($type:ty $(,)*) => {
+        impl WireId for $type {
+            const SIZE: usize = mem::size_of::<$type>();
+
+            fn write_to(&self, buffer: &mut BytesMut) {
+                buffer.reserve(Self::SIZE);
+                buffer.put_slice(&self.to_be_bytes());
+            }
+
+            fn read_from(header: &[u8]) -> Self {
+                let mut bytes = [0; mem::size_of::<$type>()];
+
+                bytes.copy_from_slice(&header[..Self::SIZE]);
+
+                <$type>::from_be_bytes(bytes)
+            }
+        }
+    };
+
+    ($type:ty $(, $rest:ty)* $(,)*) => {
+        impl_for_integers!( $type );
+        impl_for_integers!( $($rest),* );
+    };
+}
+
+impl_for_integers! {
+    u8,
+    u16,
+    u32,
+    u64,
+    i8,
+    i16,
+    i32,
+    i64,
+}
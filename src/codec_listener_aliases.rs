@@ -0,0 +1,28 @@
+use std::io;
+
+use futures::TryStream;
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
+
+use super::listening_server_error::ListeningServerError;
+use super::server_error::ServerError;
+
+/// The responses of the services in `S` to the requests decoded by `C`.
+pub type ResponseAlias<S, C> =
+    <<S as TryStream>::Ok as Service<<C as Decoder>::Item>>::Response;
+
+/// The futures of the services in `S` for the requests decoded by `C`.
+pub type FutureAlias<S, C> =
+    <<S as TryStream>::Ok as Service<<C as Decoder>::Item>>::Future;
+
+pub type ConnectionErrorAlias<SI, C> = ServerError<
+    <C as Decoder>::Error,
+    <C as Encoder<<SI as Service<<C as Decoder>::Item>>::Response>>::Error,
+    <SI as Service<<C as Decoder>::Item>>::Error,
+>;
+
+pub type ErrorAlias<S, C> = ListeningServerError<
+    <S as TryStream>::Error,
+    io::Error,
+    ConnectionErrorAlias<<S as TryStream>::Ok, C>,
+>;
@@ -0,0 +1,24 @@
+use std::num::NonZeroUsize;
+
+/// How much work a server buffers for each of its connections.
+///
+/// Once `max_in_flight_requests` requests are being serviced, the server
+/// stops reading new requests from the connection until one of them
+/// finishes. Finished responses wait in a queue of up to
+/// `response_queue_capacity` responses until the connection accepts them, so
+/// a client that reads slowly also slows down the servicing of its requests.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConnectionLimits {
+    pub max_in_flight_requests: Option<NonZeroUsize>,
+    pub response_queue_capacity: NonZeroUsize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        ConnectionLimits {
+            max_in_flight_requests: None,
+            response_queue_capacity: NonZeroUsize::new(32)
+                .expect("default response queue capacity is zero"),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
+use std::task::{Context, Poll};
 
-use futures::Poll;
-
+use super::client_error::ClientError;
 use super::receiver::Receiver;
 
 pub trait Dispatcher {
@@ -14,5 +14,42 @@ pub trait Dispatcher {
     where
         Self: Sized;
 
-    fn poll(&self, id: &Self::Id) -> Poll<Self::Item, Self::Error>;
+    fn poll(
+        &self,
+        id: &Self::Id,
+        context: &mut Context,
+    ) -> Poll<Result<Self::Item, DispatchError<Self::Error>>>;
+
+    fn deregister(&self, id: &Self::Id);
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Failure {
+    DuplicateRequestId,
+    UnexpectedResponseId,
+    ConnectionLost,
+}
+
+pub enum DispatchError<E> {
+    ReceiveError(E),
+    Failure(Failure),
+}
+
+impl<E> DispatchError<E> {
+    pub fn into_client_error<O>(self) -> ClientError<E, O> {
+        match self {
+            DispatchError::ReceiveError(error) => {
+                ClientError::ReceiveError(error)
+            }
+            DispatchError::Failure(Failure::DuplicateRequestId) => {
+                ClientError::DuplicateRequestId
+            }
+            DispatchError::Failure(Failure::UnexpectedResponseId) => {
+                ClientError::UnexpectedResponseId
+            }
+            DispatchError::Failure(Failure::ConnectionLost) => {
+                ClientError::ConnectionLost
+            }
+        }
+    }
 }
@@ -0,0 +1,39 @@
+/// The fraction of calls that must fail for a `CircuitBreaker` to open.
+///
+/// The rate is always greater than zero and at most one, so that a circuit
+/// never opens without failures, and can always open.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ErrorRate(f64);
+
+impl ErrorRate {
+    /// Returns `None` unless the `rate` is greater than zero and at most one.
+    pub fn new(rate: f64) -> Option<Self> {
+        if rate > 0.0 && rate <= 1.0 {
+            Some(ErrorRate(rate))
+        } else {
+            None
+        }
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_rates_up_to_one() {
+        assert_eq!(ErrorRate::new(0.5).map(ErrorRate::get), Some(0.5));
+        assert_eq!(ErrorRate::new(1.0).map(ErrorRate::get), Some(1.0));
+    }
+
+    #[test]
+    fn rejects_rates_that_never_or_always_open_the_circuit() {
+        for &rate in &[0.0, -0.5, 1.5, f64::NAN, f64::INFINITY] {
+            assert_eq!(ErrorRate::new(rate), None);
+        }
+    }
+}
@@ -1,78 +1,153 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::pin::Pin;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
 
-use futures::{Async, Poll, Stream};
+use futures::TryStream;
 
-use super::delayed_add::DelayedAdd;
-use super::dispatcher::Dispatcher;
+use super::dispatcher::{DispatchError, Dispatcher, Failure};
 use super::ready_queue::ReadyQueue;
 use super::receiver::Receiver;
 
 pub struct FifoDispatcher<T>
 where
-    T: Stream,
+    T: TryStream + Unpin,
 {
-    source: Arc<Mutex<T>>,
-    queue: Arc<Mutex<ReadyQueue<T::Item>>>,
-    latest_ready_id: AtomicUsize,
+    source: Arc<Mutex<Option<T>>>,
+    queue: Arc<Mutex<ReadyQueue<T::Ok>>>,
+    waiting_tasks: Mutex<HashMap<usize, Waker>>,
     new_id: AtomicUsize,
+    connection_lost: AtomicBool,
 }
 
 impl<T> FifoDispatcher<T>
 where
-    T: Stream,
+    T: TryStream + Unpin,
 {
     pub fn new(source: T) -> Self {
         FifoDispatcher {
-            source: Arc::new(Mutex::new(source)),
+            source: Arc::new(Mutex::new(Some(source))),
             queue: Arc::new(Mutex::new(ReadyQueue::new())),
-            latest_ready_id: AtomicUsize::new(0),
+            waiting_tasks: Mutex::new(HashMap::new()),
             new_id: AtomicUsize::new(0),
+            connection_lost: AtomicBool::new(false),
         }
     }
 
-    fn pop_if_ready(&self, id: usize) -> Option<T::Item> {
-        if id < self.latest_ready_id.load(Ordering::Relaxed) {
-            Some(self.lock_queue().pop(id))
+    fn pop_if_ready(
+        &self,
+        id: usize,
+        park: Option<&Waker>,
+    ) -> Result<Option<T::Ok>, Failure> {
+        let mut queue = self.lock_queue();
+        let mut waiting_tasks = self.lock_waiting_tasks();
+
+        if queue.is_ready(id) {
+            waiting_tasks.remove(&id);
+            Self::wake_any(&waiting_tasks);
+
+            Ok(Some(queue.pop(id)))
+        } else if self.connection_lost.load(Ordering::Relaxed) {
+            Err(Failure::ConnectionLost)
         } else {
-            None
+            if let Some(waker) = park {
+                waiting_tasks.insert(id, waker.clone());
+            }
+
+            Ok(None)
         }
     }
 
-    fn get_from_source(&self) -> Poll<(), T::Error> {
-        let mut source = self.source.lock().expect(
+    fn get_from_source(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), T::Error>> {
+        let mut source_guard = self.source.lock().expect(
             "a thread panicked while holding the FifoDispatcher locked",
         );
 
-        if let Some(item) = try_ready!(source.poll()) {
-            let mut update_latest_ready_id =
-                DelayedAdd::new(&self.latest_ready_id);
-            let mut queue = self.lock_queue();
+        let source_ended = match *source_guard {
+            Some(ref mut source) => {
+                ready!(self.poll_source(source, context))?
+            }
+            None => false,
+        };
+
+        if source_ended {
+            source_guard.take();
+        }
 
-            queue.push(item);
-            update_latest_ready_id.increment();
+        Poll::Ready(Ok(()))
+    }
 
-            while let Some(item) = try_ready!(source.poll()) {
-                queue.push(item);
-                update_latest_ready_id.increment();
+    fn poll_source(
+        &self,
+        source: &mut T,
+        context: &mut Context,
+    ) -> Poll<Result<bool, T::Error>> {
+        let mut locks = None;
+
+        loop {
+            let item = ready!(Pin::new(&mut *source).try_poll_next(context))
+                .transpose()?;
+            let (queue, waiting_tasks) = locks.get_or_insert_with(|| {
+                (self.lock_queue(), self.lock_waiting_tasks())
+            });
+
+            match item {
+                Some(item) => Self::enqueue(queue, waiting_tasks, item),
+                None => {
+                    self.connection_lost.store(true, Ordering::Relaxed);
+
+                    for (_, waker) in waiting_tasks.drain() {
+                        waker.wake();
+                    }
+
+                    return Poll::Ready(Ok(true));
+                }
             }
         }
+    }
+
+    fn enqueue(
+        queue: &mut ReadyQueue<T::Ok>,
+        waiting_tasks: &mut HashMap<usize, Waker>,
+        item: T::Ok,
+    ) {
+        let id = queue.push(item);
+
+        if let Some(waker) = waiting_tasks.remove(&id) {
+            waker.wake();
+        }
+    }
 
-        Ok(Async::Ready(()))
+    /// Wakes up one of the waiting tasks, so that it can take over polling
+    /// the source from a task that might not poll it again.
+    fn wake_any(waiting_tasks: &HashMap<usize, Waker>) {
+        if let Some(waker) = waiting_tasks.values().next() {
+            waker.wake_by_ref();
+        }
     }
 
-    fn lock_queue(&self) -> MutexGuard<ReadyQueue<T::Item>> {
+    fn lock_queue(&self) -> MutexGuard<'_, ReadyQueue<T::Ok>> {
         self.queue
             .lock()
             .expect("a thread panicked while holding the FifoDispatcher locked")
     }
+
+    fn lock_waiting_tasks(&self) -> MutexGuard<'_, HashMap<usize, Waker>> {
+        self.waiting_tasks
+            .lock()
+            .expect("a thread panicked while holding the FifoDispatcher locked")
+    }
 }
 
 impl<T> Dispatcher for FifoDispatcher<T>
 where
-    T: Stream,
+    T: TryStream + Unpin,
 {
-    type Item = T::Item;
+    type Item = T::Ok;
     type Error = T::Error;
     type Id = usize;
     type Seed = ();
@@ -86,17 +161,38 @@ where
         Receiver::new(arc_self, new_id)
     }
 
-    fn poll(&self, id: &Self::Id) -> Poll<Self::Item, Self::Error> {
-        if let Some(item) = self.pop_if_ready(*id) {
-            Ok(Async::Ready(item))
+    fn poll(
+        &self,
+        id: &Self::Id,
+        context: &mut Context,
+    ) -> Poll<Result<Self::Item, DispatchError<Self::Error>>> {
+        let ready = self.pop_if_ready(*id, None)
+            .map_err(DispatchError::Failure)?;
+
+        if let Some(item) = ready {
+            Poll::Ready(Ok(item))
         } else {
-            self.get_from_source()?;
+            if let Poll::Ready(Err(error)) = self.get_from_source(context) {
+                return Poll::Ready(Err(DispatchError::ReceiveError(error)));
+            }
 
-            let item = self.pop_if_ready(*id)
-                .map(Async::Ready)
-                .unwrap_or(Async::NotReady);
+            let item = self.pop_if_ready(*id, Some(context.waker()))
+                .map_err(DispatchError::Failure)?;
 
-            Ok(item)
+            match item {
+                Some(item) => Poll::Ready(Ok(item)),
+                None => Poll::Pending,
+            }
         }
     }
+
+    fn deregister(&self, id: &Self::Id) {
+        let mut queue = self.lock_queue();
+        let mut waiting_tasks = self.lock_waiting_tasks();
+
+        queue.discard(*id);
+        waiting_tasks.remove(id);
+
+        Self::wake_any(&waiting_tasks);
+    }
 }
@@ -1,101 +1,287 @@
-use futures::{Future, Poll, Sink, Stream, stream::{FuturesUnordered, Zip}};
-use tokio_service::Service;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::FuturesUnordered;
+use futures::{Sink, StreamExt, TryStream};
+use tower_service::Service;
 
 use super::{
-    generic_server::GenericServer,
+    connection_limits::ConnectionLimits,
+    generic_server::{GenericServer, ServerErrorAlias},
     listening_server_error::ListeningServerError,
     map_to_listening_server_server_error::MapToListeningServerServerError,
     map_to_listening_server_service_error::MapToListeningServerServiceError,
     map_to_listening_server_transport_error::MapToListeningServerTransportError,
-    server_error::ServerError, stream_of_future_results::StreamOfFutureResults,
+    shutdown_handle::ShutdownHandle,
+    shutdown_signal::ShutdownSignal,
+    stream_of_future_results::StreamOfFutureResults,
 };
 
-pub type ErrorAlias<S: Stream, T: Stream, SI: Service, TI: Sink + Stream> =
-    ListeningServerError<
-        S::Error,
-        T::Error,
-        ServerError<TI::Error, TI::SinkError, SI::Error>,
-    >;
+/// The requests received from the transports in `T`.
+pub type RequestAlias<T> = <<T as TryStream>::Ok as TryStream>::Ok;
+
+/// The responses of the services in `S` to the requests from `T`.
+pub type ResponseAlias<S, T> =
+    <<S as TryStream>::Ok as Service<RequestAlias<T>>>::Response;
+
+/// The futures of the services in `S` for the requests from `T`.
+pub type FutureAlias<S, T> =
+    <<S as TryStream>::Ok as Service<RequestAlias<T>>>::Future;
+
+pub type ErrorAlias<S, T> = ListeningServerError<
+    <S as TryStream>::Error,
+    <T as TryStream>::Error,
+    ConnectionErrorAlias<<S as TryStream>::Ok, <T as TryStream>::Ok>,
+>;
+
+pub type ConnectionErrorAlias<SI, TI> = ServerErrorAlias<SI, TI>;
+
+/// A new service paired with a new transport, or the error that prevented
+/// creating them.
+type EndpointsAlias<S, T> = Result<
+    (<S as TryStream>::Ok, <T as TryStream>::Ok),
+    ErrorAlias<S, T>,
+>;
+
+/// A new service, or the error that prevented creating it.
+type NewServiceAlias<S, T> = Result<<S as TryStream>::Ok, ErrorAlias<S, T>>;
+
+/// The servers of the active connections.
+type ActiveServersAlias<S, T, H> = FuturesUnordered<
+    MapToListeningServerServerError<
+        GenericServer<<S as TryStream>::Ok, <T as TryStream>::Ok, H>,
+        S,
+        T,
+    >,
+>;
+
+enum ConnectionErrorHandling<E> {
+    Report(Box<dyn FnMut(E) + Send>),
+    FailFast,
+}
 
 pub struct GenericListeningServer<S, T, H>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
-    H: StreamOfFutureResults<<S::Item as Service>::Future>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, T>>,
 {
-    active_servers: FuturesUnordered<
-        MapToListeningServerServerError<
-            GenericServer<S::Item, T::Item, H>,
-            S,
-            T,
-        >,
-    >,
-    endpoints: Zip<
-        MapToListeningServerServiceError<S, T>,
-        MapToListeningServerTransportError<S, T>,
-    >,
+    active_servers: ActiveServersAlias<S, T, H>,
+    services: MapToListeningServerServiceError<S, T>,
+    transports: MapToListeningServerTransportError<S, T>,
+    waiting_service: Option<NewServiceAlias<S, T>>,
+    waiting_transport: Option<T::Ok>,
     listening: bool,
+    limits: ConnectionLimits,
+    shutdown: ShutdownSignal,
+    connection_errors: ConnectionErrorHandling<ErrorAlias<S, T>>,
 }
 
 impl<S, T, H> GenericListeningServer<S, T, H>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
-    H: StreamOfFutureResults<<S::Item as Service>::Future>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, T>>,
 {
     pub fn new(services: S, transports: T) -> Self {
-        let transports = MapToListeningServerTransportError::from(transports);
-        let services = MapToListeningServerServiceError::from(services);
-
         GenericListeningServer {
             active_servers: FuturesUnordered::new(),
-            endpoints: services.zip(transports),
+            services: MapToListeningServerServiceError::from(services),
+            transports: MapToListeningServerTransportError::from(transports),
+            waiting_service: None,
+            waiting_transport: None,
             listening: true,
+            limits: ConnectionLimits::default(),
+            shutdown: ShutdownSignal::never(),
+            connection_errors: ConnectionErrorHandling::Report(Box::new(
+                |_| {},
+            )),
+        }
+    }
+
+    /// Reports the errors of individual connections to the `handler`.
+    ///
+    /// The handler receives a `ListeningServerError::TransportError` when a
+    /// new connection couldn't be accepted, a
+    /// `ListeningServerError::ServiceError` when no service could be created
+    /// for it, and a `ListeningServerError::ServerError` when an active
+    /// connection fails. A connection that fails is dropped, but the server
+    /// keeps running. By default, the errors are silently discarded.
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, T>) + Send + 'static,
+    {
+        self.connection_errors =
+            ConnectionErrorHandling::Report(Box::new(handler));
+    }
+
+    /// Makes the whole server fail as soon as one of its connections fails.
+    pub fn fail_fast(&mut self) {
+        self.connection_errors = ConnectionErrorHandling::FailFast;
+    }
+
+    /// Sets the limits of each connection, including the ones that are
+    /// already active.
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        for server in self.active_servers.iter_mut() {
+            server.get_mut().set_connection_limits(limits);
+        }
+
+        self.limits = limits;
+    }
+
+    /// Creates a handle to shut down the server, which stops accepting new
+    /// connections and shuts down all of its active servers.
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        let (handle, signal) = ShutdownSignal::new();
+
+        for server in self.active_servers.iter_mut() {
+            server.get_mut().set_shutdown_signal(signal.clone());
+        }
+
+        self.shutdown = signal;
+        handle
+    }
+
+    /// Pairs each new service with a new transport, in the order they are
+    /// received.
+    ///
+    /// Both streams are polled even while the other one has nothing to pair
+    /// with, because the services might only be created once the transports
+    /// are accepted. A service that couldn't be created is paired with a
+    /// transport as well, so that only that transport is dropped.
+    fn poll_endpoints(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Option<EndpointsAlias<S, T>>> {
+        if self.waiting_service.is_none() {
+            match self.services.poll_next_unpin(context) {
+                Poll::Ready(Some(service)) => {
+                    self.waiting_service = Some(service)
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => {}
+            }
+        }
+
+        if self.waiting_transport.is_none() {
+            match self.transports.poll_next_unpin(context) {
+                Poll::Ready(Some(Ok(transport))) => {
+                    self.waiting_transport = Some(transport)
+                }
+                Poll::Ready(Some(Err(error))) => {
+                    return Poll::Ready(Some(Err(error)))
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => {}
+            }
+        }
+
+        if self.waiting_service.is_some() && self.waiting_transport.is_some() {
+            let service = self.waiting_service
+                .take()
+                .expect("service was tested to exist");
+            let transport = self.waiting_transport
+                .take()
+                .expect("transport was tested to exist");
+
+            Poll::Ready(Some(service.map(|service| (service, transport))))
+        } else {
+            Poll::Pending
         }
     }
 
     fn advance_active_servers(
         &mut self,
-    ) -> Poll<(), ErrorAlias<S, T, S::Item, T::Item>> {
+        context: &mut Context,
+    ) -> Poll<Result<(), ErrorAlias<S, T>>> {
         loop {
-            try_ready!(self.active_servers.poll());
+            match self.active_servers.poll_next_unpin(context) {
+                Poll::Ready(Some(Ok(()))) => {}
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Err(error))) => {
+                    self.handle_connection_error(error)?
+                }
+            }
         }
     }
+
+    /// Reports the error of a single connection, unless the server fails
+    /// fast.
+    fn handle_connection_error(
+        &mut self,
+        error: ErrorAlias<S, T>,
+    ) -> Result<(), ErrorAlias<S, T>> {
+        match self.connection_errors {
+            ConnectionErrorHandling::Report(ref mut handler) => {
+                handler(error);
+                Ok(())
+            }
+            ConnectionErrorHandling::FailFast => Err(error),
+        }
+    }
+}
+
+// The services and transports are only pinned by their own `Unpin` types.
+impl<S, T, H> Unpin for GenericListeningServer<S, T, H>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, T>>,
+{
 }
 
 impl<S, T, H> Future for GenericListeningServer<S, T, H>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
-    H: StreamOfFutureResults<<S::Item as Service>::Future>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, T>>,
 {
-    type Item = ();
-    type Error = ErrorAlias<S, T, S::Item, T::Item>;
+    type Output = Result<(), ErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.advance_active_servers()?;
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let this = &mut *self;
 
-        while self.listening {
-            let endpoint = try_ready!(self.endpoints.poll());
+        if this.shutdown.is_shutting_down(context) {
+            this.listening = false;
+        }
 
-            if let Some((service, transport)) = endpoint {
-                let server = GenericServer::new(service, transport).into();
+        while this.listening {
+            match this.poll_endpoints(context) {
+                Poll::Ready(Some(Ok((service, transport)))) => {
+                    let mut server = GenericServer::new(service, transport);
 
-                self.active_servers.push(server);
-            } else {
-                self.listening = false;
+                    server.set_connection_limits(this.limits);
+                    server.set_shutdown_signal(this.shutdown.clone());
+                    this.active_servers.push(server.into());
+                }
+                Poll::Ready(Some(Err(error))) => {
+                    this.handle_connection_error(error)?
+                }
+                Poll::Ready(None) => this.listening = false,
+                Poll::Pending => break,
             }
         }
 
-        self.advance_active_servers()
+        let active_servers_finished = this.advance_active_servers(context)?;
+
+        if this.listening {
+            Poll::Pending
+        } else {
+            active_servers_finished.map(Ok)
+        }
     }
 }
@@ -1,125 +1,242 @@
-use futures::sink::SendAll;
-use futures::stream::{Fuse, SplitSink, SplitStream};
-use futures::sync::mpsc;
-use futures::{Async, Future, Poll, Sink, Stream};
-use tokio_service::Service;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
+use futures::stream::Fuse;
+use futures::{Sink, SinkExt, StreamExt, TryStream};
+use tower_service::Service;
+
+use super::connection_limits::ConnectionLimits;
 use super::map_to_server_send_error::MapToServerSendError;
 use super::server_error::ServerError;
+use super::shutdown_handle::ShutdownHandle;
+use super::shutdown_signal::ShutdownSignal;
+use super::split_transport::{self, SinkHalf, StreamHalf};
 use super::stream_of_future_results::StreamOfFutureResults;
 
-pub type ServerErrorAlias<S: Service, T: Stream + Sink> =
-    ServerError<T::Error, T::SinkError, S::Error>;
+pub type ServerErrorAlias<S, T> = ServerError<
+    <T as TryStream>::Error,
+    <T as Sink<<S as Service<<T as TryStream>::Ok>>::Response>>::Error,
+    <S as Service<<T as TryStream>::Ok>>::Error,
+>;
 
 pub struct GenericServer<S, T, H>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
     H: StreamOfFutureResults<S::Future>,
 {
     service: S,
-    incoming_requests: Fuse<SplitStream<T>>,
+    incoming_requests: Fuse<StreamHalf<T>>,
     active_requests: H,
-    response_queue: mpsc::UnboundedSender<T::SinkItem>,
-    response_sender: SendAll<
-        MapToServerSendError<SplitSink<T>, ServerErrorAlias<S, T>>,
-        mpsc::UnboundedReceiver<T::SinkItem>,
+    in_flight_requests: usize,
+    response_queue: VecDeque<S::Response>,
+    outgoing_responses: MapToServerSendError<
+        SinkHalf<T>,
+        ServerErrorAlias<S, T>,
     >,
+    limits: ConnectionLimits,
     no_more_requests: bool,
+    shutdown: ShutdownSignal,
 }
 
 impl<S, T, H> GenericServer<S, T, H>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
     H: StreamOfFutureResults<S::Future>,
 {
     pub fn new(service: S, transport: T) -> Self {
-        let (outgoing_responses, incoming_requests) = transport.split();
-        let (response_queue, queued_responses) = mpsc::unbounded();
+        let (outgoing_responses, incoming_requests) =
+            split_transport::split(transport);
 
         let outgoing_responses = MapToServerSendError::from(outgoing_responses);
         let incoming_requests = incoming_requests.fuse();
         let active_requests = H::new();
-        let response_sender = outgoing_responses.send_all(queued_responses);
 
         GenericServer {
             service,
             incoming_requests,
             active_requests,
-            response_queue,
-            response_sender,
+            in_flight_requests: 0,
+            response_queue: VecDeque::new(),
+            outgoing_responses,
+            limits: ConnectionLimits::default(),
             no_more_requests: false,
+            shutdown: ShutdownSignal::never(),
         }
     }
 
-    fn poll_responses(&mut self) -> Result<(), ServerErrorAlias<S, T>> {
-        loop {
-            let next_response = self.active_requests
-                .poll()
-                .map_err(ServerError::ServiceError)?;
-
-            match next_response {
-                Async::Ready(Some(response)) => {
-                    self.response_queue
-                        .unbounded_send(response)
-                        .map_err(|_| ServerError::ConnectionClosed)?;
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        let (handle, signal) = ShutdownSignal::new();
+
+        self.shutdown = signal;
+        handle
+    }
+
+    pub fn set_shutdown_signal(&mut self, signal: ShutdownSignal) {
+        self.shutdown = signal;
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.limits = limits;
+    }
+
+    fn can_accept_requests(&self) -> bool {
+        self.limits
+            .max_in_flight_requests
+            .map(|limit| self.in_flight_requests < limit.get())
+            .unwrap_or(true)
+    }
+
+    fn poll_requests(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<bool, ServerErrorAlias<S, T>> {
+        let mut received_requests = false;
+
+        while self.can_accept_requests() {
+            // Requests are only received while the service can take them, so
+            // that a busy service slows the clients down.
+            match self.service.poll_ready(context) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(error)) => {
+                    return Err(ServerError::ServiceError(error));
                 }
-                Async::Ready(None) => {
-                    if self.no_more_requests {
-                        let _ = self.response_queue.close();
-                    }
+                Poll::Pending => break,
+            }
+
+            match self.incoming_requests.poll_next_unpin(context) {
+                Poll::Ready(Some(Ok(request))) => {
+                    self.active_requests
+                        .push(self.service.call(request));
+                    self.in_flight_requests += 1;
+                    received_requests = true;
+                }
+                Poll::Ready(Some(Err(error))) => {
+                    return Err(ServerError::ReceiveError(error));
+                }
+                Poll::Ready(None) => {
+                    self.no_more_requests = true;
                     break;
                 }
-                Async::NotReady => break,
+                Poll::Pending => break,
             }
         }
 
-        Ok(())
+        Ok(received_requests)
     }
 
-    fn poll_requests(&mut self) -> Result<(), ServerErrorAlias<S, T>> {
-        loop {
-            let new_request = self.incoming_requests
-                .poll()
-                .map_err(ServerError::ReceiveError)?;
+    fn poll_responses(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<bool, ServerErrorAlias<S, T>> {
+        let mut queued_responses = false;
 
-            match new_request {
-                Async::Ready(Some(request)) => {
-                    self.active_requests
-                        .push(self.service.call(request));
+        let capacity = self.limits.response_queue_capacity.get();
+
+        while self.response_queue.len() < capacity {
+            match self.active_requests.poll_next_unpin(context) {
+                Poll::Ready(Some(Ok(response))) => {
+                    self.response_queue.push_back(response);
+                    self.in_flight_requests -= 1;
+                    queued_responses = true;
                 }
-                Async::Ready(None) => {
-                    self.no_more_requests = true;
-                    break;
+                Poll::Ready(Some(Err(error))) => {
+                    return Err(ServerError::ServiceError(error));
                 }
-                Async::NotReady => break,
+                Poll::Ready(None) | Poll::Pending => break,
             }
         }
 
-        Ok(())
+        Ok(queued_responses)
     }
 
-    fn poll_sender(&mut self) -> Poll<(), ServerErrorAlias<S, T>> {
-        match self.response_sender.poll()? {
-            Async::Ready(_) => Ok(Async::Ready(())),
-            Async::NotReady => Ok(Async::NotReady),
+    fn send_responses(
+        &mut self,
+        context: &mut Context,
+    ) -> Result<bool, ServerErrorAlias<S, T>> {
+        let mut sent_responses = false;
+
+        while !self.response_queue.is_empty() {
+            match self.outgoing_responses.poll_ready_unpin(context)? {
+                Poll::Ready(()) => {
+                    let response = self.response_queue
+                        .pop_front()
+                        .expect("response queue was tested to be non-empty");
+
+                    self.outgoing_responses.start_send_unpin(response)?;
+                    sent_responses = true;
+                }
+                Poll::Pending => break,
+            }
         }
+
+        let _ = self.outgoing_responses.poll_flush_unpin(context)?;
+
+        Ok(sent_responses)
     }
+
+    fn finished(&self) -> bool {
+        self.no_more_requests
+            && self.in_flight_requests == 0
+            && self.response_queue.is_empty()
+    }
+}
+
+// None of the fields are ever pinned in place.
+impl<S, T, H> Unpin for GenericServer<S, T, H>
+where
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
+    H: StreamOfFutureResults<S::Future>,
+{
 }
 
 impl<S, T, H> Future for GenericServer<S, T, H>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
     H: StreamOfFutureResults<S::Future>,
 {
-    type Item = ();
-    type Error = ServerErrorAlias<S, T>;
+    type Output = Result<(), ServerErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.poll_requests()?;
-        self.poll_responses()?;
-        self.poll_sender()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        if this.shutdown.is_shutting_down(context) {
+            if this.shutdown.deadline_expired(context) {
+                return Poll::Ready(Ok(()));
+            }
+
+            this.no_more_requests = true;
+        }
+
+        // Each step can make room for the previous one, so keep going until
+        // none of them makes progress.
+        loop {
+            let received_requests = if this.no_more_requests {
+                false
+            } else {
+                this.poll_requests(context)?
+            };
+
+            let queued_responses = this.poll_responses(context)?;
+            let sent_responses = this.send_responses(context)?;
+
+            if !received_requests && !queued_responses && !sent_responses {
+                break;
+            }
+        }
+
+        if this.finished() {
+            this.outgoing_responses.poll_close_unpin(context)
+        } else {
+            Poll::Pending
+        }
     }
 }
@@ -0,0 +1,29 @@
+/// How a multiplexed client reacts to request and response ID errors.
+///
+/// A request whose ID is already used by an in-flight request is never sent,
+/// and a response whose ID doesn't match any in-flight request is never
+/// delivered. The policy only decides who else is affected. Note that a late
+/// response to an abandoned request (for example, one that timed out) is also
+/// treated as unexpected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IdErrorPolicy {
+    /// Fail the call that has been waiting the longest.
+    ///
+    /// A duplicate request fails its own call. An unexpected response is
+    /// discarded, and fails the oldest call still waiting for a response,
+    /// which is the one it most likely belongs to. Other calls aren't
+    /// affected.
+    FailCall,
+
+    /// Drop the offending message.
+    ///
+    /// A duplicate request fails its own call, and unexpected responses are
+    /// discarded without failing any call.
+    DropMessage,
+
+    /// Close the connection.
+    ///
+    /// The client stops reading responses, and every pending and future call
+    /// fails with the error that caused the connection to close.
+    CloseConnection,
+}
@@ -1,17 +1,62 @@
+#[cfg(all(test, feature = "derive"))]
+extern crate self as async_protocol;
+#[cfg(feature = "derive")]
+extern crate async_protocol_derive;
+#[cfg(feature = "bincode")]
+extern crate bincode;
+#[cfg(any(test, feature = "codec"))]
+extern crate bytes;
+#[cfg(feature = "cbor")]
+extern crate ciborium;
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
 #[macro_use]
 extern crate futures;
-extern crate tokio_core;
-#[cfg(feature = "tcp")]
-extern crate tokio_io;
+#[cfg(all(test, feature = "tls"))]
+extern crate rcgen;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "serde-codec")]
+extern crate serde;
+#[cfg(all(test, feature = "serde-codec"))]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "json")]
+extern crate serde_json;
+extern crate tokio;
+#[cfg(feature = "tls")]
+extern crate tokio_rustls;
+#[cfg(feature = "tokio-service-compat")]
 extern crate tokio_service;
+#[cfg(feature = "codec")]
+extern crate tokio_util;
+#[cfg(test)]
+extern crate tower;
+extern crate tower_service;
+#[cfg(feature = "uuid")]
+extern crate uuid;
 
-mod delayed_add;
+#[cfg(any(feature = "tcp", feature = "unix"))]
+mod accept_backoff;
+mod backoff;
+mod connection_limits;
+mod id_error_policy;
+mod make_services;
 mod message_with_id;
 mod ready_queue;
+mod request_factory;
+mod retry_budget;
+mod retry_limits;
+mod retry_policy;
+mod sequential_id;
+mod split_transport;
 mod stream_of_future_results;
+mod transient_error;
+#[cfg(feature = "tokio-service-compat")]
+mod tokio_service_compat;
 
 mod dispatcher;
 mod fifo_dispatcher;
@@ -19,19 +64,31 @@ mod multiplex_dispatcher;
 mod receiver;
 
 mod request_sender;
+mod shared_sink;
 
+mod auto_id_multiplex_client;
+mod circuit_breaker;
+mod circuit_breaker_policy;
+mod circuit_state;
 mod client_error;
 mod client_receiver;
+mod client_timeout;
+mod error_rate;
 mod map_to_client_receive_error;
 mod multiplex_client;
 mod pipeline_client;
+mod retrying_client;
 
 mod generic_server;
 mod map_to_server_send_error;
 mod multiplex_server;
 mod pipeline_server;
 mod server_error;
+mod shutdown_handle;
+mod shutdown_signal;
 
+#[cfg(any(feature = "tcp", feature = "unix"))]
+mod codec_listener_aliases;
 mod generic_listening_server;
 mod listening_server_error;
 mod map_to_listening_server_server_error;
@@ -40,25 +97,61 @@ mod map_to_listening_server_transport_error;
 mod multiplex_listening_server;
 mod pipeline_listening_server;
 
+pub mod memory;
+
+#[cfg(feature = "codec")]
+mod codec;
+
 #[cfg(feature = "tcp")]
 mod tcp;
+#[cfg(feature = "udp")]
+mod udp;
+#[cfg(feature = "unix")]
+mod unix;
 
 #[cfg(test)]
 pub mod tests;
 
+pub use connection_limits::ConnectionLimits;
+pub use id_error_policy::IdErrorPolicy;
+pub use make_services::MakeServices;
 pub use message_with_id::MessageWithId;
+#[cfg(feature = "derive")]
+pub use async_protocol_derive::MessageWithId;
+pub use request_factory::{Cloned, RequestFactory};
+pub use retry_budget::RetryBudget;
+pub use retry_limits::RetryLimits;
+pub use retry_policy::{RetryPolicy, RetryTransientErrors};
+pub use sequential_id::SequentialId;
+pub use transient_error::TransientError;
+#[cfg(feature = "tokio-service-compat")]
+pub use tokio_service_compat::{FromTokioService, IntoTokioService, ReadyCall};
+pub use tower_service::Service;
 
+pub use auto_id_multiplex_client::AutoIdMultiplexClient;
+pub use circuit_breaker::CircuitBreaker;
+pub use circuit_breaker_policy::{CircuitBreakerPolicy, TripCondition};
+pub use circuit_state::CircuitState;
 pub use client_error::ClientError;
+pub use error_rate::ErrorRate;
 pub use multiplex_client::MultiplexClient;
 pub use pipeline_client::PipelineClient;
+pub use retrying_client::RetryingClient;
 
 pub use multiplex_server::MultiplexServer;
 pub use pipeline_server::PipelineServer;
 pub use server_error::ServerError;
+pub use shutdown_handle::ShutdownHandle;
 
 pub use listening_server_error::ListeningServerError;
 pub use multiplex_listening_server::MultiplexListeningServer;
 pub use pipeline_listening_server::PipelineListeningServer;
 
+#[cfg(feature = "codec")]
+pub use codec::*;
 #[cfg(feature = "tcp")]
 pub use tcp::*;
+#[cfg(feature = "udp")]
+pub use udp::*;
+#[cfg(feature = "unix")]
+pub use unix::*;
@@ -0,0 +1,192 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::{self, Repeat};
+use futures::{Stream, StreamExt};
+use tower_service::Service;
+
+/// The stream of services created by a `MakeService` for each of the
+/// `targets`, which can be used by the listening servers.
+///
+/// A `MakeService` is a service that responds with services. Each service is
+/// only requested once the previous one has been created.
+pub struct MakeServices<M, T>
+where
+    M: Service<T::Item>,
+    T: Stream + Unpin,
+{
+    make_service: M,
+    targets: T,
+    new_service: Option<Pin<Box<M::Future>>>,
+}
+
+impl<M> MakeServices<M, Repeat<()>>
+where
+    M: Service<()>,
+{
+    /// Creates a new service whenever a listening server needs one, without
+    /// any information about its connection.
+    pub fn new(make_service: M) -> Self {
+        Self::with_targets(make_service, stream::repeat(()))
+    }
+}
+
+impl<M, T> MakeServices<M, T>
+where
+    M: Service<T::Item>,
+    T: Stream + Unpin,
+{
+    pub fn with_targets(make_service: M, targets: T) -> Self {
+        MakeServices {
+            make_service,
+            targets,
+            new_service: None,
+        }
+    }
+}
+
+// The service being created is pinned in its own allocation.
+impl<M, T> Unpin for MakeServices<M, T>
+where
+    M: Service<T::Item>,
+    T: Stream + Unpin,
+{
+}
+
+impl<M, T> Stream for MakeServices<M, T>
+where
+    M: Service<T::Item>,
+    T: Stream + Unpin,
+{
+    type Item = Result<M::Response, M::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(ref mut new_service) = this.new_service {
+                let service = ready!(new_service.as_mut().poll(context));
+
+                this.new_service = None;
+
+                return Poll::Ready(Some(service));
+            }
+
+            if let Err(error) = ready!(this.make_service.poll_ready(context)) {
+                return Poll::Ready(Some(Err(error)));
+            }
+
+            match ready!(this.targets.poll_next_unpin(context)) {
+                Some(target) => {
+                    let new_service = this.make_service.call(target);
+
+                    this.new_service = Some(Box::pin(new_service));
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use futures::executor::block_on;
+    use futures::future;
+    use tower::service_fn;
+
+    use super::*;
+    use crate::client_error::ClientError;
+    use crate::listening_server_error::ListeningServerError;
+    use crate::memory::listener;
+    use crate::pipeline_client::PipelineClient;
+    use crate::pipeline_listening_server::PipelineListeningServer;
+    use crate::tests::common::ToUpperService;
+
+    #[test]
+    fn creates_a_service_for_each_target() {
+        let make_service =
+            service_fn(|name: &str| future::ok::<_, ()>(name.to_uppercase()));
+        let targets = stream::iter(vec!["first", "second"]);
+        let services = MakeServices::with_targets(make_service, targets);
+
+        assert_eq!(
+            block_on(services.collect::<Vec<_>>()),
+            vec![Ok("FIRST".to_owned()), Ok("SECOND".to_owned())]
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_listeners() {
+        let (connector, listener) = listener(4);
+
+        let make_service = service_fn(|()| future::ok::<_, ()>(ToUpperService));
+        let services = MakeServices::new(make_service);
+        let server = PipelineListeningServer::new(services, listener);
+
+        tokio::spawn(server);
+
+        let first_client = PipelineClient::new(connector.connect().unwrap());
+        let second_client = PipelineClient::new(connector.connect().unwrap());
+
+        let responses = future::try_join(
+            first_client.call("first".to_owned()),
+            second_client.call("second".to_owned()),
+        );
+
+        assert_eq!(
+            responses.await.unwrap(),
+            ("FIRST".to_owned(), "SECOND".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn failures_to_make_a_service_only_drop_their_connection() {
+        let (connector, listener) = listener(4);
+
+        let attempts = AtomicUsize::new(0);
+        let make_service = service_fn(move |()| {
+            if attempts.fetch_add(1, Ordering::AcqRel) == 0 {
+                future::err("rejected")
+            } else {
+                future::ok(ToUpperService)
+            }
+        });
+        let services = MakeServices::new(make_service);
+        let mut server = PipelineListeningServer::new(services, listener);
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let reported_errors = errors.clone();
+
+        server.on_connection_error(move |error| {
+            reported_errors.lock().unwrap().push(error);
+        });
+
+        tokio::spawn(server);
+
+        let rejected_client = PipelineClient::new(connector.connect().unwrap());
+
+        match rejected_client.call("rejected".to_owned()).await {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("rejected connection was not dropped"),
+        }
+
+        let client = PipelineClient::new(connector.connect().unwrap());
+        let response = client.call("accepted".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "ACCEPTED");
+
+        let errors = errors.lock().unwrap();
+
+        match errors.as_slice() {
+            [ListeningServerError::ServiceError("rejected")] => {}
+            _ => panic!("failure to make a service was not reported"),
+        }
+    }
+}
@@ -1,21 +1,25 @@
+use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Future, Poll};
+use futures::TryFuture;
 
 use super::client_error::ClientError;
+use super::dispatcher::DispatchError;
 
 pub struct MapToClientReceiveError<F, I, O>
 where
-    F: Future<Error = I>,
+    F: TryFuture<Error = DispatchError<I>> + Unpin,
 {
     future: F,
-    _receive_error: PhantomData<I>,
-    _send_error: PhantomData<O>,
+    _receive_error: PhantomData<fn() -> I>,
+    _send_error: PhantomData<fn() -> O>,
 }
 
 impl<F, I, O> From<F> for MapToClientReceiveError<F, I, O>
 where
-    F: Future<Error = I>,
+    F: TryFuture<Error = DispatchError<I>> + Unpin,
 {
     fn from(future: F) -> Self {
         MapToClientReceiveError {
@@ -28,14 +32,16 @@ where
 
 impl<F, I, O> Future for MapToClientReceiveError<F, I, O>
 where
-    F: Future<Error = I>,
+    F: TryFuture<Error = DispatchError<I>> + Unpin,
 {
-    type Item = F::Item;
-    type Error = ClientError<I, O>;
+    type Output = Result<F::Ok, ClientError<I, O>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.future
-            .poll()
-            .map_err(ClientError::ReceiveError)
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        Pin::new(&mut self.future)
+            .try_poll(context)
+            .map_err(DispatchError::into_client_error)
     }
 }
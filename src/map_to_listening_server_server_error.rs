@@ -1,42 +1,36 @@
+use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Future, Poll, Sink, Stream};
-use tokio_service::Service;
+use futures::{Sink, TryFuture, TryStream};
+use tower_service::Service;
 
-use super::{listening_server_error::ListeningServerError, server_error::ServerError};
+use super::generic_listening_server::{
+    ConnectionErrorAlias, ErrorAlias, RequestAlias, ResponseAlias,
+};
+use super::listening_server_error::ListeningServerError;
 
 pub struct MapToListeningServerServerError<F, S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
-    F: Future<
-        Error = ServerError<
-            <T::Item as Stream>::Error,
-            <T::Item as Sink>::SinkError,
-            <S::Item as Service>::Error,
-        >,
-    >,
+    S: TryStream,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
+    F: TryFuture<Error = ConnectionErrorAlias<S::Ok, T::Ok>> + Unpin,
 {
     future: F,
-    _services: PhantomData<S>,
-    _transports: PhantomData<T>,
+    _services: PhantomData<fn() -> S>,
+    _transports: PhantomData<fn() -> T>,
 }
 
 impl<F, S, T> From<F> for MapToListeningServerServerError<F, S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
-    F: Future<
-        Error = ServerError<
-            <T::Item as Stream>::Error,
-            <T::Item as Sink>::SinkError,
-            <S::Item as Service>::Error,
-        >,
-    >,
+    S: TryStream,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
+    F: TryFuture<Error = ConnectionErrorAlias<S::Ok, T::Ok>> + Unpin,
 {
     fn from(future: F) -> Self {
         MapToListeningServerServerError {
@@ -47,26 +41,35 @@ where
     }
 }
 
+impl<F, S, T> MapToListeningServerServerError<F, S, T>
+where
+    S: TryStream,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
+    F: TryFuture<Error = ConnectionErrorAlias<S::Ok, T::Ok>> + Unpin,
+{
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.future
+    }
+}
+
 impl<F, S, T> Future for MapToListeningServerServerError<F, S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
-    F: Future<
-        Error = ServerError<
-            <T::Item as Stream>::Error,
-            <T::Item as Sink>::SinkError,
-            <S::Item as Service>::Error,
-        >,
-    >,
+    S: TryStream,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
+    F: TryFuture<Error = ConnectionErrorAlias<S::Ok, T::Ok>> + Unpin,
 {
-    type Item = F::Item;
-    type Error = ListeningServerError<S::Error, T::Error, F::Error>;
+    type Output = Result<F::Ok, ErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.future
-            .poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        Pin::new(&mut self.future)
+            .try_poll(context)
             .map_err(ListeningServerError::ServerError)
     }
 }
@@ -1,27 +1,32 @@
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Poll, Sink, Stream};
-use tokio_service::Service;
+use futures::{Sink, Stream, TryStream};
+use tower_service::Service;
 
-use super::{listening_server_error::ListeningServerError, server_error::ServerError};
+use super::generic_listening_server::{
+    ErrorAlias, RequestAlias, ResponseAlias,
+};
+use super::listening_server_error::ListeningServerError;
 
 pub struct MapToListeningServerServiceError<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
 {
     services: S,
-    _transports: PhantomData<T>,
+    _transports: PhantomData<fn() -> T>,
 }
 
 impl<S, T> From<S> for MapToListeningServerServiceError<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
 {
     fn from(services: S) -> Self {
         MapToListeningServerServiceError {
@@ -33,25 +38,19 @@ where
 
 impl<S, T> Stream for MapToListeningServerServiceError<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
 {
-    type Item = S::Item;
-    type Error = ListeningServerError<
-        S::Error,
-        T::Error,
-        ServerError<
-            <T::Item as Stream>::Error,
-            <T::Item as Sink>::SinkError,
-            <S::Item as Service>::Error,
-        >,
-    >;
+    type Item = Result<S::Ok, ErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        self.services
-            .poll()
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.services)
+            .try_poll_next(context)
             .map_err(ListeningServerError::ServiceError)
     }
 }
@@ -1,27 +1,32 @@
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Poll, Sink, Stream};
-use tokio_service::Service;
+use futures::{Sink, Stream, TryStream};
+use tower_service::Service;
 
-use super::{listening_server_error::ListeningServerError, server_error::ServerError};
+use super::generic_listening_server::{
+    ErrorAlias, RequestAlias, ResponseAlias,
+};
+use super::listening_server_error::ListeningServerError;
 
 pub struct MapToListeningServerTransportError<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
+    S: TryStream,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
 {
-    _services: PhantomData<S>,
+    _services: PhantomData<fn() -> S>,
     transports: T,
 }
 
 impl<S, T> From<T> for MapToListeningServerTransportError<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
+    S: TryStream,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
 {
     fn from(transports: T) -> Self {
         MapToListeningServerTransportError {
@@ -33,25 +38,19 @@ where
 
 impl<S, T> Stream for MapToListeningServerTransportError<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink + Stream,
+    S: TryStream,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>>,
 {
-    type Item = T::Item;
-    type Error = ListeningServerError<
-        S::Error,
-        T::Error,
-        ServerError<
-            <T::Item as Stream>::Error,
-            <T::Item as Sink>::SinkError,
-            <S::Item as Service>::Error,
-        >,
-    >;
+    type Item = Result<T::Ok, ErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        self.transports
-            .poll()
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.transports)
+            .try_poll_next(context)
             .map_err(ListeningServerError::TransportError)
     }
 }
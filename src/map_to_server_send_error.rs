@@ -1,20 +1,17 @@
-use futures::{Poll, Sink, StartSend};
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Sink;
 
 use super::server_error::ServerError;
 
-pub struct MapToServerSendError<T, E>
-where
-    T: Sink,
-{
+pub struct MapToServerSendError<T, E> {
     sink: T,
-    _error: PhantomData<E>,
+    _error: PhantomData<fn() -> E>,
 }
 
-impl<T, E> From<T> for MapToServerSendError<T, E>
-where
-    T: Sink,
-{
+impl<T, E> From<T> for MapToServerSendError<T, E> {
     fn from(sink: T) -> Self {
         MapToServerSendError {
             sink,
@@ -23,25 +20,45 @@ where
     }
 }
 
-impl<T, I, O, S> Sink for MapToServerSendError<T, ServerError<I, O, S>>
+impl<T, M, I, O, S> Sink<M> for MapToServerSendError<T, ServerError<I, O, S>>
 where
-    T: Sink<SinkError = O>,
+    T: Sink<M, Error = O> + Unpin,
 {
-    type SinkItem = T::SinkItem;
-    type SinkError = ServerError<I, O, S>;
+    type Error = ServerError<I, O, S>;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink)
+            .poll_ready(context)
+            .map_err(ServerError::SendError)
+    }
 
     fn start_send(
-        &mut self,
-        item: Self::SinkItem,
-    ) -> StartSend<Self::SinkItem, Self::SinkError> {
-        self.sink
+        mut self: Pin<&mut Self>,
+        item: M,
+    ) -> Result<(), Self::Error> {
+        Pin::new(&mut self.sink)
             .start_send(item)
             .map_err(ServerError::SendError)
     }
 
-    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
-        self.sink
-            .poll_complete()
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink)
+            .poll_flush(context)
+            .map_err(ServerError::SendError)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink)
+            .poll_close(context)
             .map_err(ServerError::SendError)
     }
 }
@@ -0,0 +1,45 @@
+use futures::channel::mpsc::UnboundedSender;
+
+use super::memory_error::MemoryError;
+use super::memory_transport::{pair, MemoryTransport};
+
+/// Opens in-memory connections to a `MemoryListener`.
+pub struct MemoryConnector<Request, Response> {
+    connections: UnboundedSender<MemoryTransport<Request, Response>>,
+    capacity: usize,
+}
+
+impl<Request, Response> MemoryConnector<Request, Response> {
+    pub fn new(
+        connections: UnboundedSender<MemoryTransport<Request, Response>>,
+        capacity: usize,
+    ) -> Self {
+        MemoryConnector {
+            connections,
+            capacity,
+        }
+    }
+
+    /// Opens a new connection, handing the server end to the listener and
+    /// returning the client end.
+    pub fn connect(
+        &self,
+    ) -> Result<MemoryTransport<Response, Request>, MemoryError> {
+        let (client, server) = pair(self.capacity);
+
+        self.connections
+            .unbounded_send(server)
+            .map_err(|_| MemoryError::Disconnected)?;
+
+        Ok(client)
+    }
+}
+
+impl<Request, Response> Clone for MemoryConnector<Request, Response> {
+    fn clone(&self) -> Self {
+        MemoryConnector {
+            connections: self.connections.clone(),
+            capacity: self.capacity,
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use super::super::transient_error::TransientError;
+
+#[derive(Debug, Fail)]
+pub enum MemoryError {
+    #[fail(display = "the other end of the in-memory connection was closed")]
+    Disconnected,
+}
+
+impl TransientError for MemoryError {
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,108 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use futures::{Stream, StreamExt};
+
+use super::memory_connector::MemoryConnector;
+use super::memory_error::MemoryError;
+use super::memory_transport::MemoryTransport;
+
+/// A stream of the server ends of in-memory connections, which can be used
+/// as the transports of a listening server.
+///
+/// The stream ends once all of its connectors have been dropped.
+pub struct MemoryListener<Request, Response> {
+    connections: UnboundedReceiver<MemoryTransport<Request, Response>>,
+}
+
+/// Creates a listener and a connector to open connections to it.
+///
+/// Each connection buffers up to `capacity` messages in each direction, as
+/// the transports created by `pair`.
+pub fn listener<Request, Response>(
+    capacity: usize,
+) -> (
+    MemoryConnector<Request, Response>,
+    MemoryListener<Request, Response>,
+) {
+    let (sender, receiver) = mpsc::unbounded();
+    let connector = MemoryConnector::new(sender, capacity);
+    let listener = MemoryListener {
+        connections: receiver,
+    };
+
+    (connector, listener)
+}
+
+impl<Request, Response> Stream for MemoryListener<Request, Response> {
+    type Item = Result<MemoryTransport<Request, Response>, MemoryError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        self.connections.poll_next_unpin(context).map(|item| item.map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{future, stream};
+
+    use super::*;
+    use crate::multiplex_listening_server::MultiplexListeningServer;
+    use crate::pipeline_client::PipelineClient;
+    use crate::pipeline_listening_server::PipelineListeningServer;
+    use crate::tests::common::ToUpperService;
+
+    #[tokio::test]
+    async fn serves_pipeline_clients() {
+        let (connector, listener) = listener(4);
+
+        let services = stream::iter(vec![
+            Ok::<_, ()>(ToUpperService),
+            Ok(ToUpperService),
+        ]);
+        let server = PipelineListeningServer::new(services, listener);
+
+        tokio::spawn(server);
+
+        let first_client = PipelineClient::new(connector.connect().unwrap());
+        let second_client = PipelineClient::new(connector.connect().unwrap());
+
+        let responses = future::try_join(
+            first_client.call("first".to_owned()),
+            second_client.call("second".to_owned()),
+        );
+
+        assert_eq!(
+            responses.await.unwrap(),
+            ("FIRST".to_owned(), "SECOND".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn server_finishes_after_connectors_are_dropped() {
+        let (connector, listener) = listener::<String, String>(4);
+
+        let services = stream::iter(vec![Ok::<_, ()>(ToUpperService)]);
+        let server = MultiplexListeningServer::new(services, listener);
+
+        drop(connector);
+
+        assert!(server.await.is_ok());
+    }
+
+    #[test]
+    fn connecting_fails_after_the_listener_is_dropped() {
+        let (connector, listener) = listener::<String, String>(4);
+
+        drop(listener);
+
+        match connector.connect() {
+            Err(MemoryError::Disconnected) => {}
+            _ => panic!("connected to a dropped listener"),
+        }
+    }
+}
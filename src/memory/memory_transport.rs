@@ -0,0 +1,137 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::{Sink, Stream, StreamExt};
+
+use super::memory_error::MemoryError;
+
+/// One end of an in-memory duplex connection, which receives `I` messages
+/// and sends `O` messages.
+pub struct MemoryTransport<I, O> {
+    incoming: mpsc::Receiver<I>,
+    outgoing: mpsc::Sender<O>,
+}
+
+/// Creates two connected in-memory transports.
+///
+/// Each direction buffers up to `capacity` messages, plus one for the
+/// sending end, before the sender has to wait for the receiver.
+pub fn pair<A, B>(
+    capacity: usize,
+) -> (MemoryTransport<A, B>, MemoryTransport<B, A>) {
+    let (a_sender, a_receiver) = mpsc::channel(capacity);
+    let (b_sender, b_receiver) = mpsc::channel(capacity);
+
+    let first = MemoryTransport {
+        incoming: a_receiver,
+        outgoing: b_sender,
+    };
+    let second = MemoryTransport {
+        incoming: b_receiver,
+        outgoing: a_sender,
+    };
+
+    (first, second)
+}
+
+impl<I, O> Stream for MemoryTransport<I, O> {
+    type Item = Result<I, MemoryError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_next_unpin(context).map(|item| item.map(Ok))
+    }
+}
+
+impl<I, O> Sink<O> for MemoryTransport<I, O> {
+    type Error = MemoryError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.outgoing
+            .poll_ready(context)
+            .map_err(|_| MemoryError::Disconnected)
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        message: O,
+    ) -> Result<(), Self::Error> {
+        self.outgoing
+            .start_send(message)
+            .map_err(|_| MemoryError::Disconnected)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        _context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        // Closing the channel lets the other end know that no more messages
+        // will be sent.
+        self.outgoing.close_channel();
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::{FutureExt, SinkExt, TryStreamExt};
+
+    use super::*;
+    use crate::pipeline_client::PipelineClient;
+    use crate::pipeline_server::PipelineServer;
+    use crate::tests::common::ToUpperService;
+
+    #[test]
+    fn connects_both_ends() {
+        let (mut first, mut second) = pair::<String, String>(1);
+
+        block_on(first.send("to second".to_owned())).unwrap();
+        block_on(second.send("to first".to_owned())).unwrap();
+
+        let received_by_first = block_on(first.try_next()).unwrap();
+        let received_by_second = block_on(second.try_next()).unwrap();
+
+        assert_eq!(received_by_first, Some("to first".to_owned()));
+        assert_eq!(received_by_second, Some("to second".to_owned()));
+    }
+
+    #[test]
+    fn closing_one_end_finishes_the_other() {
+        let (mut first, mut second) = pair::<String, String>(1);
+
+        assert!(first.close().now_or_never().is_some());
+
+        let received = block_on(second.try_next()).unwrap();
+
+        assert_eq!(received, None);
+    }
+
+    #[tokio::test]
+    async fn serves_pipeline_clients() {
+        let (client_end, server_end) = pair(1);
+
+        let server = PipelineServer::new(ToUpperService, server_end);
+        let client = PipelineClient::new(client_end);
+
+        tokio::spawn(server);
+
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+    }
+}
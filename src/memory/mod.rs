@@ -0,0 +1,12 @@
+//! In-memory transports, for testing services end-to-end or running them
+//! in-process without sockets.
+
+mod memory_connector;
+mod memory_error;
+mod memory_listener;
+mod memory_transport;
+
+pub use self::memory_connector::MemoryConnector;
+pub use self::memory_error::MemoryError;
+pub use self::memory_listener::{listener, MemoryListener};
+pub use self::memory_transport::{pair, MemoryTransport};
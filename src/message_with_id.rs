@@ -1,3 +1,8 @@
+use std::sync::Arc;
+
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+
 pub trait MessageWithId {
     type Id;
 
@@ -21,22 +26,115 @@ macro_rules! impl_for_pair_tuples {
     };
 }
 
+macro_rules! impl_for_pair_tuples_with_cloned_ids {
+    ($type:ty $(,)*) => {
+        impl<T> MessageWithId for ($type, T) {
+            type Id = $type;
+
+            fn id(&self) -> Self::Id {
+                self.0.clone()
+            }
+        }
+    };
+
+    ($type:ty $(, $rest:ty)* $(,)*) => {
+        impl_for_pair_tuples_with_cloned_ids!( $type );
+        impl_for_pair_tuples_with_cloned_ids!( $($rest),* );
+    };
+}
+
 impl_for_pair_tuples! {
     u8,
     u16,
     u32,
     u64,
+    u128,
     usize,
     i8,
     i16,
     i32,
     i64,
+    i128,
     isize,
 }
 
+#[cfg(feature = "uuid")]
+impl_for_pair_tuples!(Uuid);
+
+impl_for_pair_tuples_with_cloned_ids! {
+    String,
+    Box<str>,
+}
+
+impl<T, const N: usize> MessageWithId for ([u8; N], T) {
+    type Id = [u8; N];
+
+    fn id(&self) -> Self::Id {
+        self.0
+    }
+}
+
+impl<M> MessageWithId for &M
+where
+    M: MessageWithId + ?Sized,
+{
+    type Id = M::Id;
+
+    fn id(&self) -> Self::Id {
+        (**self).id()
+    }
+}
+
+impl<M> MessageWithId for Box<M>
+where
+    M: MessageWithId + ?Sized,
+{
+    type Id = M::Id;
+
+    fn id(&self) -> Self::Id {
+        (**self).id()
+    }
+}
+
+impl<M> MessageWithId for Arc<M>
+where
+    M: MessageWithId + ?Sized,
+{
+    type Id = M::Id;
+
+    fn id(&self) -> Self::Id {
+        (**self).id()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "derive")]
+    use async_protocol_derive::MessageWithId;
+
+    #[cfg(feature = "derive")]
+    #[derive(MessageWithId)]
+    struct Request {
+        #[id]
+        id: String,
+        _body: Vec<u8>,
+    }
+
+    #[cfg(feature = "derive")]
+    #[derive(MessageWithId)]
+    struct Envelope<T>(T, #[id] u64);
+
+    #[cfg(feature = "derive")]
+    #[derive(MessageWithId)]
+    enum Response {
+        Value {
+            #[id]
+            request: u32,
+            _value: String,
+        },
+        Missing(#[id] u32),
+    }
 
     #[test]
     fn pair_tuple_implementation_for_u8() {
@@ -51,4 +149,87 @@ mod tests {
 
         assert_eq!(message.id(), 1_000isize);
     }
+
+    #[test]
+    fn pair_tuple_implementation_for_u128() {
+        let message = (u128::MAX, ());
+
+        assert_eq!(message.id(), u128::MAX);
+    }
+
+    #[test]
+    fn pair_tuple_implementation_for_string() {
+        let message = ("request-1".to_owned(), ());
+
+        assert_eq!(message.id(), "request-1");
+    }
+
+    #[test]
+    fn pair_tuple_implementation_for_boxed_str() {
+        let id: Box<str> = "request-1".into();
+        let message = (id.clone(), ());
+
+        assert_eq!(message.id(), id);
+    }
+
+    #[test]
+    fn pair_tuple_implementation_for_byte_array() {
+        let message = ([1u8, 2, 3, 4], "dummy string");
+
+        assert_eq!(message.id(), [1, 2, 3, 4]);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn pair_tuple_implementation_for_uuid() {
+        let id = Uuid::from_u128(0x1234);
+        let message = (id, ());
+
+        assert_eq!(message.id(), id);
+    }
+
+    #[test]
+    fn wrapped_messages_have_the_inner_id() {
+        let message = (10u8, ());
+
+        assert_eq!(id_of(&message), 10);
+        assert_eq!(id_of(Box::new(message)), 10);
+        assert_eq!(id_of(Arc::new(message)), 10);
+    }
+
+    fn id_of<M: MessageWithId>(message: M) -> M::Id {
+        message.id()
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_implementation_for_struct() {
+        let message = Request {
+            id: "request-1".to_owned(),
+            _body: vec![],
+        };
+
+        assert_eq!(message.id(), "request-1");
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_implementation_for_generic_tuple_struct() {
+        let message = Envelope("dummy string", 7);
+
+        assert_eq!(message.id(), 7);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_implementation_for_enum() {
+        let value = Response::Value {
+            request: 3,
+            _value: "value".to_owned(),
+        };
+        let missing = Response::Missing(4);
+
+        assert_eq!(value.id(), 3);
+        assert_eq!(missing.id(), 4);
+    }
 }
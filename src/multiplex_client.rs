@@ -1,80 +1,212 @@
 use std::hash::Hash;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
-use futures::future::Flatten;
-use futures::stream::{SplitSink, SplitStream};
-use futures::{Future, Sink, Stream};
-use tokio_service::Service;
+use futures::future::{self, Either, Ready, TryFlatten};
+use futures::{Sink, TryFutureExt, TryStream};
+use tower_service::Service;
 
 use super::client_error::ClientError;
 use super::client_receiver::ClientReceiver;
+use super::client_timeout::ClientTimeout;
+use super::id_error_policy::IdErrorPolicy;
+use super::map_to_client_receive_error::MapToClientReceiveError;
 use super::message_with_id::MessageWithId;
 use super::multiplex_dispatcher::MultiplexDispatcher;
+use super::receiver::Receiver;
 use super::request_sender::RequestSender;
+use super::shared_sink::SharedSink;
+use super::split_transport::{self, SinkHalf, StreamHalf};
+
+pub type MultiplexClientFuture<T, R> = ClientTimeout<UntimedFuture<T, R>>;
+
+type ResponseDispatcher<T> = MultiplexDispatcher<StreamHalf<T>>;
+
+type ErrorAlias<T, R> =
+    ClientError<<T as TryStream>::Error, <T as Sink<R>>::Error>;
+
+type UntimedFuture<T, R> = Either<
+    TryFlatten<
+        ClientReceiver<
+            ResponseDispatcher<T>,
+            RequestSender<SinkHalf<T>, R, ResponseDispatcher<T>>,
+        >,
+        MapToClientReceiveError<
+            Receiver<ResponseDispatcher<T>>,
+            <T as TryStream>::Error,
+            <T as Sink<R>>::Error,
+        >,
+    >,
+    Ready<Result<<T as TryStream>::Ok, ErrorAlias<T, R>>>,
+>;
 
 pub struct MultiplexClient<T>
 where
-    T: Stream + Sink,
-    T::Item: MessageWithId,
-    T::SinkItem: MessageWithId<Id = <T::Item as MessageWithId>::Id>,
-    <T::Item as MessageWithId>::Id: Eq + Hash,
+    T: TryStream + Unpin,
+    T::Ok: MessageWithId,
+    <T::Ok as MessageWithId>::Id: Eq + Hash,
 {
-    request_sink: Arc<Mutex<SplitSink<T>>>,
-    response_dispatcher: Arc<MultiplexDispatcher<SplitStream<T>>>,
+    request_sink: Arc<Mutex<SharedSink<SinkHalf<T>>>>,
+    response_dispatcher: Arc<ResponseDispatcher<T>>,
+    default_timeout: Option<Duration>,
 }
 
 impl<T> MultiplexClient<T>
 where
-    T: Stream + Sink,
-    T::Item: MessageWithId,
-    T::SinkItem: MessageWithId<Id = <T::Item as MessageWithId>::Id>,
-    <T::Item as MessageWithId>::Id: Eq + Hash,
+    T: TryStream + Unpin,
+    T::Ok: MessageWithId,
+    <T::Ok as MessageWithId>::Id: Eq + Hash,
 {
     pub fn new(transport: T) -> Self {
-        let (outgoing, incoming) = transport.split();
+        let (outgoing, incoming) = split_transport::split(transport);
 
         MultiplexClient {
-            request_sink: Arc::new(Mutex::new(outgoing)),
+            request_sink: Arc::new(Mutex::new(SharedSink::new(outgoing))),
             response_dispatcher: Arc::new(MultiplexDispatcher::new(incoming)),
+            default_timeout: None,
+        }
+    }
+
+    pub fn with_timeout(transport: T, timeout: Duration) -> Self {
+        let mut client = Self::new(transport);
+
+        client.default_timeout = Some(timeout);
+        client
+    }
+
+    pub fn call<R>(&self, request: R) -> MultiplexClientFuture<T, R>
+    where
+        T: Sink<R>,
+        R: MessageWithId<Id = <T::Ok as MessageWithId>::Id>,
+    {
+        let untimed_future = self.send(request);
+
+        match self.default_timeout {
+            Some(timeout) => ClientTimeout::new(untimed_future, timeout),
+            None => ClientTimeout::without_timeout(untimed_future),
+        }
+    }
+
+    /// Waits until the transport can accept another request.
+    pub fn poll_ready<R>(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), ErrorAlias<T, R>>>
+    where
+        T: Sink<R>,
+    {
+        let mut sink = self.request_sink
+            .lock()
+            .expect("a thread panicked while holding MultiplexClient locked");
+
+        Sink::<R>::poll_ready(Pin::new(&mut *sink), context)
+            .map_err(ClientError::SendError)
+    }
+
+    pub fn call_with_timeout<R>(
+        &self,
+        request: R,
+        timeout: Duration,
+    ) -> MultiplexClientFuture<T, R>
+    where
+        T: Sink<R>,
+        R: MessageWithId<Id = <T::Ok as MessageWithId>::Id>,
+    {
+        ClientTimeout::new(self.send(request), timeout)
+    }
+
+    pub fn is_waiting_for(&self, id: &<T::Ok as MessageWithId>::Id) -> bool {
+        self.response_dispatcher.is_registered(id)
+    }
+
+    pub fn discarded_responses(&self) -> usize {
+        self.response_dispatcher.discarded_responses()
+    }
+
+    /// Chooses how request and response ID errors are handled.
+    ///
+    /// The default policy is `IdErrorPolicy::DropMessage`.
+    pub fn set_id_error_policy(&self, policy: IdErrorPolicy) {
+        self.response_dispatcher.set_id_error_policy(policy);
+    }
+
+    fn send<R>(&self, request: R) -> UntimedFuture<T, R>
+    where
+        T: Sink<R>,
+        R: MessageWithId<Id = <T::Ok as MessageWithId>::Id>,
+    {
+        let sink = self.request_sink.clone();
+        let dispatcher = self.response_dispatcher.clone();
+
+        match MultiplexDispatcher::register(dispatcher.clone(), &request) {
+            Ok(registration) => {
+                let send =
+                    RequestSender::new(sink, dispatcher, request, registration);
+                let receiver = ClientReceiver::new(send);
+
+                Either::Left(receiver.try_flatten())
+            }
+            Err(error) => Either::Right(future::err(error.into_client_error())),
         }
     }
 }
 
-impl<T> Service for MultiplexClient<T>
+/// The clones share the same connection.
+impl<T> Clone for MultiplexClient<T>
 where
-    T: Stream + Sink,
-    T::Item: MessageWithId,
-    T::SinkItem: MessageWithId<Id = <T::Item as MessageWithId>::Id>,
-    <T::Item as MessageWithId>::Id: Eq + Hash,
+    T: TryStream + Unpin,
+    T::Ok: MessageWithId,
+    <T::Ok as MessageWithId>::Id: Eq + Hash,
 {
-    type Request = T::SinkItem;
-    type Response = T::Item;
-    type Error = ClientError<T::Error, T::SinkError>;
-    type Future = Flatten<
-        ClientReceiver<
-            MultiplexDispatcher<SplitStream<T>>,
-            RequestSender<SplitSink<T>, <T::Item as MessageWithId>::Id>,
-        >,
-    >;
+    fn clone(&self) -> Self {
+        MultiplexClient {
+            request_sink: self.request_sink.clone(),
+            response_dispatcher: self.response_dispatcher.clone(),
+            default_timeout: self.default_timeout,
+        }
+    }
+}
 
-    fn call(&self, request: Self::Request) -> Self::Future {
-        let id = request.id();
-        let sink = self.request_sink.clone();
-        let dispatcher = self.response_dispatcher.clone();
-        let send = RequestSender::new(sink, request, id);
-        let receiver = ClientReceiver::new(dispatcher, send);
+impl<T, R> Service<R> for MultiplexClient<T>
+where
+    T: TryStream + Sink<R> + Unpin,
+    T::Ok: MessageWithId,
+    R: MessageWithId<Id = <T::Ok as MessageWithId>::Id>,
+    <T::Ok as MessageWithId>::Id: Eq + Hash,
+{
+    type Response = T::Ok;
+    type Error = ErrorAlias<T, R>;
+    type Future = MultiplexClientFuture<T, R>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        MultiplexClient::poll_ready(self, context)
+    }
 
-        receiver.flatten()
+    fn call(&mut self, request: R) -> Self::Future {
+        MultiplexClient::call(self, request)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use futures::Async;
-    use futures::sync::mpsc;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::mpsc as std_mpsc;
+    use std::task::{Context, Poll};
+    use std::thread;
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::task;
+    use futures::{FutureExt, SinkExt, Stream, StreamExt};
 
     use super::*;
-    use tests::common::SinkStream;
+    use crate::tests::common::{NotifyFlag, SinkStream};
 
     #[test]
     fn simple_operation() {
@@ -97,8 +229,8 @@ mod tests {
         in_tx.try_send(first_response.clone()).unwrap();
         in_tx.try_send(second_response.clone()).unwrap();
 
-        let calls = first_call.join(second_call);
-        let (first_result, second_result) = calls.wait().unwrap();
+        let calls = future::try_join(first_call, second_call);
+        let (first_result, second_result) = block_on(calls).unwrap();
 
         assert_eq!(receive(&mut out_rx), first_request);
         assert_eq!(receive(&mut out_rx), second_request);
@@ -128,8 +260,8 @@ mod tests {
         in_tx.try_send(first_response.clone()).unwrap();
         in_tx.try_send(second_response.clone()).unwrap();
 
-        let calls = second_call.join(first_call);
-        let (second_result, first_result) = calls.wait().unwrap();
+        let calls = future::try_join(second_call, first_call);
+        let (second_result, first_result) = block_on(calls).unwrap();
 
         assert_eq!(receive(&mut out_rx), second_request);
         assert_eq!(receive(&mut out_rx), first_request);
@@ -159,8 +291,8 @@ mod tests {
         in_tx.try_send(second_response.clone()).unwrap();
         in_tx.try_send(first_response.clone()).unwrap();
 
-        let calls = first_call.join(second_call);
-        let (first_result, second_result) = calls.wait().unwrap();
+        let calls = future::try_join(first_call, second_call);
+        let (first_result, second_result) = block_on(calls).unwrap();
 
         assert_eq!(receive(&mut out_rx), first_request);
         assert_eq!(receive(&mut out_rx), second_request);
@@ -169,21 +301,313 @@ mod tests {
         assert_eq!(second_result, second_response);
     }
 
+    #[tokio::test]
+    async fn timeout_only_affects_unanswered_request() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let timeout = Duration::from_millis(10);
+        let client = MultiplexClient::with_timeout(transport, timeout);
+        let client_service = &client;
+
+        let first_call = client_service.call((79, "first request".to_owned()));
+        let second_call =
+            client_service.call((1094, "second request".to_owned()));
+
+        let second_response = (1094, "second response".to_owned());
+
+        in_tx.try_send(second_response.clone()).unwrap();
+
+        match first_call.await {
+            Err(ClientError::Timeout) => {}
+            _ => panic!("call did not time out"),
+        }
+
+        assert_eq!(second_call.await.unwrap(), second_response);
+    }
+
+    #[tokio::test]
+    async fn late_responses_are_discarded() {
+        let (mut in_tx, in_rx) = mpsc::channel(3);
+        let (out_tx, mut out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let timeout = Duration::from_millis(10);
+        let client = MultiplexClient::new(transport);
+
+        let first_request = (79, "first request".to_owned());
+        let second_request = (1094, "second request".to_owned());
+
+        let first_call =
+            client.call_with_timeout(first_request.clone(), timeout);
+
+        assert!(first_call.await.is_err());
+        assert_eq!(receive(&mut out_rx), first_request);
+
+        let second_call = client.call(second_request.clone());
+
+        let first_response = (79, "first response".to_owned());
+        let second_response = (1094, "second response".to_owned());
+        let unsolicited_response = (512, "unsolicited response".to_owned());
+
+        in_tx.try_send(first_response).unwrap();
+        in_tx.try_send(unsolicited_response).unwrap();
+        in_tx.try_send(second_response.clone()).unwrap();
+
+        assert_eq!(second_call.await.unwrap(), second_response);
+        assert_eq!(receive(&mut out_rx), second_request);
+        assert_eq!(client.discarded_responses(), 2);
+        assert!(client.response_dispatcher.is_empty());
+    }
+
+    #[test]
+    fn duplicate_request_ids_fail_the_call() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = MultiplexClient::new(transport);
+
+        let first_request = (79, "first request".to_owned());
+        let duplicate_request = (79, "duplicate request".to_owned());
+
+        let first_call = client.call(first_request.clone());
+        let duplicate_call = client.call(duplicate_request);
+
+        match block_on(duplicate_call) {
+            Err(ClientError::DuplicateRequestId) => {}
+            _ => panic!("duplicate request ID was not detected"),
+        }
+
+        let first_response = (79, "first response".to_owned());
+
+        in_tx.try_send(first_response.clone()).unwrap();
+
+        assert_eq!(block_on(first_call).unwrap(), first_response);
+
+        drop(client);
+
+        let sent_requests: Vec<_> = block_on(out_rx.collect());
+
+        assert_eq!(sent_requests, vec![first_request]);
+    }
+
+    #[test]
+    fn unexpected_responses_fail_the_oldest_call() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(3);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = MultiplexClient::new(transport);
+
+        client.set_id_error_policy(IdErrorPolicy::FailCall);
+
+        let first_call = client.call((79, "first request".to_owned()));
+        let second_call = client.call((1094, "second request".to_owned()));
+
+        let unexpected_response = (512, "unexpected response".to_owned());
+        let second_response = (1094, "second response".to_owned());
+
+        in_tx.try_send(unexpected_response).unwrap();
+        in_tx.try_send(second_response.clone()).unwrap();
+
+        assert_eq!(block_on(second_call).unwrap(), second_response);
+
+        match block_on(first_call) {
+            Err(ClientError::UnexpectedResponseId) => {}
+            _ => panic!("unexpected response ID did not fail the call"),
+        }
+
+        assert_eq!(client.discarded_responses(), 1);
+        assert!(client.response_dispatcher.is_empty());
+    }
+
+    #[test]
+    fn unexpected_responses_can_close_the_connection() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = MultiplexClient::new(transport);
+
+        client.set_id_error_policy(IdErrorPolicy::CloseConnection);
+
+        let first_call = client.call((79, "first request".to_owned()));
+        let second_call = client.call((1094, "second request".to_owned()));
+
+        let unexpected_response = (512, "unexpected response".to_owned());
+
+        in_tx.try_send(unexpected_response).unwrap();
+
+        match block_on(first_call) {
+            Err(ClientError::UnexpectedResponseId) => {}
+            _ => panic!("unexpected response ID was not detected"),
+        }
+
+        match block_on(second_call) {
+            Err(ClientError::UnexpectedResponseId) => {}
+            _ => panic!("pending call did not fail"),
+        }
+
+        assert_eq!(client.discarded_responses(), 1);
+    }
+
+    #[test]
+    fn id_errors_can_close_the_connection() {
+        let (_in_tx, in_rx) = mpsc::channel::<(i32, String)>(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = MultiplexClient::new(transport);
+
+        client.set_id_error_policy(IdErrorPolicy::CloseConnection);
+
+        let first_call = client.call((79, "first request".to_owned()));
+        let duplicate_call = client.call((79, "duplicate request".to_owned()));
+
+        assert!(block_on(duplicate_call).is_err());
+
+        match block_on(first_call) {
+            Err(ClientError::DuplicateRequestId) => {}
+            _ => panic!("pending call did not fail"),
+        }
+
+        match block_on(client.call((1094, "late request".to_owned()))) {
+            Err(ClientError::DuplicateRequestId) => {}
+            _ => panic!("call on closed connection did not fail"),
+        }
+    }
+
+    #[test]
+    fn source_polling_is_handed_over() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = MultiplexClient::new(transport);
+        let client_service = &client;
+
+        let first_flag = NotifyFlag::new();
+        let second_flag = NotifyFlag::new();
+        let first_waker = task::waker(first_flag.clone());
+        let second_waker = task::waker(second_flag.clone());
+
+        let first_request = (79, "first request".to_owned());
+        let second_request = (1094, "second request".to_owned());
+
+        let mut first_call = client_service.call(first_request);
+        let mut second_call = client_service.call(second_request);
+
+        let poll_first = |call: &mut MultiplexClientFuture<_, (i32, String)>| {
+            let mut context = Context::from_waker(&first_waker);
+
+            Pin::new(call).poll(&mut context).map(Result::ok)
+        };
+        let poll_second =
+            |call: &mut MultiplexClientFuture<_, (i32, String)>| {
+                let mut context = Context::from_waker(&second_waker);
+
+                Pin::new(call).poll(&mut context).map(Result::ok)
+            };
+
+        assert!(poll_first(&mut first_call).is_pending());
+        assert!(poll_second(&mut second_call).is_pending());
+
+        let first_response = (79, "first response".to_owned());
+        let second_response = (1094, "second response".to_owned());
+
+        in_tx.try_send(first_response.clone()).unwrap();
+
+        second_flag.take();
+
+        assert_eq!(
+            poll_first(&mut first_call),
+            Poll::Ready(Some(first_response))
+        );
+        assert!(second_flag.take());
+        assert!(poll_second(&mut second_call).is_pending());
+
+        in_tx.try_send(second_response.clone()).unwrap();
+
+        assert!(second_flag.take());
+        assert_eq!(
+            poll_second(&mut second_call),
+            Poll::Ready(Some(second_response))
+        );
+    }
+
+    #[test]
+    fn concurrent_calls_on_separate_tasks() {
+        const THREADS: u32 = 16;
+        const CALLS_PER_THREAD: u32 = 20;
+
+        let (in_tx, in_rx) = mpsc::channel(1);
+        let (out_tx, out_rx) = mpsc::channel(1);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = Arc::new(MultiplexClient::new(transport));
+
+        let server = thread::spawn(move || {
+            block_on(
+                out_rx
+                    .map(|(id, request): (u32, String)| {
+                        Ok((id, request.to_uppercase()))
+                    })
+                    .forward(in_tx.sink_map_err(|_| ())),
+            )
+        });
+
+        let (result_tx, result_rx) = std_mpsc::channel();
+
+        let callers: Vec<_> = (0..THREADS)
+            .map(|thread_index| {
+                let client = client.clone();
+                let result_tx = result_tx.clone();
+
+                thread::spawn(move || {
+                    for call_index in 0..CALLS_PER_THREAD {
+                        let id = thread_index * CALLS_PER_THREAD + call_index;
+                        let request = (id, format!("request {}", id));
+                        let response = block_on(client.call(request.clone()));
+
+                        result_tx.send((request, response.ok())).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..(THREADS * CALLS_PER_THREAD) {
+            let ((id, request), response) = result_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("a call was never woken up to receive its response");
+
+            assert_eq!(response, Some((id, request.to_uppercase())));
+        }
+
+        for caller in callers {
+            caller.join().unwrap();
+        }
+
+        assert_eq!(client.discarded_responses(), 0);
+
+        drop(client);
+
+        assert!(server.join().unwrap().is_ok());
+    }
+
     fn receive<S>(stream: &mut S) -> S::Item
     where
-        S: Stream,
+        S: Stream + Unpin,
     {
-        match stream.poll() {
-            Ok(Async::Ready(Some(item))) => item,
-            Ok(Async::Ready(None)) => {
+        match stream.next().now_or_never() {
+            Some(Some(item)) => item,
+            Some(None) => {
                 panic!("failed to receive item from stream: Stream is empty");
             }
-            Ok(Async::NotReady) => {
+            None => {
                 panic!("failed to receive item from stream: Not Ready");
             }
-            Err(_) => {
-                panic!("failed to receive item from stream: Error");
-            }
         }
     }
 }
@@ -1,64 +1,288 @@
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::hash::Hash;
+use std::mem;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
 
-use futures::{Async, Poll, Stream};
+use futures::TryStream;
 
-use super::dispatcher::Dispatcher;
+use super::dispatcher::{DispatchError, Dispatcher, Failure};
+use super::id_error_policy::IdErrorPolicy;
 use super::message_with_id::MessageWithId;
 use super::receiver::Receiver;
 
+struct ResponseQueue<I: MessageWithId> {
+    slots: HashMap<I::Id, ResponseSlot<I>>,
+    registrations: u64,
+    policy: IdErrorPolicy,
+    closed_by: Option<Failure>,
+}
+
+/// The slot of a call, which is waiting since its registration with the
+/// given sequence number.
+enum ResponseSlot<I> {
+    Waiting(u64, Option<Waker>),
+    Ready(I),
+    Failed(Failure),
+}
+
 pub struct MultiplexDispatcher<T>
 where
-    T: Stream,
-    T::Item: MessageWithId,
-    <T::Item as MessageWithId>::Id: Eq + Hash,
+    T: TryStream + Unpin,
+    T::Ok: MessageWithId,
+    <T::Ok as MessageWithId>::Id: Eq + Hash,
 {
-    source: Arc<Mutex<T>>,
-    queue: Arc<Mutex<HashMap<<T::Item as MessageWithId>::Id, T::Item>>>,
+    source: Arc<Mutex<Option<T>>>,
+    queue: Arc<Mutex<ResponseQueue<T::Ok>>>,
+    discarded_responses: AtomicUsize,
 }
 
 impl<T> MultiplexDispatcher<T>
 where
-    T: Stream,
-    T::Item: MessageWithId,
-    <T::Item as MessageWithId>::Id: Eq + Hash,
+    T: TryStream + Unpin,
+    T::Ok: MessageWithId,
+    <T::Ok as MessageWithId>::Id: Eq + Hash,
 {
     pub fn new(source: T) -> Self {
+        let queue = ResponseQueue {
+            slots: HashMap::new(),
+            registrations: 0,
+            policy: IdErrorPolicy::DropMessage,
+            closed_by: None,
+        };
+
         MultiplexDispatcher {
-            source: Arc::new(Mutex::new(source)),
-            queue: Arc::new(Mutex::new(HashMap::new())),
+            source: Arc::new(Mutex::new(Some(source))),
+            queue: Arc::new(Mutex::new(queue)),
+            discarded_responses: AtomicUsize::new(0),
         }
     }
 
-    fn get_from_source(&self) -> Poll<(), T::Error> {
-        let mut source = Self::lock(&self.source);
+    pub fn register<M>(
+        arc_self: Arc<Self>,
+        request: &M,
+    ) -> Result<Receiver<Self>, DispatchError<T::Error>>
+    where
+        M: MessageWithId<Id = <T::Ok as MessageWithId>::Id>,
+    {
+        let closes_connection = {
+            let mut queue = Self::lock(&arc_self.queue);
 
-        if let Some(item) = try_ready!(source.poll()) {
-            let id = item.id();
-            let mut queue = Self::lock(&self.queue);
+            if let Some(error) = queue.closed_by {
+                return Err(DispatchError::Failure(error));
+            }
+
+            let registration = queue.registrations;
+
+            queue.registrations += 1;
+
+            if let Entry::Vacant(slot) = queue.slots.entry(request.id()) {
+                slot.insert(ResponseSlot::Waiting(registration, None));
+
+                return Ok(Receiver::new(arc_self.clone(), request.id()));
+            }
 
-            queue.insert(id, item);
+            let closes_connection =
+                queue.policy == IdErrorPolicy::CloseConnection;
 
-            while let Some(item) = try_ready!(source.poll()) {
-                let id = item.id();
-                queue.insert(id, item);
+            if closes_connection {
+                Self::close(&mut queue, Failure::DuplicateRequestId);
             }
+
+            closes_connection
+        };
+
+        // The source is locked before the queue when polled, so it can only
+        // be dropped after the queue is released.
+        if closes_connection {
+            Self::lock(&arc_self.source).take();
+        }
+
+        Err(DispatchError::Failure(Failure::DuplicateRequestId))
+    }
+
+    pub fn set_id_error_policy(&self, policy: IdErrorPolicy) {
+        Self::lock(&self.queue).policy = policy;
+    }
+
+    pub fn is_registered(&self, id: &<T::Ok as MessageWithId>::Id) -> bool {
+        Self::lock(&self.queue).slots.contains_key(id)
+    }
+
+    pub fn discarded_responses(&self) -> usize {
+        self.discarded_responses.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        Self::lock(&self.queue).slots.is_empty()
+    }
+
+    fn get_from_source(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), DispatchError<T::Error>>> {
+        let mut source_guard = Self::lock(&self.source);
+        let result = match *source_guard {
+            Some(ref mut source) => self.poll_source(source, context),
+            None => return Poll::Ready(Ok(())),
+        };
+
+        let is_closed = match result {
+            Poll::Pending
+            | Poll::Ready(Err(DispatchError::ReceiveError(_))) => false,
+            _ => Self::lock(&self.queue).closed_by.is_some(),
+        };
+
+        if is_closed {
+            source_guard.take();
         }
 
-        Ok(Async::Ready(()))
+        result
+    }
+
+    fn poll_source(
+        &self,
+        source: &mut T,
+        context: &mut Context,
+    ) -> Poll<Result<(), DispatchError<T::Error>>> {
+        let mut queue = None;
+
+        loop {
+            let item = ready!(Pin::new(&mut *source).try_poll_next(context))
+                .transpose()
+                .map_err(DispatchError::ReceiveError)?;
+            let queue = queue.get_or_insert_with(|| Self::lock(&self.queue));
+
+            match item {
+                Some(item) => self.enqueue(queue, item)?,
+                None => {
+                    Self::close(queue, Failure::ConnectionLost);
+
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+    }
+
+    fn enqueue(
+        &self,
+        queue: &mut ResponseQueue<T::Ok>,
+        item: T::Ok,
+    ) -> Result<(), DispatchError<T::Error>> {
+        if let Some(slot) = queue.slots.get_mut(&item.id()) {
+            if let ResponseSlot::Waiting(..) = *slot {
+                Self::fill(slot, ResponseSlot::Ready(item));
+
+                return Ok(());
+            }
+        }
+
+        self.discarded_responses.fetch_add(1, Ordering::Relaxed);
+
+        match queue.policy {
+            IdErrorPolicy::FailCall => {
+                Self::fail_oldest_call(queue, Failure::UnexpectedResponseId);
+
+                Ok(())
+            }
+            IdErrorPolicy::DropMessage => Ok(()),
+            IdErrorPolicy::CloseConnection => {
+                Self::close(queue, Failure::UnexpectedResponseId);
+
+                Err(DispatchError::Failure(Failure::UnexpectedResponseId))
+            }
+        }
+    }
+
+    fn fail_oldest_call(queue: &mut ResponseQueue<T::Ok>, error: Failure) {
+        let oldest_slot = queue
+            .slots
+            .values_mut()
+            .filter_map(|slot| match *slot {
+                ResponseSlot::Waiting(registration, _) => {
+                    Some((registration, slot))
+                }
+                _ => None,
+            })
+            .min_by_key(|&(registration, _)| registration);
+
+        if let Some((_, slot)) = oldest_slot {
+            Self::fill(slot, ResponseSlot::Failed(error));
+        }
+    }
+
+    fn fill(slot: &mut ResponseSlot<T::Ok>, filled_slot: ResponseSlot<T::Ok>) {
+        let previous_slot = mem::replace(slot, filled_slot);
+
+        if let ResponseSlot::Waiting(_, Some(waker)) = previous_slot {
+            waker.wake();
+        }
+    }
+
+    fn close(queue: &mut ResponseQueue<T::Ok>, error: Failure) {
+        queue.closed_by = Some(error);
+
+        for slot in queue.slots.values() {
+            if let ResponseSlot::Waiting(_, Some(ref waker)) = *slot {
+                waker.wake_by_ref();
+            }
+        }
     }
 
     fn remove_if_ready(
         &self,
-        id: &<T::Item as MessageWithId>::Id,
-    ) -> Option<T::Item> {
+        id: &<T::Ok as MessageWithId>::Id,
+        park: Option<&Waker>,
+    ) -> Result<Option<T::Ok>, Failure> {
         let mut queue = Self::lock(&self.queue);
 
-        queue.remove(id)
+        // A failed slot is left for the receiver to deregister, so that its
+        // ID stays in use until then.
+        let is_ready = match queue.slots.get_mut(id) {
+            Some(&mut ResponseSlot::Ready(_)) => true,
+            Some(&mut ResponseSlot::Failed(error)) => return Err(error),
+            Some(&mut ResponseSlot::Waiting(_, ref mut waiting_task)) => {
+                if let Some(waker) = park {
+                    *waiting_task = Some(waker.clone());
+                }
+
+                false
+            }
+            None => false,
+        };
+
+        if is_ready {
+            let slot = queue.slots.remove(id);
+
+            Self::wake_any(&queue);
+
+            match slot {
+                Some(ResponseSlot::Ready(item)) => Ok(Some(item)),
+                _ => unreachable!("response slot was tested to be ready"),
+            }
+        } else if let Some(error) = queue.closed_by {
+            Err(error)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Wakes up one of the waiting tasks, so that it can take over polling
+    /// the source from a task that might not poll it again.
+    fn wake_any(queue: &ResponseQueue<T::Ok>) {
+        for slot in queue.slots.values() {
+            if let ResponseSlot::Waiting(_, Some(ref waker)) = *slot {
+                waker.wake_by_ref();
+                break;
+            }
+        }
     }
 
-    fn lock<I>(item: &Arc<Mutex<I>>) -> MutexGuard<I> {
+    fn lock<I>(item: &Arc<Mutex<I>>) -> MutexGuard<'_, I> {
         item.lock().expect(
             "a thread panicked while holding the MultiplexDispatcher locked",
         )
@@ -67,30 +291,52 @@ where
 
 impl<T> Dispatcher for MultiplexDispatcher<T>
 where
-    T: Stream,
-    T::Item: MessageWithId,
-    <T::Item as MessageWithId>::Id: Eq + Hash,
+    T: TryStream + Unpin,
+    T::Ok: MessageWithId,
+    <T::Ok as MessageWithId>::Id: Eq + Hash,
 {
-    type Item = T::Item;
+    type Item = T::Ok;
     type Error = T::Error;
-    type Id = <T::Item as MessageWithId>::Id;
-    type Seed = Self::Id;
+    type Id = <T::Ok as MessageWithId>::Id;
+    type Seed = Receiver<Self>;
 
-    fn spawn_receiver(arc_self: Arc<Self>, id: Self::Seed) -> Receiver<Self> {
-        Receiver::new(arc_self, id)
+    fn spawn_receiver(
+        _arc_self: Arc<Self>,
+        receiver: Self::Seed,
+    ) -> Receiver<Self> {
+        receiver
     }
 
-    fn poll(&self, id: &Self::Id) -> Poll<Self::Item, Self::Error> {
-        if let Some(item) = self.remove_if_ready(id) {
-            Ok(Async::Ready(item))
+    fn poll(
+        &self,
+        id: &Self::Id,
+        context: &mut Context,
+    ) -> Poll<Result<Self::Item, DispatchError<Self::Error>>> {
+        let ready = self.remove_if_ready(id, None)
+            .map_err(DispatchError::Failure)?;
+
+        if let Some(item) = ready {
+            Poll::Ready(Ok(item))
         } else {
-            self.get_from_source()?;
+            if let Poll::Ready(Err(error)) = self.get_from_source(context) {
+                return Poll::Ready(Err(error));
+            }
 
-            let item = self.remove_if_ready(id)
-                .map(Async::Ready)
-                .unwrap_or(Async::NotReady);
+            let item = self.remove_if_ready(id, Some(context.waker()))
+                .map_err(DispatchError::Failure)?;
 
-            Ok(item)
+            match item {
+                Some(item) => Poll::Ready(Ok(item)),
+                None => Poll::Pending,
+            }
         }
     }
+
+    fn deregister(&self, id: &Self::Id) {
+        let mut queue = Self::lock(&self.queue);
+
+        queue.slots.remove(id);
+
+        Self::wake_any(&queue);
+    }
 }
@@ -1,50 +1,74 @@
-use futures::{Future, Poll, Sink, Stream, stream::FuturesUnordered};
-use tokio_service::Service;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use super::generic_listening_server::{ErrorAlias, GenericListeningServer};
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, Sink, TryStream};
+use tower_service::Service;
+
+use super::connection_limits::ConnectionLimits;
+use super::generic_listening_server::{
+    ErrorAlias, FutureAlias, GenericListeningServer, RequestAlias,
+    ResponseAlias,
+};
+use super::shutdown_handle::ShutdownHandle;
 
 pub struct MultiplexListeningServer<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
 {
-    listener: GenericListeningServer<
-        S,
-        T,
-        FuturesUnordered<<S::Item as Service>::Future>,
-    >,
+    listener: GenericListeningServer<S, T, FuturesUnordered<FutureAlias<S, T>>>,
 }
 
 impl<S, T> MultiplexListeningServer<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
 {
     pub fn new(services: S, transports: T) -> Self {
         MultiplexListeningServer {
             listener: GenericListeningServer::new(services, transports),
         }
     }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.listener.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.listener.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, T>) + Send + 'static,
+    {
+        self.listener.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.listener.fail_fast();
+    }
 }
 
 impl<S, T> Future for MultiplexListeningServer<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
 {
-    type Item = ();
-    type Error = ErrorAlias<S, T, S::Item, T::Item>;
+    type Output = Result<(), ErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.listener.poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.listener.poll_unpin(context)
     }
 }
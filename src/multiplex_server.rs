@@ -1,39 +1,55 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
 use futures::stream::FuturesUnordered;
-use futures::{Future, Poll, Sink, Stream};
-use tokio_service::Service;
+use futures::{FutureExt, Sink, TryStream};
+use tower_service::Service;
 
+use super::connection_limits::ConnectionLimits;
 use super::generic_server::{GenericServer, ServerErrorAlias};
+use super::shutdown_handle::ShutdownHandle;
 
 pub struct MultiplexServer<S, T>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
 {
     server: GenericServer<S, T, FuturesUnordered<S::Future>>,
 }
 
 impl<S, T> MultiplexServer<S, T>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
 {
     pub fn new(service: S, transport: T) -> Self {
         MultiplexServer {
             server: GenericServer::new(service, transport),
         }
     }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.server.set_connection_limits(limits);
+    }
 }
 
 impl<S, T> Future for MultiplexServer<S, T>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
 {
-    type Item = ();
-    type Error = ServerErrorAlias<S, T>;
+    type Output = Result<(), ServerErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.server.poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.server.poll_unpin(context)
     }
 }
 
@@ -41,15 +57,15 @@ where
 mod tests {
     use std::time::Duration;
 
-    use futures::Async;
-    use futures::sync::mpsc;
-    use tokio_core::reactor::{Core, Timeout};
+    use futures::channel::mpsc;
+    use futures::{Stream, StreamExt};
+    use tokio::time;
 
     use super::*;
-    use tests::common::{SinkStream, SlowToUpperService, ToUpperService};
+    use crate::tests::common::{SinkStream, SlowToUpperService, ToUpperService};
 
-    #[test]
-    fn simple_operation() {
+    #[tokio::test]
+    async fn simple_operation() {
         let service = ToUpperService;
 
         let (mut in_tx, in_rx) = mpsc::channel(2);
@@ -70,22 +86,17 @@ mod tests {
         in_tx
             .try_send(second_request.to_string())
             .unwrap();
-        in_tx.close().unwrap();
-
-        let mut reactor = Core::new().unwrap();
-        let timeout =
-            Timeout::new(Duration::from_secs(1), &reactor.handle()).unwrap();
+        in_tx.close_channel();
 
-        assert!(reactor.run(timeout.select2(server)).is_ok());
+        assert!(time::timeout(Duration::from_secs(1), server).await.is_ok());
 
         assert_eq!(receive(&mut out_rx), first_response);
         assert_eq!(receive(&mut out_rx), second_response);
     }
 
-    #[test]
-    fn complex_operation() {
-        let mut reactor = Core::new().unwrap();
-        let service = SlowToUpperService::new(reactor.handle());
+    #[tokio::test]
+    async fn complex_operation() {
+        let service = SlowToUpperService;
 
         let (mut in_tx, in_rx) = mpsc::channel(2);
         let (out_tx, mut out_rx) = mpsc::channel(2);
@@ -110,12 +121,9 @@ mod tests {
 
         in_tx.try_send(first_request).unwrap();
         in_tx.try_send(second_request).unwrap();
-        in_tx.close().unwrap();
+        in_tx.close_channel();
 
-        let timeout =
-            Timeout::new(Duration::from_secs(1), &reactor.handle()).unwrap();
-
-        assert!(reactor.run(timeout.select2(server)).is_ok());
+        assert!(time::timeout(Duration::from_secs(1), server).await.is_ok());
 
         assert_eq!(receive(&mut out_rx), second_response);
         assert_eq!(receive(&mut out_rx), first_response);
@@ -123,19 +131,16 @@ mod tests {
 
     fn receive<S>(stream: &mut S) -> S::Item
     where
-        S: Stream,
+        S: Stream + Unpin,
     {
-        match stream.poll() {
-            Ok(Async::Ready(Some(item))) => item,
-            Ok(Async::Ready(None)) => {
+        match stream.next().now_or_never() {
+            Some(Some(item)) => item,
+            Some(None) => {
                 panic!("failed to receive item from stream: Stream is empty");
             }
-            Ok(Async::NotReady) => {
+            None => {
                 panic!("failed to receive item from stream: Not Ready");
             }
-            Err(_) => {
-                panic!("failed to receive item from stream: Error");
-            }
         }
     }
 }
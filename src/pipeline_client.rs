@@ -1,70 +1,173 @@
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
-use futures::future::Flatten;
-use futures::stream::{SplitSink, SplitStream};
-use futures::{Future, Sink, Stream};
-use tokio_service::Service;
+use futures::future::TryFlatten;
+use futures::{Sink, TryFutureExt, TryStream};
+use tower_service::Service;
 
 use super::client_error::ClientError;
 use super::client_receiver::ClientReceiver;
+use super::client_timeout::ClientTimeout;
 use super::fifo_dispatcher::FifoDispatcher;
+use super::map_to_client_receive_error::MapToClientReceiveError;
+use super::receiver::Receiver;
 use super::request_sender::RequestSender;
+use super::shared_sink::SharedSink;
+use super::split_transport::{self, SinkHalf, StreamHalf};
 
-pub type PipelineClientFuture<T> = Flatten<
+pub type PipelineClientFuture<T, R> = ClientTimeout<UntimedFuture<T, R>>;
+
+type ResponseDispatcher<T> = FifoDispatcher<StreamHalf<T>>;
+
+type ErrorAlias<T, R> =
+    ClientError<<T as TryStream>::Error, <T as Sink<R>>::Error>;
+
+type UntimedFuture<T, R> = TryFlatten<
     ClientReceiver<
-        FifoDispatcher<SplitStream<T>>,
-        RequestSender<SplitSink<T>, ()>,
+        ResponseDispatcher<T>,
+        RequestSender<SinkHalf<T>, R, ResponseDispatcher<T>>,
+    >,
+    MapToClientReceiveError<
+        Receiver<ResponseDispatcher<T>>,
+        <T as TryStream>::Error,
+        <T as Sink<R>>::Error,
     >,
 >;
 
 pub struct PipelineClient<T>
 where
-    T: Stream + Sink,
+    T: TryStream + Unpin,
 {
-    request_sink: Arc<Mutex<SplitSink<T>>>,
-    response_dispatcher: Arc<FifoDispatcher<SplitStream<T>>>,
+    request_sink: Arc<Mutex<SharedSink<SinkHalf<T>>>>,
+    response_dispatcher: Arc<ResponseDispatcher<T>>,
+    default_timeout: Option<Duration>,
 }
 
 impl<T> PipelineClient<T>
 where
-    T: Stream + Sink,
+    T: TryStream + Unpin,
 {
     pub fn new(transport: T) -> Self {
-        let (outgoing, incoming) = transport.split();
+        let (outgoing, incoming) = split_transport::split(transport);
 
         PipelineClient {
-            request_sink: Arc::new(Mutex::new(outgoing)),
+            request_sink: Arc::new(Mutex::new(SharedSink::new(outgoing))),
             response_dispatcher: Arc::new(FifoDispatcher::new(incoming)),
+            default_timeout: None,
+        }
+    }
+
+    pub fn with_timeout(transport: T, timeout: Duration) -> Self {
+        let mut client = Self::new(transport);
+
+        client.default_timeout = Some(timeout);
+        client
+    }
+
+    pub fn call<R>(&self, request: R) -> PipelineClientFuture<T, R>
+    where
+        T: Sink<R>,
+    {
+        let untimed_future = self.send(request);
+
+        match self.default_timeout {
+            Some(timeout) => ClientTimeout::new(untimed_future, timeout),
+            None => ClientTimeout::without_timeout(untimed_future),
         }
     }
+
+    /// Waits until the transport can accept another request.
+    pub fn poll_ready<R>(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), ErrorAlias<T, R>>>
+    where
+        T: Sink<R>,
+    {
+        let mut sink = self.request_sink
+            .lock()
+            .expect("a thread panicked while holding PipelineClient locked");
+
+        Sink::<R>::poll_ready(Pin::new(&mut *sink), context)
+            .map_err(ClientError::SendError)
+    }
+
+    pub fn call_with_timeout<R>(
+        &self,
+        request: R,
+        timeout: Duration,
+    ) -> PipelineClientFuture<T, R>
+    where
+        T: Sink<R>,
+    {
+        ClientTimeout::new(self.send(request), timeout)
+    }
+
+    fn send<R>(&self, request: R) -> UntimedFuture<T, R>
+    where
+        T: Sink<R>,
+    {
+        let sink = self.request_sink.clone();
+        let dispatcher = self.response_dispatcher.clone();
+        let send = RequestSender::new(sink, dispatcher, request, ());
+        let receiver = ClientReceiver::new(send);
+
+        receiver.try_flatten()
+    }
 }
 
-impl<T> Service for PipelineClient<T>
+/// The clones share the same connection.
+impl<T> Clone for PipelineClient<T>
 where
-    T: Stream + Sink,
+    T: TryStream + Unpin,
 {
-    type Request = T::SinkItem;
-    type Response = T::Item;
-    type Error = ClientError<T::Error, T::SinkError>;
-    type Future = PipelineClientFuture<T>;
+    fn clone(&self) -> Self {
+        PipelineClient {
+            request_sink: self.request_sink.clone(),
+            response_dispatcher: self.response_dispatcher.clone(),
+            default_timeout: self.default_timeout,
+        }
+    }
+}
 
-    fn call(&self, request: Self::Request) -> Self::Future {
-        let sink = self.request_sink.clone();
-        let dispatcher = self.response_dispatcher.clone();
-        let send = RequestSender::new(sink, request, ());
-        let receiver = ClientReceiver::new(dispatcher, send);
+impl<T, R> Service<R> for PipelineClient<T>
+where
+    T: TryStream + Sink<R> + Unpin,
+{
+    type Response = T::Ok;
+    type Error = ErrorAlias<T, R>;
+    type Future = PipelineClientFuture<T, R>;
 
-        receiver.flatten()
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        PipelineClient::poll_ready(self, context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        PipelineClient::call(self, request)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use futures::Async;
-    use futures::sync::mpsc;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::mpsc as std_mpsc;
+    use std::task::{Context, Poll};
+    use std::thread;
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::task;
+    use futures::{future, FutureExt, SinkExt, Stream, StreamExt};
+    use tower::{ServiceBuilder, ServiceExt};
 
     use super::*;
-    use tests::common::SinkStream;
+    use crate::tests::common::{NotifyFlag, SinkStream};
 
     #[test]
     fn simple_operation() {
@@ -91,8 +194,8 @@ mod tests {
             .try_send(second_response.to_string())
             .unwrap();
 
-        let calls = first_call.join(second_call);
-        let (first_result, second_result) = calls.wait().unwrap();
+        let calls = future::try_join(first_call, second_call);
+        let (first_result, second_result) = block_on(calls).unwrap();
 
         assert_eq!(receive(&mut out_rx), first_request);
         assert_eq!(receive(&mut out_rx), second_request);
@@ -126,8 +229,8 @@ mod tests {
             .try_send(second_response.to_string())
             .unwrap();
 
-        let calls = second_call.join(first_call);
-        let (first_result, second_result) = calls.wait().unwrap();
+        let calls = future::try_join(second_call, first_call);
+        let (first_result, second_result) = block_on(calls).unwrap();
 
         assert_eq!(receive(&mut out_rx), second_request);
         assert_eq!(receive(&mut out_rx), first_request);
@@ -136,21 +239,245 @@ mod tests {
         assert_eq!(second_result, second_response);
     }
 
+    #[test]
+    fn calls_can_be_awaited() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = PipelineClient::new(transport);
+
+        in_tx.try_send("response".to_string()).unwrap();
+
+        let response = block_on(async {
+            client.call("request".to_string()).await
+        });
+
+        assert_eq!(response.unwrap(), "response");
+    }
+
+    #[tokio::test]
+    async fn can_be_wrapped_in_tower_layers() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, mut out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let mut service = ServiceBuilder::new()
+            .concurrency_limit(1)
+            .service(PipelineClient::new(transport));
+
+        in_tx.try_send("response".to_string()).unwrap();
+
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call("request".to_string());
+
+        assert_eq!(response.await.unwrap(), "response");
+        assert_eq!(receive(&mut out_rx), "request");
+    }
+
+    #[tokio::test]
+    async fn default_timeout() {
+        let (_in_tx, in_rx) = mpsc::channel::<String>(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let timeout = Duration::from_millis(10);
+        let client = PipelineClient::with_timeout(transport, timeout);
+        let client_service = &client;
+
+        let call = client_service.call("request".to_string());
+
+        match call.await {
+            Err(ClientError::Timeout) => {}
+            _ => panic!("call did not time out"),
+        }
+    }
+
+    #[tokio::test]
+    async fn per_call_timeout() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = PipelineClient::new(transport);
+
+        let short_timeout = Duration::from_millis(10);
+        let long_timeout = Duration::from_secs(10);
+
+        let first_call = client
+            .call_with_timeout("first request".to_string(), short_timeout);
+
+        match first_call.await {
+            Err(ClientError::Timeout) => {}
+            _ => panic!("call did not time out"),
+        }
+
+        let second_call = client
+            .call_with_timeout("second request".to_string(), long_timeout);
+
+        in_tx
+            .try_send("first response".to_string())
+            .unwrap();
+        in_tx
+            .try_send("second response".to_string())
+            .unwrap();
+
+        assert_eq!(second_call.await.unwrap(), "second response");
+    }
+
+    #[test]
+    fn pending_calls_fail_when_the_connection_is_lost() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(3);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = PipelineClient::new(transport);
+
+        let first_call = client.call("first request".to_string());
+        let second_call = client.call("second request".to_string());
+
+        in_tx.try_send("first response".to_string()).unwrap();
+        drop(in_tx);
+
+        assert_eq!(block_on(first_call).unwrap(), "first response");
+
+        match block_on(second_call) {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("call did not fail when the connection was lost"),
+        }
+
+        match block_on(client.call("late request".to_string())) {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("call on a lost connection did not fail"),
+        }
+    }
+
+    #[test]
+    fn source_polling_is_handed_over() {
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, _out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = PipelineClient::new(transport);
+        let client_service = &client;
+
+        let first_flag = NotifyFlag::new();
+        let second_flag = NotifyFlag::new();
+        let first_waker = task::waker(first_flag.clone());
+        let second_waker = task::waker(second_flag.clone());
+
+        let mut first_call = client_service.call("first request".to_string());
+        let mut second_call =
+            client_service.call("second request".to_string());
+
+        let poll_first = |call: &mut PipelineClientFuture<_, String>| {
+            let mut context = Context::from_waker(&first_waker);
+
+            Pin::new(call).poll(&mut context).map(Result::ok)
+        };
+        let poll_second = |call: &mut PipelineClientFuture<_, String>| {
+            let mut context = Context::from_waker(&second_waker);
+
+            Pin::new(call).poll(&mut context).map(Result::ok)
+        };
+
+        assert!(poll_first(&mut first_call).is_pending());
+        assert!(poll_second(&mut second_call).is_pending());
+
+        in_tx
+            .try_send("first response".to_string())
+            .unwrap();
+
+        second_flag.take();
+
+        assert_eq!(
+            poll_first(&mut first_call),
+            Poll::Ready(Some("first response".to_string()))
+        );
+        assert!(second_flag.take());
+        assert!(poll_second(&mut second_call).is_pending());
+
+        in_tx
+            .try_send("second response".to_string())
+            .unwrap();
+
+        assert!(second_flag.take());
+        assert_eq!(
+            poll_second(&mut second_call),
+            Poll::Ready(Some("second response".to_string()))
+        );
+    }
+
+    #[test]
+    fn concurrent_calls_on_separate_tasks() {
+        const THREADS: usize = 16;
+        const CALLS_PER_THREAD: usize = 20;
+
+        let (in_tx, in_rx) = mpsc::channel(1);
+        let (out_tx, out_rx) = mpsc::channel(1);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let client = Arc::new(PipelineClient::new(transport));
+
+        let server = thread::spawn(move || {
+            block_on(
+                out_rx
+                    .map(|request: String| Ok(request.to_uppercase()))
+                    .forward(in_tx.sink_map_err(|_| ())),
+            )
+        });
+
+        let (result_tx, result_rx) = std_mpsc::channel();
+
+        let callers: Vec<_> = (0..THREADS)
+            .map(|thread_index| {
+                let client = client.clone();
+                let result_tx = result_tx.clone();
+
+                thread::spawn(move || {
+                    for call_index in 0..CALLS_PER_THREAD {
+                        let request =
+                            format!("request {}-{}", thread_index, call_index);
+                        let response = block_on(client.call(request.clone()));
+
+                        result_tx.send((request, response.ok())).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..(THREADS * CALLS_PER_THREAD) {
+            let (request, response) = result_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("a call was never woken up to receive its response");
+
+            assert_eq!(response, Some(request.to_uppercase()));
+        }
+
+        for caller in callers {
+            caller.join().unwrap();
+        }
+
+        drop(client);
+
+        assert!(server.join().unwrap().is_ok());
+    }
+
     fn receive<S>(stream: &mut S) -> S::Item
     where
-        S: Stream,
+        S: Stream + Unpin,
     {
-        match stream.poll() {
-            Ok(Async::Ready(Some(item))) => item,
-            Ok(Async::Ready(None)) => {
+        match stream.next().now_or_never() {
+            Some(Some(item)) => item,
+            Some(None) => {
                 panic!("failed to receive item from stream: Stream is empty");
             }
-            Ok(Async::NotReady) => {
+            None => {
                 panic!("failed to receive item from stream: Not Ready");
             }
-            Err(_) => {
-                panic!("failed to receive item from stream: Error");
-            }
         }
     }
 }
@@ -1,50 +1,211 @@
-use futures::{Future, Poll, Sink, Stream, stream::FuturesOrdered};
-use tokio_service::Service;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use super::generic_listening_server::{ErrorAlias, GenericListeningServer};
+use futures::stream::FuturesOrdered;
+use futures::{FutureExt, Sink, TryStream};
+use tower_service::Service;
+
+use super::connection_limits::ConnectionLimits;
+use super::generic_listening_server::{
+    ErrorAlias, FutureAlias, GenericListeningServer, RequestAlias,
+    ResponseAlias,
+};
+use super::shutdown_handle::ShutdownHandle;
 
 pub struct PipelineListeningServer<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
 {
-    listener: GenericListeningServer<
-        S,
-        T,
-        FuturesOrdered<<S::Item as Service>::Future>,
-    >,
+    listener: GenericListeningServer<S, T, FuturesOrdered<FutureAlias<S, T>>>,
 }
 
 impl<S, T> PipelineListeningServer<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
 {
     pub fn new(services: S, transports: T) -> Self {
         PipelineListeningServer {
             listener: GenericListeningServer::new(services, transports),
         }
     }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.listener.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.listener.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, T>) + Send + 'static,
+    {
+        self.listener.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.listener.fail_fast();
+    }
 }
 
 impl<S, T> Future for PipelineListeningServer<S, T>
 where
-    S: Stream,
-    S::Item: Service,
-    T: Stream,
-    T::Item: Sink<SinkItem = <S::Item as Service>::Response>
-        + Stream<Item = <S::Item as Service>::Request>,
+    S: TryStream + Unpin,
+    S::Ok: Service<RequestAlias<T>>,
+    T: TryStream + Unpin,
+    T::Ok: TryStream + Sink<ResponseAlias<S, T>> + Unpin,
 {
-    type Item = ();
-    type Error = ErrorAlias<S, T, S::Item, T::Item>;
+    type Output = Result<(), ErrorAlias<S, T>>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.listener.poll_unpin(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::channel::mpsc;
+    use futures::{stream, StreamExt};
+    use tokio::time;
+
+    use super::*;
+    use crate::listening_server_error::ListeningServerError;
+    use crate::tests::common::{SinkStream, ToUpperService};
+
+    #[tokio::test]
+    async fn shutdown_stops_accepting_connections() {
+        let services = stream::iter(vec![
+            Ok::<_, ()>(ToUpperService),
+            Ok(ToUpperService),
+        ]);
+        let (transports_tx, transports_rx) = mpsc::unbounded();
+
+        let (mut first_in_tx, first_in_rx) = mpsc::channel(1);
+        let (first_out_tx, first_out_rx) = mpsc::channel(1);
+        let (mut second_in_tx, second_in_rx) = mpsc::channel(1);
+        let (second_out_tx, second_out_rx) = mpsc::channel(1);
+
+        transports_tx
+            .unbounded_send(SinkStream::new(first_out_tx, first_in_rx))
+            .unwrap();
+
+        let transports = transports_rx.map(Ok::<_, ()>);
+        let mut server = PipelineListeningServer::new(services, transports);
+        let shutdown_handle = server.shutdown_handle();
+
+        first_in_tx.try_send("first request".to_string()).unwrap();
+
+        let start_up = Duration::from_millis(10);
+
+        assert!(time::timeout(start_up, &mut server).await.is_err());
+
+        shutdown_handle.shutdown();
+        transports_tx
+            .unbounded_send(SinkStream::new(second_out_tx, second_in_rx))
+            .unwrap();
+        second_in_tx.try_send("second request".to_string()).unwrap();
+
+        match time::timeout(Duration::from_secs(1), server).await {
+            Ok(Ok(())) => {}
+            _ => panic!("server did not shut down"),
+        }
+
+        let first_responses: Vec<_> = first_out_rx.collect().await;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.listener.poll()
+        assert_eq!(first_responses, vec!["FIRST REQUEST"]);
+        assert_eq!(second_out_rx.count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn connection_failures_are_isolated() {
+        let services = stream::iter(vec![
+            Ok::<_, ()>(ToUpperService),
+            Ok(ToUpperService),
+        ]);
+        let (transports_tx, transports_rx) = mpsc::unbounded();
+
+        let (mut failing_in_tx, failing_in_rx) = mpsc::channel(1);
+        let (failing_out_tx, failing_out_rx) = mpsc::channel(1);
+        let (mut working_in_tx, working_in_rx) = mpsc::channel(1);
+        let (working_out_tx, working_out_rx) = mpsc::channel(1);
+
+        drop(failing_out_rx);
+
+        transports_tx
+            .unbounded_send(SinkStream::new(failing_out_tx, failing_in_rx))
+            .unwrap();
+        transports_tx
+            .unbounded_send(SinkStream::new(working_out_tx, working_in_rx))
+            .unwrap();
+
+        let failures = Arc::new(AtomicUsize::new(0));
+        let reported_failures = failures.clone();
+
+        let transports = transports_rx.map(Ok::<_, ()>);
+        let mut server = PipelineListeningServer::new(services, transports);
+        let shutdown_handle = server.shutdown_handle();
+
+        server.on_connection_error(move |_| {
+            reported_failures.fetch_add(1, Ordering::SeqCst);
+        });
+
+        failing_in_tx.try_send("failing request".to_string()).unwrap();
+        working_in_tx.try_send("working request".to_string()).unwrap();
+
+        let start_up = Duration::from_millis(10);
+
+        if time::timeout(start_up, &mut server).await.is_ok() {
+            panic!("server stopped because of a connection failure");
+        }
+
+        shutdown_handle.shutdown();
+
+        assert!(server.await.is_ok());
+        assert_eq!(failures.load(Ordering::SeqCst), 1);
+
+        let responses: Vec<_> = working_out_rx.collect().await;
+
+        assert_eq!(responses, vec!["WORKING REQUEST"]);
+    }
+
+    #[tokio::test]
+    async fn fail_fast_stops_the_server() {
+        let services = stream::iter(vec![Ok::<_, ()>(ToUpperService)]);
+        let (transports_tx, transports_rx) = mpsc::unbounded();
+
+        let (mut in_tx, in_rx) = mpsc::channel(1);
+        let (out_tx, out_rx) = mpsc::channel(1);
+
+        drop(out_rx);
+
+        transports_tx
+            .unbounded_send(SinkStream::new(out_tx, in_rx))
+            .unwrap();
+
+        let transports = transports_rx.map(Ok::<_, ()>);
+        let mut server = PipelineListeningServer::new(services, transports);
+
+        server.fail_fast();
+        in_tx.try_send("failing request".to_string()).unwrap();
+
+        match server.await {
+            Err(ListeningServerError::ServerError(_)) => {}
+            _ => panic!("server did not fail with its connection"),
+        }
     }
 }
@@ -1,55 +1,74 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
 use futures::stream::FuturesOrdered;
-use futures::{Future, Poll, Sink, Stream};
-use tokio_service::Service;
+use futures::{FutureExt, Sink, TryStream};
+use tower_service::Service;
 
+use super::connection_limits::ConnectionLimits;
 use super::generic_server::{GenericServer, ServerErrorAlias};
+use super::shutdown_handle::ShutdownHandle;
 
 pub struct PipelineServer<S, T>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
 {
     server: GenericServer<S, T, FuturesOrdered<S::Future>>,
 }
 
 impl<S, T> PipelineServer<S, T>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
 {
     pub fn new(service: S, transport: T) -> Self {
         PipelineServer {
             server: GenericServer::new(service, transport),
         }
     }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.server.set_connection_limits(limits);
+    }
 }
 
 impl<S, T> Future for PipelineServer<S, T>
 where
-    S: Service,
-    T: Stream<Item = S::Request> + Sink<SinkItem = S::Response>,
+    S: Service<T::Ok>,
+    T: TryStream + Sink<S::Response> + Unpin,
 {
-    type Item = ();
-    type Error = ServerErrorAlias<S, T>;
+    type Output = Result<(), ServerErrorAlias<S, T>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.server.poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.server.poll_unpin(context)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::num::NonZeroUsize;
     use std::time::Duration;
 
-    use futures::Async;
-    use futures::sync::mpsc;
-    use tokio_core::reactor::{Core, Timeout};
+    use futures::channel::mpsc;
+    use futures::{future, Stream, StreamExt};
+    use tokio::time::{self, Instant};
+    use tower::limit::ConcurrencyLimit;
 
     use super::*;
-    use tests::common::{SinkStream, ToUpperService};
+    use crate::connection_limits::ConnectionLimits;
+    use crate::tests::common::{SinkStream, SlowToUpperService, ToUpperService};
 
-    #[test]
-    fn simple_operation() {
+    #[tokio::test]
+    async fn simple_operation() {
         let service = ToUpperService;
 
         let (mut in_tx, in_rx) = mpsc::channel(2);
@@ -70,33 +89,219 @@ mod tests {
         in_tx
             .try_send(second_request.to_string())
             .unwrap();
-        in_tx.close().unwrap();
+        in_tx.close_channel();
 
-        let mut reactor = Core::new().unwrap();
-        let timeout =
-            Timeout::new(Duration::from_secs(1), &reactor.handle()).unwrap();
-
-        assert!(reactor.run(timeout.select2(server)).is_ok());
+        assert!(time::timeout(Duration::from_secs(1), server).await.is_ok());
 
         assert_eq!(receive(&mut out_rx), first_response);
         assert_eq!(receive(&mut out_rx), second_response);
     }
 
+    #[tokio::test]
+    async fn shutdown_drains_active_requests() {
+        let service = SlowToUpperService;
+
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, out_rx) = mpsc::channel(3);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let mut server = PipelineServer::new(service, transport);
+        let shutdown_handle = server.shutdown_handle();
+
+        let delay = Duration::from_millis(100);
+
+        in_tx.try_send(("first request".to_string(), delay)).unwrap();
+        in_tx.try_send(("second request".to_string(), delay)).unwrap();
+
+        let start_up = Duration::from_millis(10);
+
+        assert!(time::timeout(start_up, &mut server).await.is_err());
+
+        shutdown_handle.shutdown();
+        in_tx.try_send(("late request".to_string(), delay)).unwrap();
+
+        match time::timeout(Duration::from_secs(1), server).await {
+            Ok(Ok(())) => {}
+            _ => panic!("server did not shut down"),
+        }
+
+        let responses: Vec<_> = out_rx.collect().await;
+
+        assert_eq!(responses, vec!["FIRST REQUEST", "SECOND REQUEST"]);
+    }
+
+    #[tokio::test]
+    async fn shutdown_deadline_stops_draining() {
+        let service = SlowToUpperService;
+
+        let (mut in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, out_rx) = mpsc::channel(2);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let mut server = PipelineServer::new(service, transport);
+        let shutdown_handle = server.shutdown_handle();
+
+        let delay = Duration::from_secs(10);
+
+        in_tx.try_send(("slow request".to_string(), delay)).unwrap();
+
+        let start_up = Duration::from_millis(10);
+
+        assert!(time::timeout(start_up, &mut server).await.is_err());
+
+        let deadline = Instant::now() + Duration::from_millis(10);
+
+        shutdown_handle.shutdown_with_deadline(deadline);
+
+        match time::timeout(Duration::from_secs(1), server).await {
+            Ok(Ok(())) => {}
+            _ => panic!("server did not stop at the deadline"),
+        }
+
+        assert_eq!(out_rx.count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn in_flight_limit_pauses_reading_requests() {
+        let service = SlowToUpperService;
+
+        let (in_tx, in_rx) = mpsc::channel(0);
+        let (out_tx, out_rx) = mpsc::channel(3);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let mut server = PipelineServer::new(service, transport);
+
+        server.set_connection_limits(ConnectionLimits {
+            max_in_flight_requests: NonZeroUsize::new(2),
+            ..ConnectionLimits::default()
+        });
+
+        let delay = Duration::from_millis(100);
+        let accepted_requests =
+            send_until_full(&mut server, in_tx, |index| {
+                (format!("request {}", index), delay)
+            })
+            .await;
+
+        assert_eq!(accepted_requests, 3);
+
+        let responses = out_rx.collect::<Vec<_>>();
+        let finished = future::join(server, responses);
+
+        match time::timeout(Duration::from_secs(1), finished).await {
+            Ok((_, responses)) => assert_eq!(
+                responses,
+                vec!["REQUEST 0", "REQUEST 1", "REQUEST 2"],
+            ),
+            _ => panic!("server did not finish"),
+        }
+    }
+
+    #[tokio::test]
+    async fn busy_services_pause_reading_requests() {
+        let service = ConcurrencyLimit::new(SlowToUpperService, 2);
+
+        let (in_tx, in_rx) = mpsc::channel(0);
+        let (out_tx, out_rx) = mpsc::channel(3);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let mut server = PipelineServer::new(service, transport);
+
+        let delay = Duration::from_millis(100);
+        let accepted_requests =
+            send_until_full(&mut server, in_tx, |index| {
+                (format!("request {}", index), delay)
+            })
+            .await;
+
+        assert_eq!(accepted_requests, 3);
+
+        let responses = out_rx.collect::<Vec<_>>();
+        let finished = future::join(server, responses);
+
+        match time::timeout(Duration::from_secs(1), finished).await {
+            Ok((_, responses)) => assert_eq!(
+                responses,
+                vec!["REQUEST 0", "REQUEST 1", "REQUEST 2"],
+            ),
+            _ => panic!("server did not finish"),
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_readers_exert_backpressure() {
+        let service = ToUpperService;
+
+        let (in_tx, in_rx) = mpsc::channel(0);
+        let (out_tx, out_rx) = mpsc::channel(0);
+        let transport = SinkStream::new(out_tx, in_rx);
+
+        let mut server = PipelineServer::new(service, transport);
+
+        server.set_connection_limits(ConnectionLimits {
+            max_in_flight_requests: NonZeroUsize::new(1),
+            response_queue_capacity: NonZeroUsize::new(1).unwrap(),
+        });
+
+        let accepted_requests =
+            send_until_full(&mut server, in_tx, |index| {
+                format!("request {}", index)
+            })
+            .await;
+
+        // One response in the connection, one in the queue, one in flight
+        // and one waiting to be read.
+        assert_eq!(accepted_requests, 4);
+
+        let responses = out_rx.collect::<Vec<_>>();
+        let finished = future::join(server, responses);
+
+        match time::timeout(Duration::from_secs(1), finished).await {
+            Ok((_, responses)) => assert_eq!(
+                responses,
+                vec!["REQUEST 0", "REQUEST 1", "REQUEST 2", "REQUEST 3"],
+            ),
+            _ => panic!("server did not finish"),
+        }
+    }
+
+    async fn send_until_full<S, T, R, F>(
+        server: &mut PipelineServer<S, T>,
+        mut requests: mpsc::Sender<R>,
+        make_request: F,
+    ) -> usize
+    where
+        S: Service<R>,
+        T: TryStream<Ok = R> + Sink<S::Response> + Unpin,
+        F: Fn(usize) -> R,
+    {
+        let mut accepted_requests = 0;
+
+        while requests.try_send(make_request(accepted_requests)).is_ok() {
+            accepted_requests += 1;
+
+            let pause = Duration::from_millis(10);
+
+            if time::timeout(pause, &mut *server).await.is_ok() {
+                panic!("server stopped while receiving requests");
+            }
+        }
+
+        accepted_requests
+    }
+
     fn receive<S>(stream: &mut S) -> S::Item
     where
-        S: Stream,
+        S: Stream + Unpin,
     {
-        match stream.poll() {
-            Ok(Async::Ready(Some(item))) => item,
-            Ok(Async::Ready(None)) => {
+        match stream.next().now_or_never() {
+            Some(Some(item)) => item,
+            Some(None) => {
                 panic!("failed to receive item from stream: Stream is empty");
             }
-            Ok(Async::NotReady) => {
+            None => {
                 panic!("failed to receive item from stream: Not Ready");
             }
-            Err(_) => {
-                panic!("failed to receive item from stream: Error");
-            }
         }
     }
 }
@@ -1,8 +1,9 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 
 pub struct ReadyQueue<T> {
     queue: VecDeque<Option<T>>,
     first_id: usize,
+    abandoned: HashSet<usize>,
 }
 
 impl<T> ReadyQueue<T> {
@@ -10,13 +11,29 @@ impl<T> ReadyQueue<T> {
         ReadyQueue {
             queue: VecDeque::new(),
             first_id: 0,
+            abandoned: HashSet::new(),
         }
     }
 
     pub fn push(&mut self, item: T) -> usize {
-        self.queue.push_back(Some(item));
+        let id = self.next_id();
 
-        self.first_id + self.queue.len()
+        if self.abandoned.remove(&id) {
+            self.queue.push_back(None);
+            self.drain_empty_slots();
+        } else {
+            self.queue.push_back(Some(item));
+        }
+
+        id
+    }
+
+    pub fn is_ready(&self, id: usize) -> bool {
+        id >= self.first_id
+            && self.queue
+                .get(id - self.first_id)
+                .map(Option::is_some)
+                .unwrap_or(false)
     }
 
     pub fn pop(&mut self, id: usize) -> T {
@@ -25,27 +42,73 @@ impl<T> ReadyQueue<T> {
         }
 
         let position = id - self.first_id;
+        let item = self.queue
+            .get_mut(position)
+            .expect("no items in queue")
+            .take()
+            .expect("item popped twice");
 
-        if position == 0 {
-            let item = self.queue
-                .pop_front()
-                .expect("no items in queue")
-                .expect("item popped twice");
+        self.drain_empty_slots();
 
-            self.first_id += 1;
+        item
+    }
 
-            let non_empty_slot = self.queue.iter().position(Option::is_some);
+    pub fn discard(&mut self, id: usize) {
+        if id >= self.next_id() {
+            self.abandoned.insert(id);
+        } else if id >= self.first_id {
+            self.queue[id - self.first_id] = None;
+            self.drain_empty_slots();
+        }
+    }
 
-            if let Some(first_non_empty_slot) = non_empty_slot {
-                self.queue.drain(0..first_non_empty_slot);
-                self.first_id += first_non_empty_slot;
-            }
+    fn next_id(&self) -> usize {
+        self.first_id + self.queue.len()
+    }
 
-            item
-        } else {
-            self.queue[position]
-                .take()
-                .expect("item popped twice")
-        }
+    fn drain_empty_slots(&mut self) {
+        let empty_slots = self.queue
+            .iter()
+            .take_while(|slot| slot.is_none())
+            .count();
+
+        self.queue.drain(0..empty_slots);
+        self.first_id += empty_slots;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_order_pops() {
+        let mut queue = ReadyQueue::new();
+
+        queue.push("first");
+        queue.push("second");
+        queue.push("third");
+
+        assert_eq!(queue.pop(1), "second");
+        assert_eq!(queue.pop(0), "first");
+        assert_eq!(queue.pop(2), "third");
+        assert!(queue.queue.is_empty());
+        assert_eq!(queue.first_id, 3);
+    }
+
+    #[test]
+    fn discarded_items_are_released() {
+        let mut queue = ReadyQueue::new();
+
+        queue.push("first");
+        queue.discard(0);
+        queue.discard(2);
+        queue.push("second");
+        queue.push("third");
+
+        assert!(queue.abandoned.is_empty());
+        assert_eq!(queue.pop(1), "second");
+        assert!(queue.queue.is_empty());
+        assert_eq!(queue.first_id, 3);
     }
 }
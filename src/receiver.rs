@@ -1,8 +1,9 @@
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 
-use futures::{Future, Poll};
-
-use super::dispatcher::Dispatcher;
+use super::dispatcher::{DispatchError, Dispatcher};
 
 pub struct Receiver<D>
 where
@@ -10,6 +11,7 @@ where
 {
     dispatcher: Arc<D>,
     id: D::Id,
+    finished: bool,
 }
 
 impl<D> Receiver<D>
@@ -17,18 +19,42 @@ where
     D: Dispatcher,
 {
     pub fn new(dispatcher: Arc<D>, id: D::Id) -> Self {
-        Receiver { dispatcher, id }
+        Receiver {
+            dispatcher,
+            id,
+            finished: false,
+        }
     }
 }
 
+// The receiver is never pinned in place, its ID is only ever borrowed.
+impl<D> Unpin for Receiver<D> where D: Dispatcher {}
+
 impl<D> Future for Receiver<D>
 where
     D: Dispatcher,
 {
-    type Item = D::Item;
-    type Error = D::Error;
+    type Output = Result<D::Item, DispatchError<D::Error>>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let item = ready!(self.dispatcher.poll(&self.id, context))?;
+
+        self.finished = true;
+
+        Poll::Ready(Ok(item))
+    }
+}
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.dispatcher.poll(&self.id)
+impl<D> Drop for Receiver<D>
+where
+    D: Dispatcher,
+{
+    fn drop(&mut self) {
+        if !self.finished {
+            self.dispatcher.deregister(&self.id);
+        }
     }
 }
@@ -0,0 +1,27 @@
+/// Produces the request for each attempt of a call made by a
+/// `RetryingClient`.
+pub trait RequestFactory<R> {
+    fn make_request(&mut self) -> R;
+}
+
+impl<R, F> RequestFactory<R> for F
+where
+    F: FnMut() -> R,
+{
+    fn make_request(&mut self) -> R {
+        self()
+    }
+}
+
+/// Sends a clone of the same request in each attempt.
+#[derive(Clone, Debug)]
+pub struct Cloned<R>(pub R);
+
+impl<R> RequestFactory<R> for Cloned<R>
+where
+    R: Clone,
+{
+    fn make_request(&mut self) -> R {
+        self.0.clone()
+    }
+}
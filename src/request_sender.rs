@@ -1,57 +1,115 @@
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 
-use futures::{Async, AsyncSink, Future, Poll, Sink};
+use futures::Sink;
 
-pub struct RequestSender<O, S>
+use super::dispatcher::Dispatcher;
+use super::receiver::Receiver;
+use super::shared_sink::SharedSink;
+
+pub struct RequestSender<O, R, D>
 where
-    O: Sink,
+    O: Sink<R> + Unpin,
+    D: Dispatcher,
 {
-    sink: Arc<Mutex<O>>,
-    request: Option<O::SinkItem>,
-    seed: Option<S>,
+    sink: Arc<Mutex<SharedSink<O>>>,
+    dispatcher: Arc<D>,
+    request: Option<R>,
+    seed: Option<D::Seed>,
+    receiver: Option<Receiver<D>>,
 }
 
-impl<O, S> RequestSender<O, S>
+impl<O, R, D> RequestSender<O, R, D>
 where
-    O: Sink,
+    O: Sink<R> + Unpin,
+    D: Dispatcher,
 {
-    pub fn new(sink: Arc<Mutex<O>>, request: O::SinkItem, seed: S) -> Self {
+    pub fn new(
+        sink: Arc<Mutex<SharedSink<O>>>,
+        dispatcher: Arc<D>,
+        request: R,
+        seed: D::Seed,
+    ) -> Self {
         RequestSender {
             sink,
+            dispatcher,
             request: Some(request),
             seed: Some(seed),
+            receiver: None,
         }
     }
 }
 
-impl<O, S> Future for RequestSender<O, S>
+// The request and the seed are only moved out, and never pinned.
+impl<O, R, D> Unpin for RequestSender<O, R, D>
+where
+    O: Sink<R> + Unpin,
+    D: Dispatcher,
+{
+}
+
+impl<O, R, D> Future for RequestSender<O, R, D>
 where
-    O: Sink,
+    O: Sink<R> + Unpin,
+    D: Dispatcher,
 {
-    type Item = S;
-    type Error = O::SinkError;
+    type Output = Result<Receiver<D>, O::Error>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let mut sink = self.sink
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut sink = this.sink
             .lock()
             .expect("a thread panicked while holding RequestSender locked");
+        let mut sink = Pin::new(&mut *sink);
 
-        if let Some(request) = self.request.take() {
-            match sink.start_send(request)? {
-                AsyncSink::Ready => (),
-                AsyncSink::NotReady(request) => {
-                    self.request = Some(request);
-                    return Ok(Async::NotReady);
-                }
-            }
+        if this.request.is_some() {
+            ready!(sink.as_mut().poll_ready(context))?;
+
+            let request = this.request
+                .take()
+                .expect("Request sender polled after it had completed");
+            let seed = this.seed
+                .take()
+                .expect("Request sender polled after it had completed");
+
+            sink.as_mut().start_send(request)?;
+
+            // The receiver is spawned while the sink is still locked, so that
+            // the dispatcher sees requests in the same order as they are
+            // sent.
+            this.receiver = Some(Dispatcher::spawn_receiver(
+                this.dispatcher.clone(),
+                seed,
+            ));
         }
 
-        try_ready!(sink.poll_complete());
+        ready!(sink.poll_flush(context))?;
 
-        let seed = self.seed
+        let receiver = this.receiver
             .take()
             .expect("Request sender polled after it had completed");
 
-        Ok(Async::Ready(seed))
+        Poll::Ready(Ok(receiver))
+    }
+}
+
+impl<O, R, D> Drop for RequestSender<O, R, D>
+where
+    O: Sink<R> + Unpin,
+    D: Dispatcher,
+{
+    fn drop(&mut self) {
+        // This might have been the task the sink would notify, so another
+        // task must be woken up to take over.
+        if self.receiver.is_some() || self.request.is_some() {
+            if let Ok(mut sink) = self.sink.lock() {
+                sink.wake_waiting_tasks();
+            }
+        }
     }
 }
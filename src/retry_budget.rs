@@ -0,0 +1,76 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Limits the retries to a fraction of the calls, so that retrying can't
+/// multiply the load on a server that is failing.
+///
+/// Each call adds `retry_ratio` to the budget, and each retry takes one from
+/// it. The budget starts with `max_retries`, and never holds more than that,
+/// so that retries aren't saved up while the server is healthy. Clones share
+/// the same budget.
+#[derive(Clone, Debug)]
+pub struct RetryBudget {
+    balance: Arc<Mutex<f64>>,
+    retry_ratio: f64,
+    max_retries: f64,
+}
+
+impl RetryBudget {
+    pub fn new(retry_ratio: f64, max_retries: u32) -> Self {
+        RetryBudget {
+            balance: Arc::new(Mutex::new(max_retries as f64)),
+            retry_ratio,
+            max_retries: max_retries as f64,
+        }
+    }
+
+    /// Records a new call, which might need to be retried.
+    pub fn deposit(&self) {
+        let mut balance = self.lock();
+
+        *balance = (*balance + self.retry_ratio).min(self.max_retries);
+    }
+
+    /// Takes a retry from the budget, if there are any left.
+    pub fn withdraw(&self) -> bool {
+        let mut balance = self.lock();
+
+        if *balance >= 1.0 {
+            *balance -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, f64> {
+        self.balance
+            .lock()
+            .expect("a thread panicked while holding RetryBudget locked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_earn_retries_up_to_the_maximum() {
+        let budget = RetryBudget::new(0.5, 2);
+
+        assert!(budget.withdraw());
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+
+        budget.deposit();
+
+        assert!(!budget.withdraw());
+
+        for _ in 0..10 {
+            budget.deposit();
+        }
+
+        assert!(budget.withdraw());
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+    }
+}
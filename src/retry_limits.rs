@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use super::backoff::exponential_backoff;
+
+/// How many times a `RetryingClient` sends a request, and how long it waits
+/// between the attempts.
+///
+/// A call is attempted at most `max_attempts` times in total. The delay
+/// before each retry starts at `initial_delay` and doubles up to `max_delay`,
+/// and is then randomly shortened by up to the `jitter` fraction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryLimits {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: f64,
+}
+
+impl RetryLimits {
+    /// The delay to wait before the given retry, counting from one.
+    pub fn delay(&self, retry: u32) -> Duration {
+        exponential_backoff(
+            self.initial_delay,
+            self.max_delay,
+            self.jitter,
+            retry,
+        )
+    }
+}
+
+impl Default for RetryLimits {
+    fn default() -> Self {
+        RetryLimits {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(1),
+            jitter: 0.5,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use super::client_error::ClientError;
+use super::transient_error::TransientError;
+
+/// Decides which failed calls a `RetryingClient` sends again.
+pub trait RetryPolicy<R, E> {
+    /// Whether the `request` should be sent again after its previous attempt
+    /// failed with the `error`.
+    fn should_retry(&self, request: &R, error: &E) -> bool;
+}
+
+impl<R, E, F> RetryPolicy<R, E> for F
+where
+    F: Fn(&R, &E) -> bool,
+{
+    fn should_retry(&self, request: &R, error: &E) -> bool {
+        self(request, error)
+    }
+}
+
+/// Retries the calls that failed because of their connection, or that timed
+/// out.
+///
+/// Errors while sending or receiving are only retried when they are
+/// `TransientError`s, so that messages that can't be encoded or decoded
+/// aren't sent again. The server might have already handled a request that
+/// failed this way, so this policy should only be used for idempotent
+/// requests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryTransientErrors;
+
+impl<R, I, O> RetryPolicy<R, ClientError<I, O>> for RetryTransientErrors
+where
+    I: TransientError,
+    O: TransientError,
+{
+    fn should_retry(&self, _: &R, error: &ClientError<I, O>) -> bool {
+        match *error {
+            ClientError::ReceiveError(ref error) => error.is_transient(),
+            ClientError::SendError(ref error) => error.is_transient(),
+            ClientError::ConnectionLost
+            | ClientError::NotConnected
+            | ClientError::Timeout => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    type TestError = ClientError<io::Error, io::Error>;
+
+    #[test]
+    fn retries_connection_errors() {
+        let errors = vec![
+            TestError::ConnectionLost,
+            TestError::NotConnected,
+            TestError::Timeout,
+            TestError::ReceiveError(io::ErrorKind::ConnectionReset.into()),
+            TestError::SendError(io::ErrorKind::BrokenPipe.into()),
+        ];
+
+        for error in errors {
+            assert!(RetryTransientErrors.should_retry(&(), &error));
+        }
+    }
+
+    #[test]
+    fn does_not_retry_messages_that_cant_be_encoded_or_decoded() {
+        let errors = vec![
+            TestError::ReceiveError(io::ErrorKind::InvalidData.into()),
+            TestError::SendError(io::ErrorKind::InvalidInput.into()),
+            TestError::DuplicateRequestId,
+        ];
+
+        for error in errors {
+            assert!(!RetryTransientErrors.should_retry(&(), &error));
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::time::{self, Sleep};
+use tower_service::Service;
+
+use super::request_factory::{Cloned, RequestFactory};
+use super::retry_budget::RetryBudget;
+use super::retry_limits::RetryLimits;
+use super::retry_policy::RetryPolicy;
+
+/// Sends failed calls again, as decided by a `RetryPolicy`.
+///
+/// Each call is made through its own clone of the service, and is attempted
+/// as many times as the `RetryLimits` and the `RetryBudget` allow. The
+/// request for each attempt is either a clone of the original request or is
+/// produced by a `RequestFactory`.
+pub struct RetryingClient<S, P> {
+    service: S,
+    policy: P,
+    limits: RetryLimits,
+    budget: Option<RetryBudget>,
+}
+
+impl<S, P> RetryingClient<S, P> {
+    pub fn new(service: S, policy: P) -> Self {
+        RetryingClient {
+            service,
+            policy,
+            limits: RetryLimits::default(),
+            budget: None,
+        }
+    }
+
+    pub fn set_retry_limits(&mut self, limits: RetryLimits) {
+        self.limits = limits;
+    }
+
+    pub fn set_retry_budget(&mut self, budget: RetryBudget) {
+        self.budget = Some(budget);
+    }
+
+    pub fn call<R>(
+        &self,
+        request: R,
+    ) -> RetryingClientFuture<S, P, R, Cloned<R>>
+    where
+        S: Service<R> + Clone,
+        P: RetryPolicy<R, S::Error> + Clone,
+        R: Clone,
+    {
+        self.call_with(Cloned(request))
+    }
+
+    /// Calls the service with the requests made by the `factory`, which is
+    /// asked for a new request before each attempt.
+    ///
+    /// After an attempt fails, the request for the next one is made before
+    /// the policy decides whether to retry it, so that requests don't need
+    /// to be kept around.
+    pub fn call_with<R, Q>(
+        &self,
+        mut factory: Q,
+    ) -> RetryingClientFuture<S, P, R, Q>
+    where
+        S: Service<R> + Clone,
+        P: RetryPolicy<R, S::Error> + Clone,
+        Q: RequestFactory<R>,
+    {
+        let request = factory.make_request();
+
+        self.start(self.service.clone(), factory, State::Ready(request))
+    }
+
+    fn start<R, Q>(
+        &self,
+        service: S,
+        factory: Q,
+        state: State<S::Future, R>,
+    ) -> RetryingClientFuture<S, P, R, Q>
+    where
+        S: Service<R>,
+        P: Clone,
+    {
+        if let Some(ref budget) = self.budget {
+            budget.deposit();
+        }
+
+        RetryingClientFuture {
+            state,
+            service,
+            policy: self.policy.clone(),
+            factory,
+            limits: self.limits,
+            budget: self.budget.clone(),
+            attempts: 1,
+        }
+    }
+}
+
+impl<S, P, R> Service<R> for RetryingClient<S, P>
+where
+    S: Service<R> + Clone,
+    P: RetryPolicy<R, S::Error> + Clone,
+    R: Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RetryingClientFuture<S, P, R, Cloned<R>>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        // The first attempt is made with the service that was polled ready,
+        // and a clone of it takes its place for the next call.
+        let clone = self.service.clone();
+        let mut ready = mem::replace(&mut self.service, clone);
+        let response = Box::pin(ready.call(request.clone()));
+
+        self.start(ready, Cloned(request), State::Calling(response))
+    }
+}
+
+enum State<F, R> {
+    Ready(R),
+    Calling(Pin<Box<F>>),
+    BackingOff(Pin<Box<Sleep>>, R),
+    Done,
+}
+
+pub struct RetryingClientFuture<S, P, R, Q>
+where
+    S: Service<R>,
+{
+    state: State<S::Future, R>,
+    service: S,
+    policy: P,
+    factory: Q,
+    limits: RetryLimits,
+    budget: Option<RetryBudget>,
+    attempts: u32,
+}
+
+impl<S, P, R, Q> RetryingClientFuture<S, P, R, Q>
+where
+    S: Service<R>,
+    P: RetryPolicy<R, S::Error>,
+    Q: RequestFactory<R>,
+{
+    /// Waits to make the next attempt, or returns the `error` if the call
+    /// shouldn't be retried.
+    ///
+    /// The factory is only asked for the next request while attempts are
+    /// left.
+    fn retry(&mut self, error: S::Error) -> Result<(), S::Error> {
+        if self.attempts >= self.limits.max_attempts {
+            return Err(error);
+        }
+
+        let request = self.factory.make_request();
+
+        if !self.policy.should_retry(&request, &error) {
+            return Err(error);
+        }
+
+        if let Some(ref budget) = self.budget {
+            if !budget.withdraw() {
+                return Err(error);
+            }
+        }
+
+        let delay = self.limits.delay(self.attempts);
+
+        self.attempts += 1;
+        self.state = State::BackingOff(Box::pin(time::sleep(delay)), request);
+
+        Ok(())
+    }
+}
+
+// The response future and the timer are pinned in their own allocations.
+impl<S, P, R, Q> Unpin for RetryingClientFuture<S, P, R, Q>
+where
+    S: Service<R>,
+{
+}
+
+impl<S, P, R, Q> Future for RetryingClientFuture<S, P, R, Q>
+where
+    S: Service<R>,
+    P: RetryPolicy<R, S::Error>,
+    Q: RequestFactory<R>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            match mem::replace(&mut this.state, State::Done) {
+                State::Ready(request) => {
+                    match this.service.poll_ready(context) {
+                        Poll::Ready(Ok(())) => {
+                            let response = this.service.call(request);
+
+                            this.state = State::Calling(Box::pin(response));
+                        }
+                        Poll::Ready(Err(error)) => this.retry(error)?,
+                        Poll::Pending => {
+                            this.state = State::Ready(request);
+
+                            return Poll::Pending;
+                        }
+                    }
+                }
+                State::Calling(mut response) => {
+                    match response.as_mut().poll(context) {
+                        Poll::Ready(Ok(response)) => {
+                            return Poll::Ready(Ok(response));
+                        }
+                        Poll::Ready(Err(error)) => this.retry(error)?,
+                        Poll::Pending => {
+                            this.state = State::Calling(response);
+
+                            return Poll::Pending;
+                        }
+                    }
+                }
+                State::BackingOff(mut delay, request) => {
+                    if delay.as_mut().poll(context).is_pending() {
+                        this.state = State::BackingOff(delay, request);
+
+                        return Poll::Pending;
+                    }
+
+                    this.state = State::Ready(request);
+                }
+                State::Done => {
+                    panic!("RetryingClientFuture polled after completion")
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use futures::future;
+    use tokio::time;
+    use tower::limit::ConcurrencyLimit;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+    use crate::client_error::ClientError;
+    use crate::make_services::MakeServices;
+    use crate::memory::listener;
+    use crate::pipeline_client::PipelineClient;
+    use crate::pipeline_listening_server::PipelineListeningServer;
+    use crate::retry_policy::RetryTransientErrors;
+    use crate::tests::common::{
+        failing_service, FailingServiceError, ToUpperService,
+    };
+
+    type TestError = FailingServiceError;
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let service = failing_service(attempts.clone(), 2);
+        let client = quick_retries(service, RetryTransientErrors, 3);
+
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+        assert_eq!(attempts.load(Ordering::Acquire), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let service = failing_service(attempts.clone(), usize::MAX);
+        let client = quick_retries(service, RetryTransientErrors, 3);
+
+        match client.call("request".to_owned()).await {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("call did not fail after the last attempt"),
+        }
+
+        assert_eq!(attempts.load(Ordering::Acquire), 3);
+    }
+
+    #[tokio::test]
+    async fn policy_decides_which_requests_are_retried() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let service = failing_service(attempts.clone(), usize::MAX);
+        let policy = |request: &String, _: &TestError| {
+            request.starts_with("idempotent")
+        };
+        let client = quick_retries(service, policy, 3);
+
+        assert!(client.call("idempotent".to_owned()).await.is_err());
+        assert_eq!(attempts.load(Ordering::Acquire), 3);
+
+        assert!(client.call("other".to_owned()).await.is_err());
+        assert_eq!(attempts.load(Ordering::Acquire), 4);
+    }
+
+    #[tokio::test]
+    async fn budget_limits_retries() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let service = failing_service(attempts.clone(), usize::MAX);
+        let mut client = quick_retries(service, RetryTransientErrors, 3);
+
+        client.set_retry_budget(RetryBudget::new(0.0, 1));
+
+        assert!(client.call("first".to_owned()).await.is_err());
+        assert_eq!(attempts.load(Ordering::Acquire), 2);
+
+        assert!(client.call("second".to_owned()).await.is_err());
+        assert_eq!(attempts.load(Ordering::Acquire), 3);
+    }
+
+    #[tokio::test]
+    async fn factory_makes_the_request_for_each_attempt() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received_requests = requests.clone();
+        let service = service_fn(move |request: String| {
+            received_requests.lock().unwrap().push(request);
+
+            future::err::<String, _>(TestError::Timeout)
+        });
+        let client = quick_retries(service, RetryTransientErrors, 2);
+
+        let mut attempt = 0;
+        let factory = move || {
+            attempt += 1;
+            format!("attempt {}", attempt)
+        };
+
+        assert!(client.call_with(factory).await.is_err());
+        assert_eq!(*requests.lock().unwrap(), vec!["attempt 1", "attempt 2"]);
+    }
+
+    #[tokio::test]
+    async fn factory_requests_dont_need_to_be_cloned() {
+        struct Request;
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let sent_requests = attempts.clone();
+        let service = service_fn(move |Request| {
+            sent_requests.fetch_add(1, Ordering::AcqRel);
+
+            future::err::<String, _>(TestError::Timeout)
+        });
+        let client = quick_retries(service, RetryTransientErrors, 3);
+
+        let mut requests = 0;
+        let factory = || {
+            requests += 1;
+            Request
+        };
+
+        assert!(client.call_with(factory).await.is_err());
+        assert_eq!(requests, 3);
+        assert_eq!(attempts.load(Ordering::Acquire), 3);
+    }
+
+    #[tokio::test]
+    async fn calls_the_service_that_was_polled_ready() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let service = ConcurrencyLimit::new(failing_service(attempts, 0), 1);
+        let mut client = RetryingClient::new(service, RetryTransientErrors);
+
+        for request in &["first", "second"] {
+            let ready_client =
+                ServiceExt::<String>::ready(&mut client).await.unwrap();
+            let response = ready_client.call(request.to_string());
+            let response = time::timeout(Duration::from_secs(5), response)
+                .await
+                .expect("call waited for a permit that was never released");
+
+            assert_eq!(response.unwrap(), request.to_uppercase());
+        }
+    }
+
+    #[tokio::test]
+    async fn wraps_pipeline_clients() {
+        let (connector, listener) = listener(1);
+        let services = MakeServices::new(service_fn(|()| {
+            future::ok::<_, ()>(ToUpperService)
+        }));
+
+        tokio::spawn(PipelineListeningServer::new(services, listener));
+
+        let service = PipelineClient::new(connector.connect().unwrap());
+        let client = RetryingClient::new(service, RetryTransientErrors);
+
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+    }
+
+    fn quick_retries<S, P>(
+        service: S,
+        policy: P,
+        max_attempts: u32,
+    ) -> RetryingClient<S, P> {
+        let mut client = RetryingClient::new(service, policy);
+
+        client.set_retry_limits(RetryLimits {
+            max_attempts,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            jitter: 0.0,
+        });
+
+        client
+    }
+}
@@ -0,0 +1,49 @@
+pub trait SequentialId: Clone + Eq {
+    fn first() -> Self;
+    fn next(&self) -> Self;
+}
+
+macro_rules! impl_for_integers {
+    ($type:ty $(,)*) => {
+        impl SequentialId for $type {
+            fn first() -> Self {
+                0
+            }
+
+            fn next(&self) -> Self {
+                self.wrapping_add(1)
+            }
+        }
+    };
+
+    ($type:ty $(, $rest:ty)* $(,)*) => {
+        impl_for_integers!( $type );
+        impl_for_integers!( $($rest),* );
+    };
+}
+
+impl_for_integers! {
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    isize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_wrap_around() {
+        assert_eq!(u8::first(), 0);
+        assert_eq!(0u8.next(), 1);
+        assert_eq!(255u8.next(), 0);
+        assert_eq!(i8::MAX.next(), i8::MIN);
+    }
+}
@@ -13,9 +13,3 @@ pub enum ServerError<I, O, S> {
     #[fail(display = "failed to send response: {}", _0)]
     SendError(#[cause] O),
 }
-
-impl<I, O, S> From<()> for ServerError<I, O, S> {
-    fn from(_: ()) -> Self {
-        unreachable!("UnboundedReceiver used to queue responses failed");
-    }
-}
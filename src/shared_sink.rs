@@ -0,0 +1,94 @@
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use futures::Sink;
+
+/// A sink that is shared between many tasks.
+///
+/// The wrapped sink only remembers the last task that it could not make
+/// progress for, so this wrapper keeps track of all of them and wakes them up
+/// once the sink has made some progress.
+pub struct SharedSink<O> {
+    sink: O,
+    waiting_tasks: Vec<Waker>,
+}
+
+impl<O> SharedSink<O> {
+    pub fn new(sink: O) -> Self {
+        SharedSink {
+            sink,
+            waiting_tasks: Vec::new(),
+        }
+    }
+
+    pub fn wake_waiting_tasks(&mut self) {
+        for waker in self.waiting_tasks.drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn park(&mut self, context: &Context) {
+        let already_waiting = self.waiting_tasks
+            .iter()
+            .any(|waker| waker.will_wake(context.waker()));
+
+        if !already_waiting {
+            self.waiting_tasks.push(context.waker().clone());
+        }
+    }
+
+    fn track<E>(
+        &mut self,
+        result: Poll<Result<(), E>>,
+        context: &Context,
+    ) -> Poll<Result<(), E>> {
+        match result {
+            Poll::Ready(Ok(())) => self.wake_waiting_tasks(),
+            Poll::Ready(Err(_)) => {}
+            Poll::Pending => self.park(context),
+        }
+
+        result
+    }
+}
+
+impl<O, I> Sink<I> for SharedSink<O>
+where
+    O: Sink<I> + Unpin,
+{
+    type Error = O::Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        let result = Pin::new(&mut self.sink).poll_ready(context);
+
+        self.track(result, context)
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        item: I,
+    ) -> Result<(), Self::Error> {
+        Pin::new(&mut self.sink).start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        let result = Pin::new(&mut self.sink).poll_flush(context);
+
+        self.track(result, context)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        let result = Pin::new(&mut self.sink).poll_close(context);
+
+        self.track(result, context)
+    }
+}
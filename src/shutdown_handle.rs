@@ -0,0 +1,28 @@
+use futures::channel::oneshot;
+use tokio::time::Instant;
+
+/// Requests a server to shut down gracefully.
+///
+/// The server stops accepting new requests, but still sends the responses to
+/// the requests that are already in progress. Dropping the handle without
+/// using it has no effect on the server.
+pub struct ShutdownHandle {
+    sender: oneshot::Sender<Option<Instant>>,
+}
+
+impl ShutdownHandle {
+    pub fn new(sender: oneshot::Sender<Option<Instant>>) -> Self {
+        ShutdownHandle { sender }
+    }
+
+    /// Shuts down the server once all requests in progress are answered.
+    pub fn shutdown(self) {
+        let _ = self.sender.send(None);
+    }
+
+    /// Shuts down the server once all requests in progress are answered, or
+    /// when the `deadline` expires, whichever comes first.
+    pub fn shutdown_with_deadline(self, deadline: Instant) {
+        let _ = self.sender.send(Some(deadline));
+    }
+}
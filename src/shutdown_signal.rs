@@ -0,0 +1,115 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::oneshot;
+use futures::future::{FutureExt, Shared};
+use tokio::time::{self, Instant, Sleep};
+
+use super::shutdown_handle::ShutdownHandle;
+
+type ShutdownRequest = Shared<oneshot::Receiver<Option<Instant>>>;
+
+enum State {
+    Running(ShutdownRequest),
+    ShuttingDown(Option<Deadline>),
+    NeverShutsDown,
+}
+
+/// The instant a shutdown must finish by, whose timer is only started when
+/// it is first polled.
+struct Deadline {
+    instant: Instant,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+/// The receiving side of a `ShutdownHandle`, which can be shared by many
+/// servers.
+pub struct ShutdownSignal {
+    state: State,
+}
+
+impl ShutdownSignal {
+    pub fn new() -> (ShutdownHandle, Self) {
+        let (sender, receiver) = oneshot::channel();
+        let signal = ShutdownSignal {
+            state: State::Running(receiver.shared()),
+        };
+
+        (ShutdownHandle::new(sender), signal)
+    }
+
+    pub fn never() -> Self {
+        ShutdownSignal {
+            state: State::NeverShutsDown,
+        }
+    }
+
+    /// Checks if a shutdown was requested, and if not, arranges for the
+    /// current task to be woken up when it is.
+    pub fn is_shutting_down(&mut self, context: &mut Context) -> bool {
+        let request = match self.state {
+            State::Running(ref mut request) => {
+                Pin::new(request).poll(context)
+            }
+            State::ShuttingDown(_) => return true,
+            State::NeverShutsDown => return false,
+        };
+
+        match request {
+            Poll::Ready(Ok(deadline)) => {
+                self.state = State::ShuttingDown(deadline.map(Deadline::new));
+                true
+            }
+            Poll::Pending => false,
+            Poll::Ready(Err(_)) => {
+                self.state = State::NeverShutsDown;
+                false
+            }
+        }
+    }
+
+    /// Checks if the deadline for a requested shutdown has expired, and if
+    /// not, arranges for the current task to be woken up when it does.
+    pub fn deadline_expired(&mut self, context: &mut Context) -> bool {
+        match self.state {
+            State::ShuttingDown(Some(ref mut deadline)) => {
+                deadline.poll_expired(context)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Clone for ShutdownSignal {
+    fn clone(&self) -> Self {
+        let state = match self.state {
+            State::Running(ref request) => State::Running(request.clone()),
+            State::ShuttingDown(ref deadline) => State::ShuttingDown(
+                deadline
+                    .as_ref()
+                    .map(|deadline| Deadline::new(deadline.instant)),
+            ),
+            State::NeverShutsDown => State::NeverShutsDown,
+        };
+
+        ShutdownSignal { state }
+    }
+}
+
+impl Deadline {
+    fn new(instant: Instant) -> Self {
+        Deadline {
+            instant,
+            timer: None,
+        }
+    }
+
+    fn poll_expired(&mut self, context: &mut Context) -> bool {
+        let instant = self.instant;
+        let timer = self.timer
+            .get_or_insert_with(|| Box::pin(time::sleep_until(instant)));
+
+        timer.as_mut().poll(context).is_ready()
+    }
+}
@@ -0,0 +1,76 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use futures::{Sink, Stream, TryStream};
+
+/// Splits a transport into a sending and a receiving half.
+///
+/// Unlike `StreamExt::split`, the sending half accepts every item type that
+/// the transport accepts, so a client's type doesn't depend on the type of
+/// its requests. The halves take turns locking the transport, which is only
+/// held while it is polled.
+///
+/// The receiving half yields the transport's results, so it can be used as a
+/// `TryStream` even where the transport is only known to be one.
+pub fn split<T>(transport: T) -> (SinkHalf<T>, StreamHalf<T>) {
+    let transport = Arc::new(Mutex::new(transport));
+
+    (SinkHalf(transport.clone()), StreamHalf(transport))
+}
+
+pub struct SinkHalf<T>(Arc<Mutex<T>>);
+
+pub struct StreamHalf<T>(Arc<Mutex<T>>);
+
+fn lock<T>(transport: &Mutex<T>) -> MutexGuard<'_, T> {
+    transport
+        .lock()
+        .expect("a thread panicked while holding a transport locked")
+}
+
+impl<T> Stream for StreamHalf<T>
+where
+    T: TryStream + Unpin,
+{
+    type Item = Result<T::Ok, T::Error>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut *lock(&self.0)).try_poll_next(context)
+    }
+}
+
+impl<T, I> Sink<I> for SinkHalf<T>
+where
+    T: Sink<I> + Unpin,
+{
+    type Error = T::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut *lock(&self.0)).poll_ready(context)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        Pin::new(&mut *lock(&self.0)).start_send(item)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut *lock(&self.0)).poll_flush(context)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut *lock(&self.0)).poll_close(context)
+    }
+}
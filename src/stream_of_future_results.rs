@@ -1,8 +1,8 @@
-use futures::Future;
+use std::future::Future;
+
 use futures::stream::{FuturesOrdered, FuturesUnordered, Stream};
 
-pub trait StreamOfFutureResults<F>:
-    Stream<Item = F::Item, Error = F::Error>
+pub trait StreamOfFutureResults<F>: Stream<Item = F::Output> + Unpin
 where
     F: Future,
 {
@@ -19,7 +19,7 @@ where
     }
 
     fn push(&mut self, future: F) {
-        FuturesOrdered::push(self, future);
+        FuturesOrdered::push_back(self, future);
     }
 }
 
@@ -0,0 +1,13 @@
+/// How a `BalancingClient` chooses the endpoint for each call.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BalanceStrategy {
+    /// Each endpoint is used in turn.
+    RoundRobin,
+
+    /// The endpoint with the fewest calls waiting for a response is used.
+    LeastOutstandingRequests,
+
+    /// Two endpoints are picked at random, and the one with the fewest calls
+    /// waiting for a response is used.
+    PowerOfTwoChoices,
+}
@@ -0,0 +1,647 @@
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::{self, Either, Ready};
+use futures::{Stream, StreamExt, TryFuture};
+use tokio::time::Instant;
+use tokio_util::codec::Decoder;
+use tower_service::Service;
+
+use super::super::backoff::random_fraction;
+use super::super::message_with_id::MessageWithId;
+use super::balance_strategy::BalanceStrategy;
+use super::endpoint_change::EndpointChange;
+use super::multiplex_tcp_client::MultiplexTcpClient;
+use super::pipeline_tcp_client::PipelineTcpClient;
+use super::poolable_client::PoolableClient;
+use super::pooled_client::PooledClientFuture;
+
+pub type BalancingClientFuture<F> = Either<
+    PooledClientFuture<F>,
+    Ready<Result<<F as TryFuture>::Ok, <F as TryFuture>::Error>>,
+>;
+
+type Connect<K> = Box<dyn Fn(&SocketAddr) -> K + Send + Sync>;
+
+struct Endpoint<K> {
+    address: SocketAddr,
+    client: Option<K>,
+    calls_in_flight: Arc<AtomicUsize>,
+    ejected_until: Option<Instant>,
+}
+
+impl<K> Endpoint<K> {
+    fn new(address: SocketAddr) -> Self {
+        Endpoint {
+            address,
+            client: None,
+            calls_in_flight: Arc::new(AtomicUsize::new(0)),
+            ejected_until: None,
+        }
+    }
+
+    fn calls_in_flight(&self) -> usize {
+        self.calls_in_flight.load(Ordering::Acquire)
+    }
+
+    fn is_ejected(&self, now: Instant) -> bool {
+        self.ejected_until
+            .map(|ejected_until| now < ejected_until)
+            .unwrap_or(false)
+    }
+}
+
+struct Endpoints<K> {
+    endpoints: Vec<Endpoint<K>>,
+    next: usize,
+    ready: Option<SocketAddr>,
+}
+
+impl<K> Endpoints<K> {
+    fn apply(&mut self, change: EndpointChange) {
+        match change {
+            EndpointChange::Insert(address) => {
+                let is_known = self.endpoints
+                    .iter()
+                    .any(|endpoint| endpoint.address == address);
+
+                if !is_known {
+                    self.endpoints.push(Endpoint::new(address));
+                }
+            }
+            EndpointChange::Remove(address) => {
+                self.endpoints
+                    .retain(|endpoint| endpoint.address != address);
+            }
+        }
+    }
+
+    /// Ejects the endpoints whose connection failed or was lost.
+    fn eject_broken<R>(&mut self, until: Instant)
+    where
+        K: PoolableClient<R>,
+    {
+        for index in 0..self.endpoints.len() {
+            let is_broken = self.endpoints[index]
+                .client
+                .as_ref()
+                .map(|client| client.is_broken())
+                .unwrap_or(false);
+
+            if is_broken {
+                self.eject(index, until);
+            }
+        }
+    }
+
+    fn eject(&mut self, index: usize, until: Instant) {
+        let endpoint = &mut self.endpoints[index];
+
+        endpoint.client = None;
+        endpoint.ejected_until = Some(until);
+
+        if self.ready == Some(endpoint.address) {
+            self.ready = None;
+        }
+    }
+
+    fn available(&self, now: Instant) -> Vec<usize> {
+        self.endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, endpoint)| !endpoint.is_ejected(now))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// The endpoint chosen while polling for readiness, if it's still
+    /// available, or else a new endpoint chosen by the `strategy`.
+    fn choose_next(
+        &mut self,
+        strategy: BalanceStrategy,
+        now: Instant,
+    ) -> Option<usize> {
+        let ready = self.ready.and_then(|address| {
+            self.endpoints.iter().position(|endpoint| {
+                endpoint.address == address && !endpoint.is_ejected(now)
+            })
+        });
+
+        if ready.is_some() {
+            return ready;
+        }
+
+        let available = self.available(now);
+        let index = self.choose(strategy, &available);
+
+        self.ready = index.map(|index| self.endpoints[index].address);
+        index
+    }
+
+    /// Returns the client of an endpoint, connecting to it first if needed.
+    fn connect(&mut self, index: usize, connect: &Connect<K>) -> &Endpoint<K> {
+        let endpoint = &mut self.endpoints[index];
+        let address = endpoint.address;
+
+        endpoint.client.get_or_insert_with(|| connect(&address));
+        endpoint.ejected_until = None;
+
+        endpoint
+    }
+
+    fn choose(
+        &mut self,
+        strategy: BalanceStrategy,
+        available: &[usize],
+    ) -> Option<usize> {
+        if available.is_empty() {
+            return None;
+        }
+
+        let choice = match strategy {
+            BalanceStrategy::RoundRobin => {
+                let choice = available[self.next % available.len()];
+
+                self.next = self.next.wrapping_add(1);
+                choice
+            }
+            BalanceStrategy::LeastOutstandingRequests => available
+                .iter()
+                .cloned()
+                .min_by_key(|&index| self.load(index))
+                .expect("no endpoints to choose from"),
+            BalanceStrategy::PowerOfTwoChoices => {
+                let count = available.len();
+                let first = random_index(count);
+                let second = (first + 1 + random_index(count - 1)) % count;
+                let (first, second) = (available[first], available[second]);
+
+                if self.load(second) < self.load(first) {
+                    second
+                } else {
+                    first
+                }
+            }
+        };
+
+        Some(choice)
+    }
+
+    fn load(&self, index: usize) -> usize {
+        self.endpoints[index].calls_in_flight()
+    }
+}
+
+/// Spreads calls over the connections to several servers.
+///
+/// The endpoint for each call is chosen by the `BalanceStrategy`, and is
+/// connected to when it's first chosen. Endpoints whose connection fails or
+/// is lost are ejected, and are only connected to again once the ejection
+/// time has passed. Calls fail with `ClientError::NotConnected` while no
+/// endpoint is available.
+pub struct BalancingClient<K> {
+    endpoints: Arc<Mutex<Endpoints<K>>>,
+    connect: Connect<K>,
+    strategy: BalanceStrategy,
+    ejection_time: Duration,
+}
+
+impl<K> BalancingClient<K> {
+    /// Creates a client for the endpoints at the `addresses`, which uses
+    /// `connect` to create a client for each of them.
+    pub fn new<I, F>(
+        addresses: I,
+        connect: F,
+        strategy: BalanceStrategy,
+    ) -> Self
+    where
+        I: IntoIterator<Item = SocketAddr>,
+        F: Fn(&SocketAddr) -> K + Send + Sync + 'static,
+    {
+        let mut endpoints = Endpoints {
+            endpoints: Vec::new(),
+            next: 0,
+            ready: None,
+        };
+
+        for address in addresses {
+            endpoints.apply(EndpointChange::Insert(address));
+        }
+
+        BalancingClient {
+            endpoints: Arc::new(Mutex::new(endpoints)),
+            connect: Box::new(connect),
+            strategy,
+            ejection_time: Duration::from_secs(10),
+        }
+    }
+
+    /// Creates a client whose endpoints are inserted and removed by the
+    /// `changes`.
+    ///
+    /// The changes are applied by a task spawned on the current Tokio
+    /// runtime, until the stream ends or the client is dropped.
+    pub fn with_endpoint_changes<S, F>(
+        changes: S,
+        connect: F,
+        strategy: BalanceStrategy,
+    ) -> Self
+    where
+        S: Stream<Item = EndpointChange> + Send + 'static,
+        F: Fn(&SocketAddr) -> K + Send + Sync + 'static,
+        K: Send + 'static,
+    {
+        let client = Self::new(None, connect, strategy);
+        let endpoints = Arc::downgrade(&client.endpoints);
+
+        tokio::spawn(async move {
+            let mut changes = Box::pin(changes);
+
+            while let Some(change) = changes.next().await {
+                match endpoints.upgrade() {
+                    Some(endpoints) => lock(&endpoints).apply(change),
+                    None => break,
+                }
+            }
+        });
+
+        client
+    }
+
+    pub fn set_ejection_time(&mut self, ejection_time: Duration) {
+        self.ejection_time = ejection_time;
+    }
+
+    pub fn endpoints(&self) -> Vec<SocketAddr> {
+        lock(&self.endpoints)
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.address)
+            .collect()
+    }
+
+    pub fn call<R>(&self, request: R) -> BalancingClientFuture<K::Future>
+    where
+        K: PoolableClient<R>,
+    {
+        let mut endpoints = lock(&self.endpoints);
+        let now = Instant::now();
+
+        endpoints.eject_broken(now + self.ejection_time);
+
+        let index = endpoints.choose_next(self.strategy, now);
+
+        endpoints.ready = None;
+
+        let index = match index {
+            Some(index) => index,
+            None => return Either::Right(future::err(K::not_connected_error())),
+        };
+
+        let endpoint = endpoints.connect(index, &self.connect);
+        let client = endpoint.client.as_ref().expect("endpoint is connected");
+
+        Either::Left(PooledClientFuture::new(
+            client.call(request),
+            endpoint.calls_in_flight.clone(),
+        ))
+    }
+
+    /// Waits until the endpoint for the next call can accept another call.
+    ///
+    /// The endpoint is chosen by the `BalanceStrategy`, and the next call is
+    /// sent through it. Endpoints that fail to become ready are ejected, and
+    /// another one is chosen instead. The client is ready while no endpoint
+    /// is available, so that calls fail with `ClientError::NotConnected`.
+    pub fn poll_ready<R>(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), <K::Future as TryFuture>::Error>>
+    where
+        K: PoolableClient<R>,
+    {
+        let mut endpoints = lock(&self.endpoints);
+        let now = Instant::now();
+
+        endpoints.eject_broken(now + self.ejection_time);
+
+        loop {
+            let index = match endpoints.choose_next(self.strategy, now) {
+                Some(index) => index,
+                None => return Poll::Ready(Ok(())),
+            };
+
+            let endpoint = endpoints.connect(index, &self.connect);
+            let client =
+                endpoint.client.as_ref().expect("endpoint is connected");
+
+            match client.poll_ready(context) {
+                Poll::Ready(Err(_)) => {
+                    endpoints.eject(index, now + self.ejection_time)
+                }
+                readiness => return readiness,
+            }
+        }
+    }
+}
+
+impl<C> BalancingClient<PipelineTcpClient<C>>
+where
+    C: Clone + Decoder + Send + Sync + Unpin + 'static,
+{
+    /// Creates a client with pipelined connections to the servers at the
+    /// `addresses`.
+    pub fn connect<I>(addresses: I, codec: C, strategy: BalanceStrategy) -> Self
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        BalancingClient::new(
+            addresses,
+            move |address| PipelineTcpClient::connect(address, codec.clone()),
+            strategy,
+        )
+    }
+}
+
+impl<C> BalancingClient<MultiplexTcpClient<C>>
+where
+    C: Clone + Decoder + Send + Sync + Unpin + 'static,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    /// Creates a client with multiplexed connections to the servers at the
+    /// `addresses`.
+    pub fn connect<I>(addresses: I, codec: C, strategy: BalanceStrategy) -> Self
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        BalancingClient::new(
+            addresses,
+            move |address| MultiplexTcpClient::connect(address, codec.clone()),
+            strategy,
+        )
+    }
+}
+
+impl<K, R> Service<R> for BalancingClient<K>
+where
+    K: PoolableClient<R>,
+{
+    type Response = <K::Future as TryFuture>::Ok;
+    type Error = <K::Future as TryFuture>::Error;
+    type Future = BalancingClientFuture<K::Future>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        BalancingClient::poll_ready(self, context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        BalancingClient::call(self, request)
+    }
+}
+
+fn lock<K>(endpoints: &Mutex<Endpoints<K>>) -> MutexGuard<'_, Endpoints<K>> {
+    endpoints
+        .lock()
+        .expect("a thread panicked while holding BalancingClient locked")
+}
+
+fn random_index(count: usize) -> usize {
+    let index = (random_fraction() * count as f64) as usize;
+
+    index.min(count.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use futures::channel::mpsc;
+    use tokio::time;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+    use super::super::super::client_error::ClientError;
+    use super::super::connection_info::ConnectionInfo;
+    use super::super::pipeline_tcp_listener_server::PipelineTcpListenerServer;
+    use crate::tests::common::LineCodec;
+
+    #[tokio::test]
+    async fn round_robin_uses_each_endpoint_in_turn() {
+        let addresses = vec![start_server("a"), start_server("b")];
+        let client = BalancingClient::<PipelineTcpClient<_>>::connect(
+            addresses,
+            LineCodec,
+            BalanceStrategy::RoundRobin,
+        );
+
+        let mut responses = Vec::new();
+
+        for _ in 0..4 {
+            responses.push(client.call("request".to_owned()).await.unwrap());
+        }
+
+        assert_eq!(
+            responses,
+            vec!["a: request", "b: request", "a: request", "b: request"]
+        );
+    }
+
+    #[tokio::test]
+    async fn least_outstanding_requests_avoids_busy_endpoints() {
+        let addresses = vec![start_server("a"), start_server("b")];
+        let client = BalancingClient::<PipelineTcpClient<_>>::connect(
+            addresses,
+            LineCodec,
+            BalanceStrategy::LeastOutstandingRequests,
+        );
+
+        let first_response = client.call("first".to_owned());
+        let second_response = client.call("second".to_owned());
+
+        assert_eq!(first_response.await.unwrap(), "a: first");
+
+        let third_response = client.call("third".to_owned());
+
+        assert_eq!(second_response.await.unwrap(), "b: second");
+        assert_eq!(third_response.await.unwrap(), "a: third");
+    }
+
+    #[tokio::test]
+    async fn power_of_two_choices_avoids_busy_endpoints() {
+        let addresses = vec![start_server("a"), start_server("b")];
+        let client = BalancingClient::<PipelineTcpClient<_>>::connect(
+            addresses,
+            LineCodec,
+            BalanceStrategy::PowerOfTwoChoices,
+        );
+
+        let responses = future::try_join(
+            client.call("first".to_owned()),
+            client.call("second".to_owned()),
+        );
+        let (first_response, second_response) = responses.await.unwrap();
+
+        assert_ne!(first_response[..1], second_response[..1]);
+    }
+
+    #[tokio::test]
+    async fn ejects_endpoints_that_fail_to_connect() {
+        let addresses = vec![free_address(), start_server("a")];
+        let mut client = BalancingClient::<PipelineTcpClient<_>>::connect(
+            addresses,
+            LineCodec,
+            BalanceStrategy::RoundRobin,
+        );
+
+        client.set_ejection_time(Duration::from_millis(100));
+
+        assert!(client.call("failed".to_owned()).await.is_err());
+
+        for _ in 0..3 {
+            let response = client.call("request".to_owned()).await;
+
+            assert_eq!(response.unwrap(), "a: request");
+        }
+
+        time::sleep(Duration::from_millis(150)).await;
+
+        // The round robin resumes with the endpoint that was ejected.
+        let first_response = client.call("first".to_owned()).await;
+        let second_response = client.call("second".to_owned()).await;
+
+        assert!(first_response.is_err());
+        assert_eq!(second_response.unwrap(), "a: second");
+    }
+
+    #[tokio::test]
+    async fn is_ready_once_an_endpoint_is_connected() {
+        let addresses = vec![free_address(), start_server("a")];
+        let mut client = BalancingClient::<PipelineTcpClient<_>>::connect(
+            addresses,
+            LineCodec,
+            BalanceStrategy::RoundRobin,
+        );
+
+        ServiceExt::<String>::ready(&mut client).await.unwrap();
+
+        // The endpoint that refused the connection was ejected.
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "a: request");
+    }
+
+    #[tokio::test]
+    async fn calls_the_endpoint_that_was_polled_ready() {
+        let addresses = vec![start_server("a"), free_address()];
+        let mut client = BalancingClient::<PipelineTcpClient<_>>::connect(
+            addresses,
+            LineCodec,
+            BalanceStrategy::RoundRobin,
+        );
+
+        for _ in 0..3 {
+            let response = ServiceExt::<String>::ready(&mut client)
+                .await
+                .unwrap()
+                .call("request".to_owned())
+                .await;
+
+            assert_eq!(response.unwrap(), "a: request");
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_without_endpoints() {
+        let client = BalancingClient::<PipelineTcpClient<_>>::connect(
+            None,
+            LineCodec,
+            BalanceStrategy::RoundRobin,
+        );
+
+        match client.call("request".to_owned()).await {
+            Err(ClientError::NotConnected) => {}
+            _ => panic!("call without endpoints did not fail"),
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_endpoint_changes() {
+        let first_address = start_server("a");
+        let second_address = start_server("b");
+
+        let (changes, changes_receiver) = mpsc::unbounded();
+        let client = BalancingClient::with_endpoint_changes(
+            changes_receiver,
+            |address| PipelineTcpClient::connect(address, LineCodec),
+            BalanceStrategy::RoundRobin,
+        );
+
+        changes
+            .unbounded_send(EndpointChange::Insert(first_address))
+            .unwrap();
+        wait_for_endpoints(&client, vec![first_address]).await;
+
+        let response = client.call("first".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "a: first");
+
+        changes
+            .unbounded_send(EndpointChange::Insert(second_address))
+            .unwrap();
+        changes
+            .unbounded_send(EndpointChange::Remove(first_address))
+            .unwrap();
+        wait_for_endpoints(&client, vec![second_address]).await;
+
+        let response = client.call("second".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "b: second");
+    }
+
+    async fn wait_for_endpoints<K>(
+        client: &BalancingClient<K>,
+        endpoints: Vec<SocketAddr>,
+    ) {
+        for _ in 0..100 {
+            if client.endpoints() == endpoints {
+                return;
+            }
+
+            time::sleep(Duration::from_millis(1)).await;
+        }
+
+        panic!("endpoint changes were not applied");
+    }
+
+    fn start_server(name: &'static str) -> SocketAddr {
+        let address = free_address();
+        let factory = move |_: &ConnectionInfo| {
+            service_fn(move |request: String| {
+                future::ok::<_, ()>(format!("{}: {}", name, request))
+            })
+        };
+
+        let server = PipelineTcpListenerServer::listen_with_factory(
+            factory, &address, LineCodec,
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        address
+    }
+
+    fn free_address() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+}
@@ -0,0 +1,14 @@
+use std::net::SocketAddr;
+
+/// Information about a connection accepted by a TCP listener server.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConnectionInfo {
+    /// Identifies the connection among the others accepted by the same
+    /// server, in the order they were accepted.
+    pub id: u64,
+    pub peer_address: SocketAddr,
+    pub local_address: SocketAddr,
+    /// The DER encoded certificate that a TLS client authenticated itself
+    /// with.
+    pub peer_certificate: Option<Vec<u8>>,
+}
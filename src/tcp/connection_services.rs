@@ -0,0 +1,60 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{channel::mpsc, Stream, StreamExt};
+
+use super::super::make_services::MakeServices;
+use super::connection_info::ConnectionInfo;
+use super::service_factory::ServiceFactory;
+
+/// The stream of services created by a `MakeService` for each accepted
+/// connection.
+pub type ConnectionMakeServices<M> =
+    MakeServices<M, mpsc::UnboundedReceiver<ConnectionInfo>>;
+
+/// The stream of services created by a `ServiceFactory` for each accepted
+/// connection.
+pub struct ConnectionServices<F>
+where
+    F: ServiceFactory,
+{
+    factory: F,
+    connections: mpsc::UnboundedReceiver<ConnectionInfo>,
+}
+
+impl<F> ConnectionServices<F>
+where
+    F: ServiceFactory,
+{
+    pub fn new(
+        factory: F,
+        connections: mpsc::UnboundedReceiver<ConnectionInfo>,
+    ) -> Self {
+        ConnectionServices {
+            factory,
+            connections,
+        }
+    }
+}
+
+// The factory is never pinned.
+impl<F> Unpin for ConnectionServices<F> where F: ServiceFactory {}
+
+impl<F> Stream for ConnectionServices<F>
+where
+    F: ServiceFactory,
+{
+    type Item = io::Result<F::Service>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        let connection = ready!(self.connections.poll_next_unpin(context));
+        let service = connection
+            .map(|connection| Ok(self.factory.new_service(&connection)));
+
+        Poll::Ready(service)
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const CONNECTING: usize = 0;
+const CONNECTED: usize = 1;
+const DISCONNECTED: usize = 2;
+const LOST: usize = 3;
+
+/// The state of a `TcpClientTransport`'s connection, shared with the client
+/// that uses it.
+#[derive(Clone)]
+pub struct ConnectionStatus {
+    state: Arc<AtomicUsize>,
+}
+
+impl ConnectionStatus {
+    pub fn new() -> Self {
+        ConnectionStatus {
+            state: Arc::new(AtomicUsize::new(CONNECTING)),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state.load(Ordering::Acquire) == CONNECTED
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        let state = self.state.load(Ordering::Acquire);
+
+        state == DISCONNECTED || state == LOST
+    }
+
+    /// Whether the connection is down and no more attempts will be made to
+    /// reestablish it.
+    pub fn is_lost(&self) -> bool {
+        self.state.load(Ordering::Acquire) == LOST
+    }
+
+    pub fn set_connected(&self) {
+        self.update(CONNECTED);
+    }
+
+    pub fn set_disconnected(&self) {
+        self.update(DISCONNECTED);
+    }
+
+    pub fn set_lost(&self) {
+        self.state.store(LOST, Ordering::Release);
+    }
+
+    // A lost connection stays lost.
+    fn update(&self, new_state: usize) {
+        let _ = self.state.fetch_update(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |state| if state == LOST { None } else { Some(new_state) },
+        );
+    }
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        ConnectionStatus::new()
+    }
+}
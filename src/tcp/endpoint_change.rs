@@ -0,0 +1,8 @@
+use std::net::SocketAddr;
+
+/// An update to the endpoints used by a `BalancingClient`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EndpointChange {
+    Insert(SocketAddr),
+    Remove(SocketAddr),
+}
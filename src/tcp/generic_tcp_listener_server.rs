@@ -1,80 +1,215 @@
+use std::future::Future;
 use std::io;
-use std::net::SocketAddr;
+use std::net::{self, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Future, Poll, Stream};
-use tokio_core::{net::TcpListener, reactor::Handle};
-use tokio_io::codec::{Decoder, Encoder};
-use tokio_service::Service;
+use futures::{FutureExt, TryStream};
+use tokio::net::TcpListener;
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
 
+#[cfg(feature = "tls")]
+use super::tls_acceptor::TlsAcceptor;
 use super::{
+    connection_info::ConnectionInfo,
+    connection_services::{ConnectionMakeServices, ConnectionServices},
     incoming_transports::IncomingTransports,
+    service_factory::ServiceFactory,
     super::{
+        codec_listener_aliases::{ErrorAlias, FutureAlias, ResponseAlias},
+        connection_limits::ConnectionLimits,
         generic_listening_server::GenericListeningServer,
-        listening_server_error::ListeningServerError,
-        server_error::ServerError,
+        make_services::MakeServices,
+        shutdown_handle::ShutdownHandle,
         stream_of_future_results::StreamOfFutureResults,
     },
 };
 
-pub type ErrorAlias<S: Stream, SI: Service, C> = ListeningServerError<
-    S::Error,
-    io::Error,
-    ServerError<<C as Decoder>::Error, <C as Encoder>::Error, SI::Error>,
->;
-
 pub struct GenericTcpListenerServer<S, C, H>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
-    H: StreamOfFutureResults<<S::Item as Service>::Future>,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, C>>,
 {
     server: GenericListeningServer<S, IncomingTransports<C>, H>,
 }
 
 impl<S, C, H> GenericTcpListenerServer<S, C, H>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
-    H: StreamOfFutureResults<<S::Item as Service>::Future>,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, C>>,
 {
     pub fn listen(
         services: S,
         address: &SocketAddr,
         codec: C,
-        handle: &Handle,
     ) -> io::Result<Self> {
-        let listener = TcpListener::bind(address, handle)?;
-        let incoming = listener.incoming();
-        let transports = IncomingTransports::new(codec, incoming);
+        let transports = IncomingTransports::new(codec, bind(address)?);
+
+        Ok(Self::with_transports(services, transports))
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_tls(
+        services: S,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let transports =
+            IncomingTransports::with_tls(codec, bind(address)?, acceptor);
 
-        Ok(GenericTcpListenerServer {
+        Ok(Self::with_transports(services, transports))
+    }
+
+    fn with_transports(services: S, transports: IncomingTransports<C>) -> Self {
+        GenericTcpListenerServer {
             server: GenericListeningServer::new(services, transports),
-        })
+        }
+    }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.server.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, C>) + Send + 'static,
+    {
+        self.server.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.server.fail_fast();
+    }
+}
+
+impl<F, C, H> GenericTcpListenerServer<ConnectionServices<F>, C, H>
+where
+    F: ServiceFactory,
+    F::Service: Service<C::Item>,
+    C: Clone
+        + Decoder
+        + Encoder<<F::Service as Service<C::Item>>::Response>
+        + Unpin,
+    H: StreamOfFutureResults<<F::Service as Service<C::Item>>::Future>,
+{
+    pub fn listen_with_factory(
+        factory: F,
+        address: &SocketAddr,
+        codec: C,
+    ) -> io::Result<Self> {
+        let transports = IncomingTransports::new(codec, bind(address)?);
+
+        Ok(Self::with_factory(factory, transports))
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`, creating
+    /// a new service for each of them with the `factory`.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_factory_and_tls(
+        factory: F,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let transports =
+            IncomingTransports::with_tls(codec, bind(address)?, acceptor);
+
+        Ok(Self::with_factory(factory, transports))
+    }
+
+    fn with_factory(factory: F, mut transports: IncomingTransports<C>) -> Self {
+        let services =
+            ConnectionServices::new(factory, transports.connection_infos());
+
+        GenericTcpListenerServer {
+            server: GenericListeningServer::new(services, transports),
+        }
+    }
+}
+
+impl<M, C, H> GenericTcpListenerServer<ConnectionMakeServices<M>, C, H>
+where
+    M: Service<ConnectionInfo>,
+    M::Response: Service<C::Item>,
+    C: Clone
+        + Decoder
+        + Encoder<<M::Response as Service<C::Item>>::Response>
+        + Unpin,
+    H: StreamOfFutureResults<<M::Response as Service<C::Item>>::Future>,
+{
+    pub fn listen_with_make_service(
+        make_service: M,
+        address: &SocketAddr,
+        codec: C,
+    ) -> io::Result<Self> {
+        let transports = IncomingTransports::new(codec, bind(address)?);
+
+        Ok(Self::with_make_service(make_service, transports))
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`, asking
+    /// the `make_service` for a new service for each of them.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_make_service_and_tls(
+        make_service: M,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let transports =
+            IncomingTransports::with_tls(codec, bind(address)?, acceptor);
+
+        Ok(Self::with_make_service(make_service, transports))
+    }
+
+    fn with_make_service(
+        make_service: M,
+        mut transports: IncomingTransports<C>,
+    ) -> Self {
+        let services = MakeServices::with_targets(
+            make_service,
+            transports.connection_infos(),
+        );
+
+        GenericTcpListenerServer {
+            server: GenericListeningServer::new(services, transports),
+        }
     }
 }
 
 impl<S, C, H> Future for GenericTcpListenerServer<S, C, H>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
-    H: StreamOfFutureResults<<S::Item as Service>::Future>,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, C>>,
 {
-    type Item = ();
-    type Error = ErrorAlias<S, S::Item, C>;
+    type Output = Result<(), ErrorAlias<S, C>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.server.poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.server.poll_unpin(context)
     }
 }
+
+/// Binds a listener to the `address`, registering it with the current Tokio
+/// runtime.
+fn bind(address: &SocketAddr) -> io::Result<TcpListener> {
+    let listener = net::TcpListener::bind(address)?;
+
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
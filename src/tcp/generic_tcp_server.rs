@@ -1,20 +1,26 @@
-use futures::{Future, Poll};
-use tokio_core::net::TcpStream;
-use tokio_io::{AsyncRead, codec::{Decoder, Encoder, Framed}};
-use tokio_service::Service;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::FutureExt;
+use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tower_service::Service;
 
 use super::super::{
+    connection_limits::ConnectionLimits,
     generic_server::{GenericServer, ServerErrorAlias as GenericServerError},
+    shutdown_handle::ShutdownHandle,
     stream_of_future_results::StreamOfFutureResults,
 };
 
-pub type ServerErrorAlias<S: Service, C> =
+pub type ServerErrorAlias<S, C> =
     GenericServerError<S, Framed<TcpStream, C>>;
 
 pub struct GenericTcpServer<S, C, H>
 where
-    S: Service,
-    C: Decoder<Item = S::Request> + Encoder<Item = S::Response>,
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response> + Unpin,
     H: StreamOfFutureResults<S::Future>,
 {
     server: GenericServer<S, Framed<TcpStream, C>, H>,
@@ -22,29 +28,39 @@ where
 
 impl<S, C, H> GenericTcpServer<S, C, H>
 where
-    S: Service,
-    C: Decoder<Item = S::Request> + Encoder<Item = S::Response>,
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response> + Unpin,
     H: StreamOfFutureResults<S::Future>,
 {
     pub fn new(service: S, connection: TcpStream, codec: C) -> Self {
-        let transport = connection.framed(codec);
+        let transport = Framed::new(connection, codec);
 
         GenericTcpServer {
             server: GenericServer::new(service, transport),
         }
     }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.server.set_connection_limits(limits);
+    }
 }
 
 impl<S, C, H> Future for GenericTcpServer<S, C, H>
 where
-    S: Service,
-    C: Decoder<Item = S::Request> + Encoder<Item = S::Response>,
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response> + Unpin,
     H: StreamOfFutureResults<S::Future>,
 {
-    type Item = ();
-    type Error = ServerErrorAlias<S, C>;
+    type Output = Result<(), ServerErrorAlias<S, C>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.server.poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.server.poll_unpin(context)
     }
 }
@@ -1,42 +1,131 @@
 use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Async, Poll, Stream};
-use tokio_core::net::{Incoming, TcpStream};
-use tokio_io::{AsyncRead, codec::{Decoder, Encoder, Framed}};
+use futures::channel::mpsc;
+use futures::{Stream, StreamExt};
+use tokio::net::TcpListener;
+use tokio_util::codec::{Decoder, Framed};
+
+use super::connection_info::ConnectionInfo;
+use super::tcp_connection::TcpConnection;
+use super::tcp_incoming::TcpIncoming;
+#[cfg(feature = "tls")]
+use super::{tls_acceptor::TlsAcceptor, tls_incoming::TlsIncoming};
+
+type Connections = Pin<
+    Box<dyn Stream<Item = io::Result<(TcpConnection, SocketAddr)>> + Send>,
+>;
 
 pub struct IncomingTransports<C>
 where
-    C: Clone + Decoder + Encoder,
+    C: Clone + Decoder,
 {
     codec: C,
-    connections: Incoming,
+    connections: Connections,
+    next_connection_id: u64,
+    connection_infos: Option<mpsc::UnboundedSender<ConnectionInfo>>,
 }
 
 impl<C> IncomingTransports<C>
 where
-    C: Clone + Decoder + Encoder,
+    C: Clone + Decoder,
 {
-    pub fn new(codec: C, connections: Incoming) -> Self {
+    pub fn new(codec: C, listener: TcpListener) -> Self {
+        let connections = TcpIncoming::new(listener).map(|result| {
+            result.map(|(socket, address)| {
+                (TcpConnection::from(socket), address)
+            })
+        });
+
+        Self::with_connections(codec, Box::pin(connections))
+    }
+
+    /// Secures each accepted connection with TLS before producing its
+    /// transport.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(
+        codec: C,
+        listener: TcpListener,
+        acceptor: TlsAcceptor,
+    ) -> Self {
+        let connections = TlsIncoming::new(listener, acceptor);
+
+        Self::with_connections(codec, Box::pin(connections))
+    }
+
+    fn with_connections(codec: C, connections: Connections) -> Self {
         IncomingTransports {
             codec,
             connections,
+            next_connection_id: 0,
+            connection_infos: None,
         }
     }
+
+    /// Reports the information of each accepted connection, in the same
+    /// order as the transports are produced.
+    pub fn connection_infos(
+        &mut self,
+    ) -> mpsc::UnboundedReceiver<ConnectionInfo> {
+        let (sender, receiver) = mpsc::unbounded();
+
+        self.connection_infos = Some(sender);
+        receiver
+    }
+
+    fn report(
+        &mut self,
+        connection: &TcpConnection,
+        peer_address: SocketAddr,
+    ) -> io::Result<()> {
+        let id = self.next_connection_id;
+
+        self.next_connection_id += 1;
+
+        if let Some(ref connection_infos) = self.connection_infos {
+            let info = ConnectionInfo {
+                id,
+                peer_address,
+                local_address: connection.local_addr()?,
+                peer_certificate: connection.peer_certificate(),
+            };
+
+            // The services stream might have been dropped already, in which
+            // case nobody is interested in the connection.
+            let _ = connection_infos.unbounded_send(info);
+        }
+
+        Ok(())
+    }
 }
 
 impl<C> Stream for IncomingTransports<C>
 where
-    C: Clone + Decoder + Encoder,
+    C: Clone + Decoder + Unpin,
 {
-    type Item = Framed<TcpStream, C>;
-    type Error = io::Error;
+    type Item = io::Result<Framed<TcpConnection, C>>;
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        let connection = try_ready!(self.connections.poll());
-        let transport = connection.map(|(connection, _address)| {
-            connection.framed(self.codec.clone())
-        });
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        match ready!(self.connections.poll_next_unpin(context)) {
+            Some(Ok((connection, peer_address))) => {
+                self.report(&connection, peer_address)?;
+
+                let transport = Framed::new(connection, self.codec.clone());
 
-        Ok(Async::Ready(transport))
+                Poll::Ready(Some(Ok(transport)))
+            }
+            Some(Err(error)) => Poll::Ready(Some(Err(error))),
+            None => {
+                // Ends the services stream as well.
+                self.connection_infos.take();
+
+                Poll::Ready(None)
+            }
+        }
     }
 }
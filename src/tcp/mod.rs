@@ -1,8 +1,28 @@
+mod balance_strategy;
+mod connection_info;
+mod connection_services;
+mod connection_status;
+mod endpoint_change;
 mod incoming_transports;
+mod pool_limits;
+mod poolable_client;
+mod reconnect_policy;
+mod service_factory;
+mod tcp_client_connection;
 mod tcp_client_transport;
+mod tcp_connection;
+mod tcp_incoming;
+#[cfg(feature = "tls")]
+mod tls_acceptor;
+#[cfg(feature = "tls")]
+mod tls_connector;
+#[cfg(feature = "tls")]
+mod tls_incoming;
 
+mod balancing_client;
 mod multiplex_tcp_client;
 mod pipeline_tcp_client;
+mod pooled_client;
 
 mod generic_tcp_server;
 mod multiplex_tcp_server;
@@ -12,8 +32,23 @@ mod generic_tcp_listener_server;
 mod multiplex_tcp_listener_server;
 mod pipeline_tcp_listener_server;
 
+pub use self::balance_strategy::BalanceStrategy;
+pub use self::balancing_client::BalancingClient;
+pub use self::endpoint_change::EndpointChange;
 pub use self::multiplex_tcp_client::MultiplexTcpClient;
 pub use self::pipeline_tcp_client::PipelineTcpClient;
+pub use self::pool_limits::PoolLimits;
+pub use self::poolable_client::PoolableClient;
+pub use self::pooled_client::PooledClient;
+pub use self::reconnect_policy::{ReconnectPolicy, WhileReconnecting};
+
+pub use self::connection_info::ConnectionInfo;
+pub use self::connection_services::{ConnectionMakeServices, ConnectionServices};
+pub use self::service_factory::ServiceFactory;
+#[cfg(feature = "tls")]
+pub use self::tls_acceptor::TlsAcceptor;
+#[cfg(feature = "tls")]
+pub use self::tls_connector::TlsConnector;
 
 pub use self::multiplex_tcp_server::MultiplexTcpServer;
 pub use self::pipeline_tcp_server::PipelineTcpServer;
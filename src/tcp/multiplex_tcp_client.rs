@@ -1,41 +1,101 @@
-use std::{hash::Hash, net::SocketAddr};
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
 
-use tokio_core::{net::TcpStream, reactor::Handle};
-use tokio_io::codec::{Decoder, Encoder};
-use tokio_service::Service;
+use futures::future::{self, Either, Ready};
+use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
 
 use super::{
     super::{
-        client_error::ClientError, message_with_id::MessageWithId,
-        multiplex_client::MultiplexClient,
+        client_error::ClientError,
+        message_with_id::MessageWithId,
+        multiplex_client::{MultiplexClient, MultiplexClientFuture},
     },
+    reconnect_policy::ReconnectPolicy,
+    tcp_client_connection::{TcpClientConnection, Unavailable},
     tcp_client_transport::TcpClientTransport,
 };
+#[cfg(feature = "tls")]
+use super::tls_connector::TlsConnector;
+
+pub type MultiplexTcpClientFuture<C, R> = Either<
+    MultiplexClientFuture<TcpClientTransport<C>, R>,
+    Ready<Result<<C as Decoder>::Item, ErrorAlias<C, R>>>,
+>;
+
+type ErrorAlias<C, R> =
+    ClientError<<C as Decoder>::Error, <C as Encoder<R>>::Error>;
 
 pub struct MultiplexTcpClient<C>
 where
-    C: Decoder + Encoder,
-    <C as Decoder>::Item: MessageWithId,
-    <C as Encoder>::Item:
-        MessageWithId<Id = <<C as Decoder>::Item as MessageWithId>::Id>,
-    <<C as Decoder>::Item as MessageWithId>::Id: Eq + Hash,
+    C: Decoder + Unpin,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
 {
-    client: MultiplexClient<TcpClientTransport<C>>,
+    connection: TcpClientConnection<MultiplexClient<TcpClientTransport<C>>, C>,
 }
 
 impl<C> MultiplexTcpClient<C>
 where
-    C: Decoder + Encoder,
-    <C as Decoder>::Item: MessageWithId,
-    <C as Encoder>::Item:
-        MessageWithId<Id = <<C as Decoder>::Item as MessageWithId>::Id>,
-    <<C as Decoder>::Item as MessageWithId>::Id: Eq + Hash,
+    C: Decoder + Unpin,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
 {
-    pub fn connect(address: &SocketAddr, codec: C, handle: &Handle) -> Self {
-        let transport = TcpClientTransport::connect(address, codec, handle);
+    pub fn connect(address: &SocketAddr, codec: C) -> Self {
+        let transport = TcpClientTransport::connect(address, codec);
+
+        MultiplexTcpClient {
+            connection: TcpClientConnection::new(
+                transport,
+                MultiplexClient::new,
+            ),
+        }
+    }
+
+    /// Connects to the server, and reconnects whenever the connection is
+    /// lost, as configured by the `policy`.
+    ///
+    /// Requests that were waiting for a response when the connection was lost
+    /// fail with `ClientError::ConnectionLost`, and so do all requests once
+    /// the `policy`'s attempts run out.
+    ///
+    /// The connection attempts are spawned on the current Tokio runtime, so
+    /// this panics when called outside of one.
+    pub fn connect_with_reconnection(
+        address: &SocketAddr,
+        codec: C,
+        policy: ReconnectPolicy,
+    ) -> Self
+    where
+        C: Clone + Send + Sync + 'static,
+    {
+        MultiplexTcpClient {
+            connection: TcpClientConnection::reconnecting(
+                address,
+                codec,
+                policy,
+                MultiplexClient::new,
+            ),
+        }
+    }
+
+    /// Connects to the server and secures the connection with TLS.
+    #[cfg(feature = "tls")]
+    pub fn connect_with_tls(
+        address: &SocketAddr,
+        connector: TlsConnector,
+        codec: C,
+    ) -> Self {
+        let transport =
+            TcpClientTransport::connect_with_tls(address, connector, codec);
 
         MultiplexTcpClient {
-            client: MultiplexClient::new(transport),
+            connection: TcpClientConnection::new(
+                transport,
+                MultiplexClient::new,
+            ),
         }
     }
 
@@ -43,27 +103,84 @@ where
         let transport = TcpClientTransport::with_connection(connection, codec);
 
         MultiplexTcpClient {
-            client: MultiplexClient::new(transport),
+            connection: TcpClientConnection::new(
+                transport,
+                MultiplexClient::new,
+            ),
         }
     }
+
+    /// Whether the connection failed or was lost, and won't be reestablished.
+    ///
+    /// Clients that reconnect are only considered lost once they run out of
+    /// reconnection attempts.
+    pub fn is_connection_lost(&self) -> bool {
+        self.connection.is_lost()
+    }
+
+    pub fn discarded_responses(&self) -> usize {
+        self.connection
+            .with_client(|client| client.discarded_responses())
+    }
+
+    pub fn call<R>(&self, request: R) -> MultiplexTcpClientFuture<C, R>
+    where
+        C: Encoder<R>,
+        R: MessageWithId<Id = <C::Item as MessageWithId>::Id>,
+    {
+        let response = self.connection.call(|client| client.call(request));
+
+        match response {
+            Ok(response) => Either::Left(response),
+            Err(unavailable) => {
+                Either::Right(future::err(unavailable.into_client_error()))
+            }
+        }
+    }
+
+    /// Waits until the connection is established and can accept another
+    /// request.
+    ///
+    /// While a client that fails fast is reconnecting, it is always ready,
+    /// so that its calls fail immediately. Once a reconnecting client runs
+    /// out of attempts, this fails with `ClientError::ConnectionLost`.
+    pub fn poll_ready<R>(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), ErrorAlias<C, R>>>
+    where
+        C: Encoder<R>,
+    {
+        self.connection
+            .call(|client| client.poll_ready::<R>(context))
+            .unwrap_or_else(|unavailable| match unavailable {
+                Unavailable::Reconnecting => Poll::Ready(Ok(())),
+                Unavailable::Lost => {
+                    Poll::Ready(Err(unavailable.into_client_error()))
+                }
+            })
+    }
 }
 
-impl<C> Service for MultiplexTcpClient<C>
+impl<C, R> Service<R> for MultiplexTcpClient<C>
 where
-    C: Decoder + Encoder,
-    <C as Decoder>::Item: MessageWithId,
-    <C as Encoder>::Item:
-        MessageWithId<Id = <<C as Decoder>::Item as MessageWithId>::Id>,
-    <<C as Decoder>::Item as MessageWithId>::Id: Eq + Hash,
+    C: Decoder + Encoder<R> + Unpin,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = <C::Item as MessageWithId>::Id>,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
 {
-    type Request = <C as Encoder>::Item;
-    type Response = <C as Decoder>::Item;
-    type Error = ClientError<<C as Decoder>::Error, <C as Encoder>::Error>;
-    type Future = <MultiplexClient<TcpClientTransport<C>> as Service>::Future;
+    type Response = C::Item;
+    type Error = ErrorAlias<C, R>;
+    type Future = MultiplexTcpClientFuture<C, R>;
 
-    fn call(&self, request: Self::Request) -> Self::Future {
-        let client = &self.client;
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        MultiplexTcpClient::poll_ready(self, context)
+    }
 
-        client.call(request)
+    fn call(&mut self, request: R) -> Self::Future {
+        MultiplexTcpClient::call(self, request)
     }
 }
@@ -1,46 +1,166 @@
+use std::future::Future;
 use std::io;
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Future, Poll, Stream, stream::FuturesUnordered};
-use tokio_core::reactor::Handle;
-use tokio_io::codec::{Decoder, Encoder};
-use tokio_service::Service;
+use futures::{stream::FuturesUnordered, FutureExt, TryStream};
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
 
-use super::generic_tcp_listener_server::{ErrorAlias, GenericTcpListenerServer};
+use super::super::codec_listener_aliases::{
+    ErrorAlias, FutureAlias, ResponseAlias,
+};
+use super::super::connection_limits::ConnectionLimits;
+use super::super::shutdown_handle::ShutdownHandle;
+use super::connection_info::ConnectionInfo;
+use super::connection_services::{ConnectionMakeServices, ConnectionServices};
+use super::generic_tcp_listener_server::GenericTcpListenerServer;
+use super::service_factory::ServiceFactory;
+#[cfg(feature = "tls")]
+use super::tls_acceptor::TlsAcceptor;
 
 pub struct MultiplexTcpListenerServer<S, C>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
 {
-    listener: GenericTcpListenerServer<
-        S,
-        C,
-        FuturesUnordered<<S::Item as Service>::Future>,
-    >,
+    listener:
+        GenericTcpListenerServer<S, C, FuturesUnordered<FutureAlias<S, C>>>,
 }
 
 impl<S, C> MultiplexTcpListenerServer<S, C>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
 {
     pub fn listen(
         services: S,
         address: &SocketAddr,
         codec: C,
-        handle: &Handle,
     ) -> io::Result<Self> {
         let listener =
-            GenericTcpListenerServer::listen(services, address, codec, handle)?;
+            GenericTcpListenerServer::listen(services, address, codec)?;
+
+        Ok(MultiplexTcpListenerServer { listener })
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_tls(
+        services: S,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_tls(
+            services, address, acceptor, codec,
+        )?;
+
+        Ok(MultiplexTcpListenerServer { listener })
+    }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.listener.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.listener.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, C>) + Send + 'static,
+    {
+        self.listener.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.listener.fail_fast();
+    }
+}
+
+impl<F, C> MultiplexTcpListenerServer<ConnectionServices<F>, C>
+where
+    F: ServiceFactory,
+    F::Service: Service<C::Item>,
+    C: Clone
+        + Decoder
+        + Encoder<<F::Service as Service<C::Item>>::Response>
+        + Unpin,
+{
+    /// Listens for connections, creating a new service for each of them with
+    /// the `factory`.
+    pub fn listen_with_factory(
+        factory: F,
+        address: &SocketAddr,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_factory(
+            factory, address, codec,
+        )?;
+
+        Ok(MultiplexTcpListenerServer { listener })
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`, creating
+    /// a new service for each of them with the `factory`.
+    ///
+    /// The certificate that each client authenticated itself with is
+    /// available in its `ConnectionInfo`.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_factory_and_tls(
+        factory: F,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_factory_and_tls(
+            factory, address, acceptor, codec,
+        )?;
+
+        Ok(MultiplexTcpListenerServer { listener })
+    }
+}
+
+impl<M, C> MultiplexTcpListenerServer<ConnectionMakeServices<M>, C>
+where
+    M: Service<ConnectionInfo>,
+    M::Response: Service<C::Item>,
+    C: Clone
+        + Decoder
+        + Encoder<<M::Response as Service<C::Item>>::Response>
+        + Unpin,
+{
+    /// Listens for connections, asking the `make_service` for a new service
+    /// for each of them.
+    pub fn listen_with_make_service(
+        make_service: M,
+        address: &SocketAddr,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_make_service(
+            make_service, address, codec,
+        )?;
+
+        Ok(MultiplexTcpListenerServer { listener })
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`, asking
+    /// the `make_service` for a new service for each of them.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_make_service_and_tls(
+        make_service: M,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener =
+            GenericTcpListenerServer::listen_with_make_service_and_tls(
+                make_service, address, acceptor, codec,
+            )?;
 
         Ok(MultiplexTcpListenerServer { listener })
     }
@@ -48,17 +168,16 @@ where
 
 impl<S, C> Future for MultiplexTcpListenerServer<S, C>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
 {
-    type Item = ();
-    type Error = ErrorAlias<S, S::Item, C>;
+    type Output = Result<(), ErrorAlias<S, C>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.listener.poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.listener.poll_unpin(context)
     }
 }
@@ -1,40 +1,56 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
 use futures::stream::FuturesUnordered;
-use futures::{Future, Poll};
-use tokio_core::net::TcpStream;
-use tokio_io::codec::{Decoder, Encoder};
-use tokio_service::Service;
+use futures::FutureExt;
+use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
 
+use super::super::connection_limits::ConnectionLimits;
+use super::super::shutdown_handle::ShutdownHandle;
 use super::generic_tcp_server::{GenericTcpServer, ServerErrorAlias};
 
 pub struct MultiplexTcpServer<S, C>
 where
-    S: Service,
-    C: Decoder<Item = S::Request> + Encoder<Item = S::Response>,
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response> + Unpin,
 {
     server: GenericTcpServer<S, C, FuturesUnordered<S::Future>>,
 }
 
 impl<S, C> MultiplexTcpServer<S, C>
 where
-    S: Service,
-    C: Decoder<Item = S::Request> + Encoder<Item = S::Response>,
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response> + Unpin,
 {
     pub fn new(service: S, connection: TcpStream, codec: C) -> Self {
         MultiplexTcpServer {
             server: GenericTcpServer::new(service, connection, codec),
         }
     }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.server.set_connection_limits(limits);
+    }
 }
 
 impl<S, C> Future for MultiplexTcpServer<S, C>
 where
-    S: Service,
-    C: Decoder<Item = S::Request> + Encoder<Item = S::Response>,
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response> + Unpin,
 {
-    type Item = ();
-    type Error = ServerErrorAlias<S, C>;
+    type Output = Result<(), ServerErrorAlias<S, C>>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.server.poll()
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.server.poll_unpin(context)
     }
 }
@@ -1,30 +1,95 @@
 use std::net::SocketAddr;
+use std::task::{Context, Poll};
 
-use tokio_core::{net::TcpStream, reactor::Handle};
-use tokio_io::codec::{Decoder, Encoder};
-use tokio_service::Service;
+use futures::future::{self, Either, Ready};
+use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
 
 use super::{
-    super::{client_error::ClientError, pipeline_client::PipelineClient},
+    super::{
+        client_error::ClientError,
+        pipeline_client::{PipelineClient, PipelineClientFuture},
+    },
+    reconnect_policy::ReconnectPolicy,
+    tcp_client_connection::{TcpClientConnection, Unavailable},
     tcp_client_transport::TcpClientTransport,
 };
+#[cfg(feature = "tls")]
+use super::tls_connector::TlsConnector;
+
+pub type PipelineTcpClientFuture<C, R> = Either<
+    PipelineClientFuture<TcpClientTransport<C>, R>,
+    Ready<Result<<C as Decoder>::Item, ErrorAlias<C, R>>>,
+>;
+
+type ErrorAlias<C, R> =
+    ClientError<<C as Decoder>::Error, <C as Encoder<R>>::Error>;
 
 pub struct PipelineTcpClient<C>
 where
-    C: Decoder + Encoder,
+    C: Decoder + Unpin,
 {
-    client: PipelineClient<TcpClientTransport<C>>,
+    connection: TcpClientConnection<PipelineClient<TcpClientTransport<C>>, C>,
 }
 
 impl<C> PipelineTcpClient<C>
 where
-    C: Decoder + Encoder,
+    C: Decoder + Unpin,
 {
-    pub fn connect(address: &SocketAddr, codec: C, handle: &Handle) -> Self {
-        let transport = TcpClientTransport::connect(address, codec, handle);
+    pub fn connect(address: &SocketAddr, codec: C) -> Self {
+        let transport = TcpClientTransport::connect(address, codec);
+
+        PipelineTcpClient {
+            connection: TcpClientConnection::new(
+                transport,
+                PipelineClient::new,
+            ),
+        }
+    }
+
+    /// Connects to the server, and reconnects whenever the connection is
+    /// lost, as configured by the `policy`.
+    ///
+    /// Requests that were waiting for a response when the connection was lost
+    /// fail with `ClientError::ConnectionLost`, and so do all requests once
+    /// the `policy`'s attempts run out.
+    ///
+    /// The connection attempts are spawned on the current Tokio runtime, so
+    /// this panics when called outside of one.
+    pub fn connect_with_reconnection(
+        address: &SocketAddr,
+        codec: C,
+        policy: ReconnectPolicy,
+    ) -> Self
+    where
+        C: Clone + Send + Sync + 'static,
+    {
+        PipelineTcpClient {
+            connection: TcpClientConnection::reconnecting(
+                address,
+                codec,
+                policy,
+                PipelineClient::new,
+            ),
+        }
+    }
+
+    /// Connects to the server and secures the connection with TLS.
+    #[cfg(feature = "tls")]
+    pub fn connect_with_tls(
+        address: &SocketAddr,
+        connector: TlsConnector,
+        codec: C,
+    ) -> Self {
+        let transport =
+            TcpClientTransport::connect_with_tls(address, connector, codec);
 
         PipelineTcpClient {
-            client: PipelineClient::new(transport),
+            connection: TcpClientConnection::new(
+                transport,
+                PipelineClient::new,
+            ),
         }
     }
 
@@ -32,23 +97,211 @@ where
         let transport = TcpClientTransport::with_connection(connection, codec);
 
         PipelineTcpClient {
-            client: PipelineClient::new(transport),
+            connection: TcpClientConnection::new(
+                transport,
+                PipelineClient::new,
+            ),
         }
     }
+
+    /// Whether the connection failed or was lost, and won't be reestablished.
+    ///
+    /// Clients that reconnect are only considered lost once they run out of
+    /// reconnection attempts.
+    pub fn is_connection_lost(&self) -> bool {
+        self.connection.is_lost()
+    }
+
+    pub fn call<R>(&self, request: R) -> PipelineTcpClientFuture<C, R>
+    where
+        C: Encoder<R>,
+    {
+        let response = self.connection.call(|client| client.call(request));
+
+        match response {
+            Ok(response) => Either::Left(response),
+            Err(unavailable) => {
+                Either::Right(future::err(unavailable.into_client_error()))
+            }
+        }
+    }
+
+    /// Waits until the connection is established and can accept another
+    /// request.
+    ///
+    /// While a client that fails fast is reconnecting, it is always ready,
+    /// so that its calls fail immediately. Once a reconnecting client runs
+    /// out of attempts, this fails with `ClientError::ConnectionLost`.
+    pub fn poll_ready<R>(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), ErrorAlias<C, R>>>
+    where
+        C: Encoder<R>,
+    {
+        self.connection
+            .call(|client| client.poll_ready::<R>(context))
+            .unwrap_or_else(|unavailable| match unavailable {
+                Unavailable::Reconnecting => Poll::Ready(Ok(())),
+                Unavailable::Lost => {
+                    Poll::Ready(Err(unavailable.into_client_error()))
+                }
+            })
+    }
 }
 
-impl<C> Service for PipelineTcpClient<C>
+impl<C, R> Service<R> for PipelineTcpClient<C>
 where
-    C: Decoder + Encoder,
+    C: Decoder + Encoder<R> + Unpin,
 {
-    type Request = <C as Encoder>::Item;
-    type Response = <C as Decoder>::Item;
-    type Error = ClientError<<C as Decoder>::Error, <C as Encoder>::Error>;
-    type Future = <PipelineClient<TcpClientTransport<C>> as Service>::Future;
+    type Response = C::Item;
+    type Error = ErrorAlias<C, R>;
+    type Future = PipelineTcpClientFuture<C, R>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        PipelineTcpClient::poll_ready(self, context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        PipelineTcpClient::call(self, request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    use tokio::time;
+
+    use super::*;
+    use super::super::reconnect_policy::WhileReconnecting;
+    use crate::tests::common::LineCodec;
+
+    #[tokio::test]
+    async fn reconnects_after_connection_is_lost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            drop_first_connection_then_echo(listener);
+        });
+
+        let client = PipelineTcpClient::connect_with_reconnection(
+            &address,
+            LineCodec,
+            quick_reconnection(WhileReconnecting::QueueRequests),
+        );
+
+        match client.call("lost request".to_owned()).await {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("request did not fail when the connection was lost"),
+        }
 
-    fn call(&self, request: Self::Request) -> Self::Future {
-        let client = &self.client;
+        let response = client.call("request".to_owned()).await;
 
-        client.call(request)
+        assert_eq!(response.unwrap(), "REQUEST");
+
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_fast_while_reconnecting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            drop_first_connection_then_echo(listener);
+        });
+
+        let client = PipelineTcpClient::connect_with_reconnection(
+            &address,
+            LineCodec,
+            quick_reconnection(WhileReconnecting::FailFast),
+        );
+
+        assert!(client.call("lost request".to_owned()).await.is_err());
+
+        match client.call("early request".to_owned()).await {
+            Err(ClientError::NotConnected) => {}
+            _ => panic!("request did not fail while reconnecting"),
+        }
+
+        time::sleep(Duration::from_millis(200)).await;
+
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn connection_is_lost_once_attempts_run_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (connection, _) = listener.accept().unwrap();
+            let mut request = String::new();
+
+            BufReader::new(connection).read_line(&mut request).unwrap();
+        });
+
+        let client = PipelineTcpClient::connect_with_reconnection(
+            &address,
+            LineCodec,
+            ReconnectPolicy {
+                max_attempts: Some(2),
+                ..quick_reconnection(WhileReconnecting::QueueRequests)
+            },
+        );
+
+        assert!(client.call("lost request".to_owned()).await.is_err());
+        assert!(!client.is_connection_lost());
+
+        server.join().unwrap();
+
+        assert!(client.call("unsent request".to_owned()).await.is_err());
+        assert!(client.is_connection_lost());
+
+        match client.call("late request".to_owned()).await {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("request did not fail after reconnection gave up"),
+        }
+    }
+
+    fn quick_reconnection(
+        while_reconnecting: WhileReconnecting,
+    ) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+            jitter: 0.0,
+            max_attempts: Some(10),
+            while_reconnecting,
+        }
+    }
+
+    fn drop_first_connection_then_echo(listener: TcpListener) {
+        let (first_connection, _) = listener.accept().unwrap();
+        let mut first_request = String::new();
+
+        BufReader::new(first_connection)
+            .read_line(&mut first_request)
+            .unwrap();
+
+        let (mut connection, _) = listener.accept().unwrap();
+        let reader = BufReader::new(connection.try_clone().unwrap());
+
+        for line in reader.lines() {
+            let response = line.unwrap().to_uppercase();
+
+            writeln!(connection, "{}", response).unwrap();
+        }
     }
 }
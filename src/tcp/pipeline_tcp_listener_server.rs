@@ -1,46 +1,166 @@
+use std::future::Future;
 use std::io;
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use futures::{Future, Poll, Stream, stream::FuturesOrdered};
-use tokio_core::reactor::Handle;
-use tokio_io::codec::{Decoder, Encoder};
-use tokio_service::Service;
+use futures::{stream::FuturesOrdered, FutureExt, TryStream};
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
 
-use super::generic_tcp_listener_server::{ErrorAlias, GenericTcpListenerServer};
+use super::super::codec_listener_aliases::{
+    ErrorAlias, FutureAlias, ResponseAlias,
+};
+use super::super::connection_limits::ConnectionLimits;
+use super::super::shutdown_handle::ShutdownHandle;
+use super::connection_info::ConnectionInfo;
+use super::connection_services::{ConnectionMakeServices, ConnectionServices};
+use super::generic_tcp_listener_server::GenericTcpListenerServer;
+use super::service_factory::ServiceFactory;
+#[cfg(feature = "tls")]
+use super::tls_acceptor::TlsAcceptor;
 
 pub struct PipelineTcpListenerServer<S, C>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
 {
-    listener: GenericTcpListenerServer<
-        S,
-        C,
-        FuturesOrdered<<S::Item as Service>::Future>,
-    >,
+    listener:
+        GenericTcpListenerServer<S, C, FuturesOrdered<FutureAlias<S, C>>>,
 }
 
 impl<S, C> PipelineTcpListenerServer<S, C>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
 {
     pub fn listen(
         services: S,
         address: &SocketAddr,
         codec: C,
-        handle: &Handle,
     ) -> io::Result<Self> {
         let listener =
-            GenericTcpListenerServer::listen(services, address, codec, handle)?;
+            GenericTcpListenerServer::listen(services, address, codec)?;
+
+        Ok(PipelineTcpListenerServer { listener })
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_tls(
+        services: S,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_tls(
+            services, address, acceptor, codec,
+        )?;
+
+        Ok(PipelineTcpListenerServer { listener })
+    }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.listener.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.listener.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, C>) + Send + 'static,
+    {
+        self.listener.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.listener.fail_fast();
+    }
+}
+
+impl<F, C> PipelineTcpListenerServer<ConnectionServices<F>, C>
+where
+    F: ServiceFactory,
+    F::Service: Service<C::Item>,
+    C: Clone
+        + Decoder
+        + Encoder<<F::Service as Service<C::Item>>::Response>
+        + Unpin,
+{
+    /// Listens for connections, creating a new service for each of them with
+    /// the `factory`.
+    pub fn listen_with_factory(
+        factory: F,
+        address: &SocketAddr,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_factory(
+            factory, address, codec,
+        )?;
+
+        Ok(PipelineTcpListenerServer { listener })
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`, creating
+    /// a new service for each of them with the `factory`.
+    ///
+    /// The certificate that each client authenticated itself with is
+    /// available in its `ConnectionInfo`.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_factory_and_tls(
+        factory: F,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_factory_and_tls(
+            factory, address, acceptor, codec,
+        )?;
+
+        Ok(PipelineTcpListenerServer { listener })
+    }
+}
+
+impl<M, C> PipelineTcpListenerServer<ConnectionMakeServices<M>, C>
+where
+    M: Service<ConnectionInfo>,
+    M::Response: Service<C::Item>,
+    C: Clone
+        + Decoder
+        + Encoder<<M::Response as Service<C::Item>>::Response>
+        + Unpin,
+{
+    /// Listens for connections, asking the `make_service` for a new service
+    /// for each of them.
+    pub fn listen_with_make_service(
+        make_service: M,
+        address: &SocketAddr,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener = GenericTcpListenerServer::listen_with_make_service(
+            make_service, address, codec,
+        )?;
+
+        Ok(PipelineTcpListenerServer { listener })
+    }
+
+    /// Listens for connections secured with TLS by the `acceptor`, asking
+    /// the `make_service` for a new service for each of them.
+    #[cfg(feature = "tls")]
+    pub fn listen_with_make_service_and_tls(
+        make_service: M,
+        address: &SocketAddr,
+        acceptor: TlsAcceptor,
+        codec: C,
+    ) -> io::Result<Self> {
+        let listener =
+            GenericTcpListenerServer::listen_with_make_service_and_tls(
+                make_service, address, acceptor, codec,
+            )?;
 
         Ok(PipelineTcpListenerServer { listener })
     }
@@ -48,17 +168,352 @@ where
 
 impl<S, C> Future for PipelineTcpListenerServer<S, C>
 where
-    S: Stream,
-    S::Item: Service<
-        Request = <C as Decoder>::Item,
-        Response = <C as Encoder>::Item,
-    >,
-    C: Clone + Decoder + Encoder,
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
 {
-    type Item = ();
-    type Error = ErrorAlias<S, S::Item, C>;
+    type Output = Result<(), ErrorAlias<S, C>>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.listener.poll_unpin(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tls")]
+    use std::io::Write;
+    #[cfg(feature = "tls")]
+    use std::net::TcpStream as StdTcpStream;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    #[cfg(feature = "tls")]
+    use std::time::Duration;
+
+    use futures::future;
+    #[cfg(feature = "tls")]
+    use futures::stream;
+
+    #[cfg(feature = "tls")]
+    use rcgen::{self, CertifiedKey};
+    #[cfg(feature = "tls")]
+    use rustls::pki_types::{
+        CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer,
+    };
+    #[cfg(feature = "tls")]
+    use rustls::server::WebPkiClientVerifier;
+    #[cfg(feature = "tls")]
+    use rustls::{ClientConfig, RootCertStore, ServerConfig};
+    #[cfg(feature = "tls")]
+    use tokio::time;
+    use tower::service_fn;
+
+    use super::*;
+    use super::super::connection_info::ConnectionInfo;
+    use super::super::pipeline_tcp_client::PipelineTcpClient;
+    #[cfg(feature = "tls")]
+    use super::super::{tls_acceptor::TlsAcceptor, tls_connector::TlsConnector};
+    #[cfg(feature = "tls")]
+    use crate::listening_server_error::ListeningServerError;
+    use crate::tests::common::{LineCodec, ToUpperService};
+
+    #[cfg(feature = "tls")]
+    type Identity = (CertificateDer<'static>, PrivateKeyDer<'static>);
+
+    #[cfg(feature = "tls")]
+    type ToUpperFactory = fn(&ConnectionInfo) -> ToUpperService;
+
+    #[cfg(feature = "tls")]
+    type TlsServer = PipelineTcpListenerServer<
+        ConnectionServices<ToUpperFactory>,
+        LineCodec,
+    >;
+
+    #[tokio::test]
+    async fn services_receive_connection_info() {
+        let address = free_address();
+
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let accepted_connections = connections.clone();
+        let factory = move |connection: &ConnectionInfo| {
+            accepted_connections.lock().unwrap().push(connection.clone());
+            ToUpperService
+        };
+
+        let server = PipelineTcpListenerServer::listen_with_factory(
+            factory, &address, LineCodec,
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        let first_client = PipelineTcpClient::connect(&address, LineCodec);
+        let first_response = first_client.call("first".to_owned()).await;
+
+        let second_client = PipelineTcpClient::connect(&address, LineCodec);
+        let second_response = second_client.call("second".to_owned()).await;
+
+        assert_eq!(first_response.unwrap(), "FIRST");
+        assert_eq!(second_response.unwrap(), "SECOND");
+
+        let connections = connections.lock().unwrap();
+
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections[0].id, 0);
+        assert_eq!(connections[1].id, 1);
+
+        for connection in connections.iter() {
+            assert_eq!(connection.local_address, address);
+            assert_eq!(connection.peer_address.ip(), address.ip());
+        }
+
+        assert_ne!(connections[0].peer_address, connections[1].peer_address);
+    }
+
+    #[tokio::test]
+    async fn make_services_receive_connection_info() {
+        let address = free_address();
+
+        let make_service = service_fn(|connection: ConnectionInfo| {
+            let id = connection.id;
+            let service = service_fn(move |request: String| {
+                future::ok::<_, ()>(format!("{}: {}", id, request))
+            });
+
+            future::ok::<_, ()>(service)
+        });
+
+        let server = PipelineTcpListenerServer::listen_with_make_service(
+            make_service, &address, LineCodec,
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        let first_client = PipelineTcpClient::connect(&address, LineCodec);
+        let first_response = first_client.call("first".to_owned()).await;
+
+        let second_client = PipelineTcpClient::connect(&address, LineCodec);
+        let second_response = second_client.call("second".to_owned()).await;
+
+        assert_eq!(first_response.unwrap(), "0: first");
+        assert_eq!(second_response.unwrap(), "1: second");
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn serves_tls_clients() {
+        let address = free_address();
+
+        let (server_certificate, server_key) = self_signed("localhost");
+        let server_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![server_certificate.clone()], server_key)
+            .unwrap();
+
+        let services = stream::iter(vec![Ok::<_, ()>(ToUpperService)]);
+        let acceptor = TlsAcceptor::new(Arc::new(server_config));
+        let server = PipelineTcpListenerServer::listen_with_tls(
+            services, &address, acceptor, LineCodec,
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        let connector = client_connector(&server_certificate, None);
+        let client =
+            PipelineTcpClient::connect_with_tls(&address, connector, LineCodec);
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn services_receive_client_certificates() {
+        let address = free_address();
+
+        let (server_certificate, server_key) = self_signed("localhost");
+        let client_identity = self_signed("client");
+        let mut trusted_clients = RootCertStore::empty();
+
+        trusted_clients.add(client_identity.0.clone()).unwrap();
+
+        let client_verifier =
+            WebPkiClientVerifier::builder(Arc::new(trusted_clients))
+                .build()
+                .unwrap();
+        let server_config = ServerConfig::builder()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(vec![server_certificate.clone()], server_key)
+            .unwrap();
+
+        let certificates = Arc::new(Mutex::new(Vec::new()));
+        let received_certificates = certificates.clone();
+        let factory = move |connection: &ConnectionInfo| {
+            received_certificates
+                .lock()
+                .unwrap()
+                .push(connection.peer_certificate.clone());
+            ToUpperService
+        };
+
+        let acceptor = TlsAcceptor::new(Arc::new(server_config));
+        let server = PipelineTcpListenerServer::listen_with_factory_and_tls(
+            factory, &address, acceptor, LineCodec,
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        let anonymous_connector = client_connector(&server_certificate, None);
+        let anonymous_client = PipelineTcpClient::connect_with_tls(
+            &address,
+            anonymous_connector,
+            LineCodec,
+        );
+
+        assert!(anonymous_client.call("rejected".to_owned()).await.is_err());
+
+        let identity =
+            (client_identity.0.clone(), client_identity.1.clone_key());
+        let connector = client_connector(&server_certificate, Some(identity));
+        let client =
+            PipelineTcpClient::connect_with_tls(&address, connector, LineCodec);
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+        assert_eq!(
+            *certificates.lock().unwrap(),
+            vec![Some(client_identity.0.to_vec())],
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn reports_failed_tls_handshakes() {
+        let address = free_address();
+        let (server_certificate, server) = tls_server(&address, None);
+        let handshake_errors = report_transport_errors(server);
+
+        let mut plain_client = StdTcpStream::connect(address).unwrap();
+
+        plain_client.write_all(b"plain text\n").unwrap();
+
+        let connector = client_connector(&server_certificate, None);
+        let client =
+            PipelineTcpClient::connect_with_tls(&address, connector, LineCodec);
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+        assert_eq!(
+            *handshake_errors.lock().unwrap(),
+            vec![io::ErrorKind::InvalidData],
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn tls_handshakes_time_out() {
+        let address = free_address();
+        let timeout = Duration::from_millis(50);
+        let (server_certificate, server) = tls_server(&address, Some(timeout));
+        let handshake_errors = report_transport_errors(server);
+
+        let _silent_client = StdTcpStream::connect(address).unwrap();
+
+        let connector = client_connector(&server_certificate, None);
+        let client =
+            PipelineTcpClient::connect_with_tls(&address, connector, LineCodec);
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+        assert!(handshake_errors.lock().unwrap().is_empty());
+
+        time::sleep(timeout * 4).await;
+
+        assert_eq!(
+            *handshake_errors.lock().unwrap(),
+            vec![io::ErrorKind::TimedOut],
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    fn tls_server(
+        address: &SocketAddr,
+        handshake_timeout: Option<Duration>,
+    ) -> (CertificateDer<'static>, TlsServer) {
+        let (server_certificate, server_key) = self_signed("localhost");
+        let server_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![server_certificate.clone()], server_key)
+            .unwrap();
+
+        let mut acceptor = TlsAcceptor::new(Arc::new(server_config));
+
+        if let Some(timeout) = handshake_timeout {
+            acceptor.set_handshake_timeout(timeout);
+        }
+
+        let factory: ToUpperFactory = |_| ToUpperService;
+        let server = PipelineTcpListenerServer::listen_with_factory_and_tls(
+            factory, address, acceptor, LineCodec,
+        ).unwrap();
+
+        (server_certificate, server)
+    }
+
+    /// Runs the `server`, collecting the kinds of its transport errors.
+    #[cfg(feature = "tls")]
+    fn report_transport_errors(
+        mut server: TlsServer,
+    ) -> Arc<Mutex<Vec<io::ErrorKind>>> {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let reported_errors = errors.clone();
+
+        server.on_connection_error(move |error| {
+            if let ListeningServerError::TransportError(error) = error {
+                reported_errors.lock().unwrap().push(error.kind());
+            }
+        });
+
+        tokio::spawn(server);
+
+        errors
+    }
+
+    #[cfg(feature = "tls")]
+    fn self_signed(name: &str) -> Identity {
+        let CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec![name.to_owned()]).unwrap();
+        let key = PrivatePkcs8KeyDer::from(key_pair.serialize_der());
+
+        (cert.der().clone(), PrivateKeyDer::Pkcs8(key))
+    }
+
+    #[cfg(feature = "tls")]
+    fn client_connector(
+        server_certificate: &CertificateDer<'static>,
+        identity: Option<Identity>,
+    ) -> TlsConnector {
+        let mut root_store = RootCertStore::empty();
+
+        root_store.add(server_certificate.clone()).unwrap();
+
+        let builder =
+            ClientConfig::builder().with_root_certificates(root_store);
+        let config = match identity {
+            Some((certificate, key)) => builder
+                .with_client_auth_cert(vec![certificate], key)
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+
+        TlsConnector::new(Arc::new(config), "localhost").unwrap()
+    }
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.listener.poll()
+    fn free_address() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
     }
 }
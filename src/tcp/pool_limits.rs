@@ -0,0 +1,72 @@
+use std::num::NonZeroUsize;
+
+/// How many connections a `PooledClient` keeps.
+///
+/// Connections are only opened when a call finds none of them free, up to
+/// `max_connections`. A connection is idle while it has no calls in flight,
+/// and the pool opens new connections ahead of time so that `min_idle` of
+/// them are ready, and closes idle connections beyond `max_idle`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PoolLimits {
+    max_connections: NonZeroUsize,
+    min_idle: usize,
+    max_idle: usize,
+}
+
+impl PoolLimits {
+    /// Returns `None` unless `min_idle <= max_idle <= max_connections`, so
+    /// that the pool never closes idle connections only to open new ones.
+    pub fn new(
+        max_connections: NonZeroUsize,
+        min_idle: usize,
+        max_idle: usize,
+    ) -> Option<Self> {
+        if min_idle <= max_idle && max_idle <= max_connections.get() {
+            Some(PoolLimits {
+                max_connections,
+                min_idle,
+                max_idle,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn max_connections(&self) -> NonZeroUsize {
+        self.max_connections
+    }
+
+    pub fn min_idle(&self) -> usize {
+        self.min_idle
+    }
+
+    pub fn max_idle(&self) -> usize {
+        self.max_idle
+    }
+}
+
+impl Default for PoolLimits {
+    fn default() -> Self {
+        PoolLimits {
+            max_connections: NonZeroUsize::new(8)
+                .expect("default maximum connection count is zero"),
+            min_idle: 0,
+            max_idle: 8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_idle_limits_that_churn_connections() {
+        let max_connections = NonZeroUsize::new(4).unwrap();
+
+        assert!(PoolLimits::new(max_connections, 0, 4).is_some());
+        assert!(PoolLimits::new(max_connections, 2, 2).is_some());
+        assert!(PoolLimits::new(max_connections, 3, 2).is_none());
+        assert!(PoolLimits::new(max_connections, 0, 5).is_none());
+    }
+}
@@ -0,0 +1,87 @@
+use std::hash::Hash;
+use std::task::{Context, Poll};
+
+use futures::TryFuture;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::super::client_error::ClientError;
+use super::super::message_with_id::MessageWithId;
+use super::multiplex_tcp_client::{MultiplexTcpClient, MultiplexTcpClientFuture};
+use super::pipeline_tcp_client::{PipelineTcpClient, PipelineTcpClientFuture};
+
+/// A client with a single connection, which can be shared by the calls of a
+/// `PooledClient` or a `BalancingClient`.
+pub trait PoolableClient<R> {
+    type Future: TryFuture;
+
+    fn call(&self, request: R) -> Self::Future;
+
+    /// Waits until the connection is established and can accept another
+    /// request.
+    fn poll_ready(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), <Self::Future as TryFuture>::Error>>;
+
+    /// Whether the connection can no longer be used, so that the pool should
+    /// replace it.
+    fn is_broken(&self) -> bool;
+
+    /// The error for calls that have no connection to be sent through.
+    fn not_connected_error() -> <Self::Future as TryFuture>::Error;
+}
+
+impl<C, R> PoolableClient<R> for PipelineTcpClient<C>
+where
+    C: Decoder + Encoder<R> + Unpin,
+{
+    type Future = PipelineTcpClientFuture<C, R>;
+
+    fn call(&self, request: R) -> Self::Future {
+        PipelineTcpClient::call(self, request)
+    }
+
+    fn poll_ready(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), <Self::Future as TryFuture>::Error>> {
+        PipelineTcpClient::poll_ready(self, context)
+    }
+
+    fn is_broken(&self) -> bool {
+        self.is_connection_lost()
+    }
+
+    fn not_connected_error() -> <Self::Future as TryFuture>::Error {
+        ClientError::NotConnected
+    }
+}
+
+impl<C, R> PoolableClient<R> for MultiplexTcpClient<C>
+where
+    C: Decoder + Encoder<R> + Unpin,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+    R: MessageWithId<Id = <C::Item as MessageWithId>::Id>,
+{
+    type Future = MultiplexTcpClientFuture<C, R>;
+
+    fn call(&self, request: R) -> Self::Future {
+        MultiplexTcpClient::call(self, request)
+    }
+
+    fn poll_ready(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), <Self::Future as TryFuture>::Error>> {
+        MultiplexTcpClient::poll_ready(self, context)
+    }
+
+    fn is_broken(&self) -> bool {
+        self.is_connection_lost()
+    }
+
+    fn not_connected_error() -> <Self::Future as TryFuture>::Error {
+        ClientError::NotConnected
+    }
+}
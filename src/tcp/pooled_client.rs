@@ -0,0 +1,489 @@
+use std::future::Future;
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use futures::TryFuture;
+use tokio_util::codec::Decoder;
+use tower_service::Service;
+
+use super::super::message_with_id::MessageWithId;
+use super::multiplex_tcp_client::MultiplexTcpClient;
+use super::pipeline_tcp_client::PipelineTcpClient;
+use super::pool_limits::PoolLimits;
+use super::poolable_client::PoolableClient;
+
+type Connect<K> = Box<dyn Fn() -> K + Send + Sync>;
+
+struct PooledConnection<K> {
+    client: K,
+    calls_in_flight: Arc<AtomicUsize>,
+}
+
+impl<K> PooledConnection<K> {
+    fn calls_in_flight(&self) -> usize {
+        self.calls_in_flight.load(Ordering::Acquire)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.calls_in_flight() == 0
+    }
+}
+
+/// Spreads calls over several connections to the same server.
+///
+/// Each call is sent through an idle connection if there is one, otherwise
+/// through a new connection, or through the connection with the fewest calls
+/// in flight once the `PoolLimits` allow no more connections. Lost
+/// connections are replaced, and the limits on idle connections are applied
+/// whenever the pool is polled for readiness or a call is made.
+///
+/// Idle connections are only established ahead of calls while the pool is
+/// polled for readiness, which is what a `Service` caller does before each
+/// call.
+pub struct PooledClient<K> {
+    connections: Mutex<Vec<PooledConnection<K>>>,
+    connect: Connect<K>,
+    limits: PoolLimits,
+}
+
+impl<K> PooledClient<K> {
+    /// Creates a pool of the clients returned by `connect`.
+    ///
+    /// No connection is opened until the pool is first polled for readiness
+    /// or a call is made.
+    pub fn new<F>(connect: F, limits: PoolLimits) -> Self
+    where
+        F: Fn() -> K + Send + Sync + 'static,
+    {
+        PooledClient {
+            connections: Mutex::new(Vec::new()),
+            connect: Box::new(connect),
+            limits,
+        }
+    }
+
+    pub fn connections(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn idle_connections(&self) -> usize {
+        self.lock()
+            .iter()
+            .filter(|connection| connection.is_idle())
+            .count()
+    }
+
+    pub fn call<R>(&self, request: R) -> PooledClientFuture<K::Future>
+    where
+        K: PoolableClient<R>,
+    {
+        let mut connections = self.lock();
+
+        connections.retain(|connection| !connection.client.is_broken());
+
+        let index = self.choose_connection(&mut connections);
+        let connection = &connections[index];
+        let response = PooledClientFuture::new(
+            connection.client.call(request),
+            connection.calls_in_flight.clone(),
+        );
+
+        self.apply_idle_limits(&mut connections);
+
+        response
+    }
+
+    /// Waits until the connection that the next call would be sent through
+    /// can accept it.
+    ///
+    /// The idle connections are polled as well, so that they are established
+    /// before any call needs them.
+    pub fn poll_ready<R>(
+        &self,
+        context: &mut Context,
+    ) -> Poll<Result<(), <K::Future as TryFuture>::Error>>
+    where
+        K: PoolableClient<R>,
+    {
+        let mut connections = self.lock();
+
+        connections.retain(|connection| !connection.client.is_broken());
+
+        self.apply_idle_limits(&mut connections);
+
+        // Connections that fail are replaced the next time the pool is used.
+        for connection in connections.iter().filter(|c| c.is_idle()) {
+            let _ = connection.client.poll_ready(context);
+        }
+
+        let index = self.choose_connection(&mut connections);
+
+        connections[index].client.poll_ready(context)
+    }
+
+    fn choose_connection(
+        &self,
+        connections: &mut Vec<PooledConnection<K>>,
+    ) -> usize {
+        if let Some(index) = connections.iter().position(|c| c.is_idle()) {
+            return index;
+        }
+
+        if connections.len() < self.limits.max_connections().get() {
+            connections.push(self.open());
+
+            return connections.len() - 1;
+        }
+
+        connections
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, connection)| connection.calls_in_flight())
+            .map(|(index, _)| index)
+            .expect("a full pool has no connections")
+    }
+
+    fn apply_idle_limits(&self, connections: &mut Vec<PooledConnection<K>>) {
+        let limits = self.limits;
+        let mut idle_connections = connections
+            .iter()
+            .filter(|connection| connection.is_idle())
+            .count();
+
+        // Surplus connections are closed by dropping their clients.
+        connections.retain(|connection| {
+            let is_surplus = idle_connections > limits.max_idle()
+                && connection.is_idle();
+
+            if is_surplus {
+                idle_connections -= 1;
+            }
+
+            !is_surplus
+        });
+
+        while idle_connections < limits.min_idle()
+            && connections.len() < limits.max_connections().get()
+        {
+            connections.push(self.open());
+            idle_connections += 1;
+        }
+    }
+
+    fn open(&self) -> PooledConnection<K> {
+        PooledConnection {
+            client: (self.connect)(),
+            calls_in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<PooledConnection<K>>> {
+        self.connections
+            .lock()
+            .expect("a thread panicked while holding PooledClient locked")
+    }
+}
+
+impl<C> PooledClient<PipelineTcpClient<C>>
+where
+    C: Clone + Decoder + Send + Sync + Unpin + 'static,
+{
+    /// Creates a pool of pipelined connections to the server at `address`.
+    pub fn connect(address: &SocketAddr, codec: C, limits: PoolLimits) -> Self {
+        let address = *address;
+
+        PooledClient::new(
+            move || PipelineTcpClient::connect(&address, codec.clone()),
+            limits,
+        )
+    }
+}
+
+impl<C> PooledClient<MultiplexTcpClient<C>>
+where
+    C: Clone + Decoder + Send + Sync + Unpin + 'static,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    /// Creates a pool of multiplexed connections to the server at `address`.
+    pub fn connect(address: &SocketAddr, codec: C, limits: PoolLimits) -> Self {
+        let address = *address;
+
+        PooledClient::new(
+            move || MultiplexTcpClient::connect(&address, codec.clone()),
+            limits,
+        )
+    }
+}
+
+impl<K, R> Service<R> for PooledClient<K>
+where
+    K: PoolableClient<R>,
+{
+    type Response = <K::Future as TryFuture>::Ok;
+    type Error = <K::Future as TryFuture>::Error;
+    type Future = PooledClientFuture<K::Future>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        PooledClient::poll_ready(self, context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        PooledClient::call(self, request)
+    }
+}
+
+/// The response to a call made through a `PooledClient` or a
+/// `BalancingClient`.
+///
+/// The call counts as in flight on its connection until the response is
+/// received or the future is dropped.
+pub struct PooledClientFuture<F> {
+    response: Pin<Box<F>>,
+    calls_in_flight: Option<Arc<AtomicUsize>>,
+}
+
+impl<F> PooledClientFuture<F> {
+    pub fn new(response: F, calls_in_flight: Arc<AtomicUsize>) -> Self {
+        calls_in_flight.fetch_add(1, Ordering::AcqRel);
+
+        PooledClientFuture {
+            response: Box::pin(response),
+            calls_in_flight: Some(calls_in_flight),
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(calls_in_flight) = self.calls_in_flight.take() {
+            calls_in_flight.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl<F> Future for PooledClientFuture<F>
+where
+    F: TryFuture,
+{
+    type Output = Result<F::Ok, F::Error>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let result = ready!(self.response.as_mut().try_poll(context));
+
+        self.finish();
+
+        Poll::Ready(result)
+    }
+}
+
+impl<F> Drop for PooledClientFuture<F> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::num::NonZeroUsize;
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::future;
+    use tokio::time;
+    use tower::ServiceExt;
+
+    use super::*;
+    use super::super::connection_info::ConnectionInfo;
+    use super::super::pipeline_tcp_listener_server::PipelineTcpListenerServer;
+    use crate::client_error::ClientError;
+    use crate::tests::common::{LineCodec, ToUpperService};
+
+    #[tokio::test]
+    async fn reuses_idle_connections() {
+        let (address, accepted_connections) = start_server();
+        let client = PooledClient::<PipelineTcpClient<_>>::connect(
+            &address,
+            LineCodec,
+            PoolLimits::default(),
+        );
+
+        for request in &["first", "second", "third"] {
+            let response = client.call(request.to_string()).await;
+
+            assert_eq!(response.unwrap(), request.to_uppercase());
+        }
+
+        assert_eq!(client.connections(), 1);
+        assert_eq!(accepted_connections.load(Ordering::Acquire), 1);
+    }
+
+    #[tokio::test]
+    async fn spreads_concurrent_calls_over_connections() {
+        let (address, accepted_connections) = start_server();
+        let client = PooledClient::<PipelineTcpClient<_>>::connect(
+            &address,
+            LineCodec,
+            pool_limits(2, 0, 2),
+        );
+
+        let responses = future::try_join3(
+            client.call("first".to_owned()),
+            client.call("second".to_owned()),
+            client.call("third".to_owned()),
+        );
+
+        assert_eq!(
+            responses.await.unwrap(),
+            ("FIRST".to_owned(), "SECOND".to_owned(), "THIRD".to_owned())
+        );
+        assert_eq!(client.connections(), 2);
+        assert_eq!(accepted_connections.load(Ordering::Acquire), 2);
+    }
+
+    #[tokio::test]
+    async fn keeps_idle_connections_within_limits() {
+        let (address, _) = start_server();
+        let client = PooledClient::<PipelineTcpClient<_>>::connect(
+            &address,
+            LineCodec,
+            pool_limits(4, 1, 1),
+        );
+
+        let first_response = client.call("first".to_owned());
+
+        assert_eq!(client.connections(), 2);
+
+        let second_response = client.call("second".to_owned());
+
+        assert_eq!(client.connections(), 3);
+        assert_eq!(client.idle_connections(), 1);
+
+        let responses = future::try_join(first_response, second_response);
+
+        assert_eq!(
+            responses.await.unwrap(),
+            ("FIRST".to_owned(), "SECOND".to_owned())
+        );
+        assert_eq!(client.idle_connections(), 3);
+
+        let third_response = client.call("third".to_owned());
+
+        assert_eq!(client.connections(), 2);
+        assert_eq!(client.idle_connections(), 1);
+        assert_eq!(third_response.await.unwrap(), "THIRD");
+    }
+
+    #[tokio::test]
+    async fn opens_idle_connections_before_any_call() {
+        let (address, accepted_connections) = start_server();
+        let mut client = PooledClient::<PipelineTcpClient<_>>::connect(
+            &address,
+            LineCodec,
+            pool_limits(4, 2, 2),
+        );
+
+        ServiceExt::<String>::ready(&mut client).await.unwrap();
+
+        let all_accepted = async {
+            while accepted_connections.load(Ordering::Acquire) < 2 {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+
+        time::timeout(Duration::from_secs(5), all_accepted)
+            .await
+            .expect("idle connections were not opened");
+
+        assert_eq!(client.connections(), 2);
+        assert_eq!(client.idle_connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn replaces_lost_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            drop_first_connection_then_echo(listener);
+        });
+
+        let client = PooledClient::<PipelineTcpClient<_>>::connect(
+            &address,
+            LineCodec,
+            pool_limits(1, 0, 1),
+        );
+
+        match client.call("lost request".to_owned()).await {
+            Err(ClientError::ConnectionLost) => {}
+            _ => panic!("request did not fail when the connection was lost"),
+        }
+
+        let response = client.call("request".to_owned()).await;
+
+        assert_eq!(response.unwrap(), "REQUEST");
+        assert_eq!(client.connections(), 1);
+
+        drop(client);
+        server.join().unwrap();
+    }
+
+    fn pool_limits(
+        max_connections: usize,
+        min_idle: usize,
+        max_idle: usize,
+    ) -> PoolLimits {
+        let max_connections = NonZeroUsize::new(max_connections).unwrap();
+
+        PoolLimits::new(max_connections, min_idle, max_idle).unwrap()
+    }
+
+    fn start_server() -> (SocketAddr, Arc<AtomicUsize>) {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted_connections = connections.clone();
+        let factory = move |_: &ConnectionInfo| {
+            accepted_connections.fetch_add(1, Ordering::AcqRel);
+            ToUpperService
+        };
+
+        let server = PipelineTcpListenerServer::listen_with_factory(
+            factory, &address, LineCodec,
+        ).unwrap();
+
+        tokio::spawn(server);
+
+        (address, connections)
+    }
+
+    fn drop_first_connection_then_echo(listener: TcpListener) {
+        let (first_connection, _) = listener.accept().unwrap();
+        let mut first_request = String::new();
+
+        BufReader::new(first_connection)
+            .read_line(&mut first_request)
+            .unwrap();
+
+        let (mut connection, _) = listener.accept().unwrap();
+        let reader = BufReader::new(connection.try_clone().unwrap());
+
+        for line in reader.lines() {
+            let response = line.unwrap().to_uppercase();
+
+            writeln!(connection, "{}", response).unwrap();
+        }
+    }
+}
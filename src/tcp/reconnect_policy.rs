@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use super::super::backoff::exponential_backoff;
+
+/// What happens to new requests while a TCP client is reconnecting.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WhileReconnecting {
+    /// Requests wait until the connection is established again.
+    QueueRequests,
+
+    /// Requests fail immediately with `ClientError::NotConnected`.
+    FailFast,
+}
+
+/// How a TCP client reconnects after its connection is lost.
+///
+/// Connection attempts are retried with an exponential backoff: the delay
+/// before each retry starts at `initial_delay` and doubles up to `max_delay`.
+/// Each delay is then randomly shortened by up to the `jitter` fraction, so
+/// that many clients don't all reconnect at once. Once `max_attempts`
+/// attempts in a row have failed, the client gives up and its connection is
+/// lost for good.
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: f64,
+    pub max_attempts: Option<u32>,
+    pub while_reconnecting: WhileReconnecting,
+}
+
+impl ReconnectPolicy {
+    /// The delay to wait before the given connection attempt.
+    ///
+    /// The first attempt is made immediately.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        exponential_backoff(
+            self.initial_delay,
+            self.max_delay,
+            self.jitter,
+            attempt,
+        )
+    }
+
+    pub fn has_attempts_left(&self, attempts: u32) -> bool {
+        self.max_attempts
+            .map(|max_attempts| attempts < max_attempts)
+            .unwrap_or(true)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.5,
+            max_attempts: None,
+            while_reconnecting: WhileReconnecting::QueueRequests,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_grow_exponentially_up_to_the_maximum() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            jitter: 0.0,
+            ..ReconnectPolicy::default()
+        };
+
+        let delays: Vec<_> =
+            (0..7).map(|attempt| policy.delay(attempt)).collect();
+
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(0),
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(400),
+                Duration::from_millis(800),
+                Duration::from_millis(1000),
+                Duration::from_millis(1000),
+            ]
+        );
+        assert_eq!(policy.delay(100), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn jitter_only_shortens_delays() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(100),
+            jitter: 0.5,
+            ..ReconnectPolicy::default()
+        };
+
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+
+            assert!(delay <= Duration::from_millis(100));
+            assert!(delay >= Duration::from_millis(50));
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use super::connection_info::ConnectionInfo;
+
+/// Creates the service that handles the requests of each new connection.
+///
+/// It is implemented for closures that receive a `&ConnectionInfo` and
+/// return a service.
+pub trait ServiceFactory {
+    type Service;
+
+    fn new_service(&self, connection: &ConnectionInfo) -> Self::Service;
+}
+
+impl<F, S> ServiceFactory for F
+where
+    F: Fn(&ConnectionInfo) -> S,
+{
+    type Service = S;
+
+    fn new_service(&self, connection: &ConnectionInfo) -> Self::Service {
+        self(connection)
+    }
+}
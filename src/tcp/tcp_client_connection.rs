@@ -0,0 +1,162 @@
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+
+use tokio_util::codec::Decoder;
+
+use super::super::client_error::ClientError;
+use super::connection_status::ConnectionStatus;
+use super::reconnect_policy::{ReconnectPolicy, WhileReconnecting};
+use super::tcp_client_transport::TcpClientTransport;
+
+type Reconnect<C> = Box<dyn Fn() -> TcpClientTransport<C> + Send + Sync>;
+
+/// Why a `TcpClientConnection` has no client to perform a call with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Unavailable {
+    /// The client fails fast and is reconnecting.
+    Reconnecting,
+
+    /// The client ran out of reconnection attempts.
+    Lost,
+}
+
+impl Unavailable {
+    pub fn into_client_error<I, O>(self) -> ClientError<I, O> {
+        match self {
+            Unavailable::Reconnecting => ClientError::NotConnected,
+            Unavailable::Lost => ClientError::ConnectionLost,
+        }
+    }
+}
+
+struct CurrentConnection<K> {
+    client: K,
+    status: ConnectionStatus,
+    is_reconnection: bool,
+}
+
+/// The client used by a TCP client, which is replaced by a client with a new
+/// connection when reconnection is enabled and the old connection is lost.
+pub struct TcpClientConnection<K, C>
+where
+    C: Decoder,
+{
+    current: Mutex<CurrentConnection<K>>,
+    new_client: fn(TcpClientTransport<C>) -> K,
+    reconnect: Option<Reconnect<C>>,
+    while_reconnecting: WhileReconnecting,
+}
+
+impl<K, C> TcpClientConnection<K, C>
+where
+    C: Decoder,
+{
+    pub fn new(
+        transport: TcpClientTransport<C>,
+        new_client: fn(TcpClientTransport<C>) -> K,
+    ) -> Self {
+        TcpClientConnection {
+            current: Mutex::new(Self::start(transport, new_client, false)),
+            new_client,
+            reconnect: None,
+            while_reconnecting: WhileReconnecting::QueueRequests,
+        }
+    }
+
+    pub fn reconnecting(
+        address: &SocketAddr,
+        codec: C,
+        policy: ReconnectPolicy,
+        new_client: fn(TcpClientTransport<C>) -> K,
+    ) -> Self
+    where
+        C: Clone + Send + Sync + 'static,
+    {
+        let address = *address;
+        let while_reconnecting = policy.while_reconnecting;
+        let reconnect = move || {
+            TcpClientTransport::connect_with_policy(
+                &address,
+                codec.clone(),
+                &policy,
+            )
+        };
+        let transport = reconnect();
+
+        TcpClientConnection {
+            current: Mutex::new(Self::start(transport, new_client, false)),
+            new_client,
+            reconnect: Some(Box::new(reconnect)),
+            while_reconnecting,
+        }
+    }
+
+    /// Uses the current client to perform a call, reconnecting first if the
+    /// connection was lost.
+    ///
+    /// Fails if the client is reconnecting and fails fast, or if it ran out
+    /// of reconnection attempts.
+    pub fn call<F, R>(&self, call: F) -> Result<R, Unavailable>
+    where
+        F: FnOnce(&K) -> R,
+    {
+        let mut current = self.lock();
+
+        if let Some(ref reconnect) = self.reconnect {
+            if current.status.is_lost() {
+                return Err(Unavailable::Lost);
+            }
+
+            if current.status.is_disconnected() {
+                *current = Self::start(reconnect(), self.new_client, true);
+            }
+
+            let fails_fast =
+                self.while_reconnecting == WhileReconnecting::FailFast;
+
+            if fails_fast
+                && current.is_reconnection
+                && !current.status.is_connected()
+            {
+                return Err(Unavailable::Reconnecting);
+            }
+        }
+
+        Ok(call(&current.client))
+    }
+
+    /// Whether the connection was lost and won't be reestablished.
+    pub fn is_lost(&self) -> bool {
+        let status = &self.lock().status;
+
+        match self.reconnect {
+            Some(_) => status.is_lost(),
+            None => status.is_disconnected(),
+        }
+    }
+
+    pub fn with_client<F, R>(&self, function: F) -> R
+    where
+        F: FnOnce(&K) -> R,
+    {
+        function(&self.lock().client)
+    }
+
+    fn start(
+        transport: TcpClientTransport<C>,
+        new_client: fn(TcpClientTransport<C>) -> K,
+        is_reconnection: bool,
+    ) -> CurrentConnection<K> {
+        CurrentConnection {
+            status: transport.status(),
+            client: new_client(transport),
+            is_reconnection,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CurrentConnection<K>> {
+        self.current.lock().expect(
+            "a thread panicked while holding the TcpClientConnection locked",
+        )
+    }
+}
@@ -1,131 +1,323 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::{io, mem, net::SocketAddr};
 
-use futures::{Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
-use tokio_core::{net::{TcpStream, TcpStreamNew}, reactor::Handle};
-use tokio_io::{AsyncRead, codec::{Decoder, Encoder, Framed}};
+use futures::channel::oneshot;
+use futures::{FutureExt, Sink, Stream, StreamExt, TryFutureExt};
+use tokio::net::TcpStream;
+use tokio::time;
+use tokio_util::codec::{Decoder, Encoder, Framed};
 
-pub enum TcpClientTransport<C>
+use super::connection_status::ConnectionStatus;
+use super::reconnect_policy::ReconnectPolicy;
+use super::tcp_connection::TcpConnection;
+#[cfg(feature = "tls")]
+use super::tls_connector::TlsConnector;
+
+type Connecting =
+    Pin<Box<dyn Future<Output = io::Result<TcpConnection>> + Send>>;
+
+pub struct TcpClientTransport<C>
 where
-    C: Decoder + Encoder,
+    C: Decoder,
 {
-    Connecting(TcpStreamNew, C),
-    Connected(Framed<TcpStream, C>),
+    state: State<C>,
+    status: ConnectionStatus,
+}
+
+enum State<C>
+where
+    C: Decoder,
+{
+    Connecting(Connecting, C),
+    Connected(Framed<TcpConnection, C>),
+    Disconnected,
     Polling,
 }
 
 impl<C> TcpClientTransport<C>
 where
-    C: Decoder + Encoder,
+    C: Decoder,
 {
-    pub fn connect(address: &SocketAddr, codec: C, handle: &Handle) -> Self {
-        TcpClientTransport::Connecting(
-            TcpStream::connect(address, handle),
-            codec,
-        )
+    pub fn connect(address: &SocketAddr, codec: C) -> Self {
+        let connecting =
+            TcpStream::connect(*address).map_ok(TcpConnection::from);
+
+        Self::with_state(State::Connecting(Box::pin(connecting), codec))
+    }
+
+    /// Connects to the server and secures the connection with TLS.
+    #[cfg(feature = "tls")]
+    pub fn connect_with_tls(
+        address: &SocketAddr,
+        connector: TlsConnector,
+        codec: C,
+    ) -> Self {
+        let connecting = TcpStream::connect(*address)
+            .and_then(move |socket| connector.connect(socket))
+            .map_ok(TcpConnection::from);
+
+        Self::with_state(State::Connecting(Box::pin(connecting), codec))
+    }
+
+    /// Connects in the background, retrying failed attempts as configured by
+    /// the `policy`.
+    ///
+    /// The connection attempts are spawned on the current Tokio runtime, so
+    /// this panics when called outside of one. Once the attempts run out, the
+    /// connection is marked as lost.
+    pub fn connect_with_policy(
+        address: &SocketAddr,
+        codec: C,
+        policy: &ReconnectPolicy,
+    ) -> Self {
+        let (sender, receiver) = oneshot::channel();
+        let address = *address;
+        let policy = policy.clone();
+        let status = ConnectionStatus::new();
+        let background_status = status.clone();
+
+        tokio::spawn(async move {
+            let result = connect_with_backoff(address, policy).await;
+
+            // Update the status right away, because the transport might not
+            // be polled until requests are sent.
+            match result {
+                Ok(_) => background_status.set_connected(),
+                Err(_) => background_status.set_lost(),
+            }
+
+            let _ = sender.send(result);
+        });
+
+        let connecting = receiver.map(|result| match result {
+            Ok(connection_result) => connection_result.map(TcpConnection::from),
+            Err(_) => Err(io::Error::other("connection attempt was cancelled")),
+        });
+
+        TcpClientTransport {
+            state: State::Connecting(Box::pin(connecting), codec),
+            status,
+        }
     }
 
     pub fn with_connection(connection: TcpStream, codec: C) -> Self {
-        TcpClientTransport::Connected(connection.framed(codec))
+        let framed_connection =
+            Framed::new(TcpConnection::from(connection), codec);
+        let transport = Self::with_state(State::Connected(framed_connection));
+
+        transport.status.set_connected();
+        transport
     }
 
-    fn is_connecting(&self) -> bool {
-        match *self {
-            TcpClientTransport::Connecting(..) => true,
-            _ => false,
+    pub fn status(&self) -> ConnectionStatus {
+        self.status.clone()
+    }
+
+    fn with_state(state: State<C>) -> Self {
+        TcpClientTransport {
+            state,
+            status: ConnectionStatus::new(),
         }
     }
 
-    fn wait_for_connection(&mut self) -> Poll<(), io::Error> {
-        use self::TcpClientTransport::*;
+    fn is_connecting(&self) -> bool {
+        matches!(self.state, State::Connecting(..))
+    }
 
+    fn wait_for_connection(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<io::Result<()>> {
         if self.is_connecting() {
-            let old_state = mem::replace(self, TcpClientTransport::Polling);
+            let old_state = mem::replace(&mut self.state, State::Polling);
 
-            if let Connecting(mut connecting, codec) = old_state {
-                match connecting.poll() {
-                    Ok(Async::Ready(connected)) => {
-                        let transport = connected.framed(codec);
+            if let State::Connecting(mut connecting, codec) = old_state {
+                match connecting.as_mut().poll(context) {
+                    Poll::Ready(Ok(connected)) => {
+                        let transport = Framed::new(connected, codec);
 
-                        mem::replace(self, Connected(transport));
+                        self.state = State::Connected(transport);
+                        self.status.set_connected();
 
-                        Ok(Async::Ready(()))
+                        Poll::Ready(Ok(()))
                     }
-                    Ok(Async::NotReady) => {
-                        mem::replace(self, Connecting(connecting, codec));
+                    Poll::Pending => {
+                        self.state = State::Connecting(connecting, codec);
 
-                        Ok(Async::NotReady)
+                        Poll::Pending
                     }
-                    Err(error) => {
-                        mem::replace(self, Connecting(connecting, codec));
+                    Poll::Ready(Err(error)) => {
+                        self.disconnect();
 
-                        Err(error)
+                        Poll::Ready(Err(error))
                     }
                 }
             } else {
                 unreachable!("State was tested to be Connecting");
             }
         } else {
-            Ok(Async::Ready(()))
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.state = State::Disconnected;
+        self.status.set_disconnected();
+    }
+
+    fn check_result<T, E>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        if result.is_err() {
+            self.disconnect();
         }
+
+        result
     }
 }
 
 impl<C> Stream for TcpClientTransport<C>
 where
-    C: Decoder + Encoder,
+    C: Decoder + Unpin,
 {
-    type Item = <C as Decoder>::Item;
-    type Error = <C as Decoder>::Error;
+    type Item = Result<C::Item, C::Error>;
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        try_ready!(self.wait_for_connection());
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        ready!(self.wait_for_connection(context))?;
 
-        if let TcpClientTransport::Connected(ref mut transport) = *self {
-            transport.poll()
-        } else {
-            unreachable!(
+        let result = match self.state {
+            State::Connected(ref mut transport) => {
+                ready!(transport.poll_next_unpin(context))
+            }
+            State::Disconnected => return Poll::Ready(None),
+            _ => unreachable!(
                 "Function wait_for_connection left TcpClientTransport in \
                  invalid state"
-            );
+            ),
+        };
+
+        match result {
+            Some(Ok(message)) => Poll::Ready(Some(Ok(message))),
+            Some(Err(error)) => {
+                self.disconnect();
+                Poll::Ready(Some(Err(error)))
+            }
+            None => {
+                self.disconnect();
+                Poll::Ready(None)
+            }
         }
     }
 }
 
-impl<C> Sink for TcpClientTransport<C>
+impl<C, I> Sink<I> for TcpClientTransport<C>
 where
-    C: Decoder + Encoder,
+    C: Decoder + Encoder<I> + Unpin,
 {
-    type SinkItem = <C as Encoder>::Item;
-    type SinkError = <C as Encoder>::Error;
+    type Error = <C as Encoder<I>>::Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        ready!(self.wait_for_connection(context))?;
+
+        let result = match self.state {
+            State::Connected(ref mut transport) => {
+                ready!(Pin::new(transport).poll_ready(context))
+            }
+            State::Disconnected => {
+                return Poll::Ready(Err(connection_lost().into()))
+            }
+            _ => unreachable!(
+                "Function wait_for_connection left TcpClientTransport in \
+                 invalid state"
+            ),
+        };
+
+        Poll::Ready(self.check_result(result))
+    }
 
     fn start_send(
-        &mut self,
-        item: Self::SinkItem,
-    ) -> StartSend<Self::SinkItem, Self::SinkError> {
-        if let Async::NotReady = self.wait_for_connection()? {
-            return Ok(AsyncSink::NotReady(item));
-        }
+        mut self: Pin<&mut Self>,
+        item: I,
+    ) -> Result<(), Self::Error> {
+        let result = match self.state {
+            State::Connected(ref mut transport) => {
+                Pin::new(transport).start_send(item)
+            }
+            State::Disconnected => return Err(connection_lost().into()),
+            _ => panic!(
+                "TcpClientTransport received an item before it was ready"
+            ),
+        };
 
-        if let TcpClientTransport::Connected(ref mut transport) = *self {
-            transport.start_send(item)
-        } else {
-            unreachable!(
+        self.check_result(result)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        ready!(self.wait_for_connection(context))?;
+
+        let result = match self.state {
+            State::Connected(ref mut transport) => {
+                ready!(Pin::new(transport).poll_flush(context))
+            }
+            State::Disconnected => {
+                return Poll::Ready(Err(connection_lost().into()))
+            }
+            _ => unreachable!(
                 "Function wait_for_connection left TcpClientTransport in \
                  invalid state"
-            );
-        }
+            ),
+        };
+
+        Poll::Ready(self.check_result(result))
     }
 
-    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
-        try_ready!(self.wait_for_connection());
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        ready!(self.wait_for_connection(context))?;
 
-        if let TcpClientTransport::Connected(ref mut transport) = *self {
-            transport.poll_complete()
-        } else {
-            unreachable!(
+        let result = match self.state {
+            State::Connected(ref mut transport) => {
+                ready!(Pin::new(transport).poll_close(context))
+            }
+            State::Disconnected => return Poll::Ready(Ok(())),
+            _ => unreachable!(
                 "Function wait_for_connection left TcpClientTransport in \
                  invalid state"
-            );
+            ),
+        };
+
+        Poll::Ready(self.check_result(result))
+    }
+}
+
+async fn connect_with_backoff(
+    address: SocketAddr,
+    policy: ReconnectPolicy,
+) -> io::Result<TcpStream> {
+    let mut attempt = 0;
+
+    loop {
+        time::sleep(policy.delay(attempt)).await;
+
+        match TcpStream::connect(address).await {
+            Ok(connection) => return Ok(connection),
+            Err(_) if policy.has_attempts_left(attempt + 1) => attempt += 1,
+            Err(error) => return Err(error),
         }
     }
 }
+
+fn connection_lost() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "connection to server was lost")
+}
@@ -0,0 +1,162 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+#[cfg(feature = "tls")]
+use rustls::pki_types::CertificateDer;
+#[cfg(feature = "tls")]
+use tokio_rustls::{client, server};
+
+/// A TCP connection, which might be secured with TLS.
+pub enum TcpConnection {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    TlsClient(Box<client::TlsStream<TcpStream>>),
+    #[cfg(feature = "tls")]
+    TlsServer(Box<server::TlsStream<TcpStream>>),
+}
+
+impl TcpConnection {
+    fn socket(&self) -> &TcpStream {
+        match *self {
+            TcpConnection::Plain(ref socket) => socket,
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsClient(ref stream) => stream.get_ref().0,
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsServer(ref stream) => stream.get_ref().0,
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket().local_addr()
+    }
+
+    /// The DER encoded certificate the peer authenticated itself with, if
+    /// any.
+    pub fn peer_certificate(&self) -> Option<Vec<u8>> {
+        match *self {
+            TcpConnection::Plain(_) => None,
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsClient(ref stream) => {
+                first_certificate(stream.get_ref().1.peer_certificates())
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsServer(ref stream) => {
+                first_certificate(stream.get_ref().1.peer_certificates())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+fn first_certificate(chain: Option<&[CertificateDer]>) -> Option<Vec<u8>> {
+    chain
+        .and_then(|chain| chain.first())
+        .map(|certificate| certificate.to_vec())
+}
+
+impl From<TcpStream> for TcpConnection {
+    fn from(socket: TcpStream) -> Self {
+        TcpConnection::Plain(socket)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl From<client::TlsStream<TcpStream>> for TcpConnection {
+    fn from(stream: client::TlsStream<TcpStream>) -> Self {
+        TcpConnection::TlsClient(Box::new(stream))
+    }
+}
+
+#[cfg(feature = "tls")]
+impl From<server::TlsStream<TcpStream>> for TcpConnection {
+    fn from(stream: server::TlsStream<TcpStream>) -> Self {
+        TcpConnection::TlsServer(Box::new(stream))
+    }
+}
+
+impl AsyncRead for TcpConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+        buffer: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        match *self {
+            TcpConnection::Plain(ref mut socket) => {
+                Pin::new(socket).poll_read(context, buffer)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsClient(ref mut stream) => {
+                Pin::new(stream).poll_read(context, buffer)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsServer(ref mut stream) => {
+                Pin::new(stream).poll_read(context, buffer)
+            }
+        }
+    }
+}
+
+impl AsyncWrite for TcpConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+        buffer: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match *self {
+            TcpConnection::Plain(ref mut socket) => {
+                Pin::new(socket).poll_write(context, buffer)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsClient(ref mut stream) => {
+                Pin::new(stream).poll_write(context, buffer)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsServer(ref mut stream) => {
+                Pin::new(stream).poll_write(context, buffer)
+            }
+        }
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<io::Result<()>> {
+        match *self {
+            TcpConnection::Plain(ref mut socket) => {
+                Pin::new(socket).poll_flush(context)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsClient(ref mut stream) => {
+                Pin::new(stream).poll_flush(context)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsServer(ref mut stream) => {
+                Pin::new(stream).poll_flush(context)
+            }
+        }
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<io::Result<()>> {
+        match *self {
+            TcpConnection::Plain(ref mut socket) => {
+                Pin::new(socket).poll_shutdown(context)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsClient(ref mut stream) => {
+                Pin::new(stream).poll_shutdown(context)
+            }
+            #[cfg(feature = "tls")]
+            TcpConnection::TlsServer(ref mut stream) => {
+                Pin::new(stream).poll_shutdown(context)
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use tokio::net::{TcpListener, TcpStream};
+
+use super::super::accept_backoff::AcceptBackoff;
+
+/// The sockets accepted by a listener.
+///
+/// Accept errors are produced without ending the stream. After an error that
+/// doesn't belong to a single connection, accepting is paused for a while, so
+/// that the same error isn't produced over and over again.
+pub struct TcpIncoming {
+    listener: TcpListener,
+    backoff: AcceptBackoff,
+}
+
+impl TcpIncoming {
+    pub fn new(listener: TcpListener) -> Self {
+        TcpIncoming {
+            listener,
+            backoff: AcceptBackoff::default(),
+        }
+    }
+}
+
+impl Stream for TcpIncoming {
+    type Item = io::Result<(TcpStream, SocketAddr)>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        ready!(self.backoff.poll_ready(context));
+
+        let result = ready!(self.listener.poll_accept(context));
+
+        self.backoff.record(&result);
+
+        Poll::Ready(Some(result))
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::ServerConfig;
+use tokio::net::TcpStream;
+use tokio_rustls::Accept;
+
+/// Secures the connections accepted by TCP listener servers with TLS.
+///
+/// The server's certificate is configured in the `ServerConfig`, as is
+/// whether clients must authenticate themselves with certificates.
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    acceptor: tokio_rustls::TlsAcceptor,
+    handshake_timeout: Duration,
+}
+
+impl TlsAcceptor {
+    pub fn new(config: Arc<ServerConfig>) -> Self {
+        TlsAcceptor {
+            acceptor: tokio_rustls::TlsAcceptor::from(config),
+            handshake_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets how long a client has to complete its handshake before its
+    /// connection is dropped. The default is ten seconds.
+    pub fn set_handshake_timeout(&mut self, timeout: Duration) {
+        self.handshake_timeout = timeout;
+    }
+
+    pub fn handshake_timeout(&self) -> Duration {
+        self.handshake_timeout
+    }
+
+    /// Completes the TLS handshake of a new connection.
+    pub fn accept(&self, socket: TcpStream) -> Accept<TcpStream> {
+        self.acceptor.accept(socket)
+    }
+}
@@ -0,0 +1,45 @@
+use std::convert::TryFrom;
+use std::io;
+use std::sync::Arc;
+
+use rustls::pki_types::ServerName;
+use rustls::ClientConfig;
+use tokio::net::TcpStream;
+use tokio_rustls::Connect;
+
+/// Secures the connections of TCP clients with TLS.
+///
+/// The server's certificate is verified against the root certificates of the
+/// `ClientConfig` and the expected server name. Client certificates for
+/// servers that require them are also configured in the `ClientConfig`.
+#[derive(Clone)]
+pub struct TlsConnector {
+    connector: tokio_rustls::TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl TlsConnector {
+    pub fn new(
+        config: Arc<ClientConfig>,
+        server_name: &str,
+    ) -> io::Result<Self> {
+        let server_name = ServerName::try_from(server_name)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid TLS server name",
+                )
+            })?
+            .to_owned();
+
+        Ok(TlsConnector {
+            connector: tokio_rustls::TlsConnector::from(config),
+            server_name,
+        })
+    }
+
+    /// Completes the TLS handshake of a new connection.
+    pub fn connect(&self, socket: TcpStream) -> Connect<TcpStream> {
+        self.connector.connect(self.server_name.clone(), socket)
+    }
+}
@@ -0,0 +1,75 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::FuturesUnordered;
+use futures::{Future, Stream, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+
+use super::tcp_connection::TcpConnection;
+use super::tcp_incoming::TcpIncoming;
+use super::tls_acceptor::TlsAcceptor;
+
+type Handshake = Pin<
+    Box<dyn Future<Output = io::Result<(TcpConnection, SocketAddr)>> + Send>,
+>;
+
+/// The connections accepted by a listener, after their TLS handshakes have
+/// completed.
+///
+/// Handshakes are completed concurrently, so a slow client doesn't delay the
+/// others. Handshakes that fail or time out produce errors, as do failures to
+/// accept a connection, but neither of them ends the stream.
+pub struct TlsIncoming {
+    sockets: TcpIncoming,
+    acceptor: TlsAcceptor,
+    handshakes: FuturesUnordered<Handshake>,
+}
+
+impl TlsIncoming {
+    pub fn new(listener: TcpListener, acceptor: TlsAcceptor) -> Self {
+        TlsIncoming {
+            sockets: TcpIncoming::new(listener),
+            acceptor,
+            handshakes: FuturesUnordered::new(),
+        }
+    }
+
+    fn start_handshake(&mut self, socket: TcpStream, address: SocketAddr) {
+        let timeout = self.acceptor.handshake_timeout();
+        let handshake = self.acceptor.accept(socket);
+
+        self.handshakes.push(Box::pin(async move {
+            let stream = time::timeout(timeout, handshake).await.map_err(
+                |_| io::Error::new(io::ErrorKind::TimedOut, "TLS handshake"),
+            )??;
+
+            Ok((TcpConnection::from(stream), address))
+        }));
+    }
+}
+
+impl Stream for TlsIncoming {
+    type Item = io::Result<(TcpConnection, SocketAddr)>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        while let Poll::Ready(Some(result)) =
+            self.sockets.poll_next_unpin(context)
+        {
+            match result {
+                Ok((socket, address)) => self.start_handshake(socket, address),
+                Err(error) => return Poll::Ready(Some(Err(error))),
+            }
+        }
+
+        match self.handshakes.poll_next_unpin(context) {
+            Poll::Ready(Some(result)) => Poll::Ready(Some(result)),
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
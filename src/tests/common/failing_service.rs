@@ -0,0 +1,31 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::future;
+use tower::service_fn;
+use tower_service::Service;
+
+use crate::client_error::ClientError;
+
+pub type FailingServiceError = ClientError<io::Error, io::Error>;
+
+/// A service whose first `failures` calls fail as if their connection was
+/// lost, and whose later calls respond with the request in uppercase.
+///
+/// Every call is counted in the `attempts`.
+pub fn failing_service<R>(
+    attempts: Arc<AtomicUsize>,
+    failures: usize,
+) -> impl Service<R, Response = String, Error = FailingServiceError> + Clone
+where
+    R: AsRef<str>,
+{
+    service_fn(move |request: R| {
+        if attempts.fetch_add(1, Ordering::AcqRel) < failures {
+            future::err(ClientError::ConnectionLost)
+        } else {
+            future::ok(request.as_ref().to_uppercase())
+        }
+    })
+}
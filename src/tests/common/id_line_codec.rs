@@ -0,0 +1,49 @@
+use std::io;
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::LineCodec;
+
+/// Encodes `(id, text)` pairs as lines with the ID before the text.
+#[derive(Clone)]
+pub struct IdLineCodec;
+
+impl Decoder for IdLineCodec {
+    type Item = (u32, String);
+    type Error = io::Error;
+
+    fn decode(
+        &mut self,
+        buffer: &mut BytesMut,
+    ) -> io::Result<Option<(u32, String)>> {
+        let line = match LineCodec.decode(buffer)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        let mut parts = line.splitn(2, ' ');
+        let id = parts.next().and_then(|id| id.parse().ok());
+        let text = parts.next();
+
+        match (id, text) {
+            (Some(id), Some(text)) => Ok(Some((id, text.to_owned()))),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "line is missing its ID",
+            )),
+        }
+    }
+}
+
+impl Encoder<(u32, String)> for IdLineCodec {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        (id, text): (u32, String),
+        buffer: &mut BytesMut,
+    ) -> io::Result<()> {
+        LineCodec.encode(format!("{} {}", id, text), buffer)
+    }
+}
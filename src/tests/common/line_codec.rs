@@ -0,0 +1,42 @@
+use std::io;
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[derive(Clone)]
+pub struct LineCodec;
+
+impl Decoder for LineCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, buffer: &mut BytesMut) -> io::Result<Option<String>> {
+        match buffer.iter().position(|&byte| byte == b'\n') {
+            Some(position) => {
+                let line = buffer.split_to(position + 1);
+
+                String::from_utf8(line[..position].to_vec())
+                    .map(Some)
+                    .map_err(|error| {
+                        io::Error::new(io::ErrorKind::InvalidData, error)
+                    })
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<String> for LineCodec {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        line: String,
+        buffer: &mut BytesMut,
+    ) -> io::Result<()> {
+        buffer.extend_from_slice(line.as_bytes());
+        buffer.extend_from_slice(b"\n");
+
+        Ok(())
+    }
+}
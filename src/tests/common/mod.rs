@@ -1,7 +1,19 @@
+mod failing_service;
+#[cfg(feature = "udp")]
+mod id_line_codec;
+#[cfg(feature = "codec")]
+mod line_codec;
+mod notify_flag;
 mod sink_stream;
 mod slow_to_upper_service;
 mod to_upper_service;
 
+pub use self::failing_service::{failing_service, FailingServiceError};
+#[cfg(feature = "udp")]
+pub use self::id_line_codec::IdLineCodec;
+#[cfg(feature = "codec")]
+pub use self::line_codec::LineCodec;
+pub use self::notify_flag::NotifyFlag;
 pub use self::sink_stream::SinkStream;
 pub use self::slow_to_upper_service::SlowToUpperService;
 pub use self::to_upper_service::ToUpperService;
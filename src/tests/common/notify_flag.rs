@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures::task::ArcWake;
+
+pub struct NotifyFlag {
+    notified: AtomicBool,
+}
+
+impl NotifyFlag {
+    pub fn new() -> Arc<Self> {
+        Arc::new(NotifyFlag {
+            notified: AtomicBool::new(false),
+        })
+    }
+
+    pub fn take(&self) -> bool {
+        self.notified.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl ArcWake for NotifyFlag {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.notified.store(true, Ordering::SeqCst);
+    }
+}
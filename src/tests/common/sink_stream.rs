@@ -1,18 +1,19 @@
-use futures::{Poll, Sink, StartSend, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-pub struct SinkStream<I, O>
-where
-    I: Stream,
-    O: Sink,
-{
+use futures::{Sink, Stream};
+
+/// A transport made of separate channels for each direction.
+///
+/// The incoming stream can't fail, so its items are wrapped in `Ok`.
+pub struct SinkStream<I, O> {
     sink: O,
     stream: I,
 }
 
 impl<I, O> SinkStream<I, O>
 where
-    I: Stream,
-    O: Sink,
+    I: Stream + Unpin,
 {
     pub fn new(sink: O, stream: I) -> Self {
         SinkStream { sink, stream }
@@ -21,33 +22,51 @@ where
 
 impl<I, O> Stream for SinkStream<I, O>
 where
-    I: Stream,
-    O: Sink,
+    I: Stream + Unpin,
+    O: Unpin,
 {
-    type Item = I::Item;
-    type Error = I::Error;
+    type Item = Result<I::Item, ()>;
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        self.stream.poll()
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream).poll_next(context).map(|item| item.map(Ok))
     }
 }
 
-impl<I, O> Sink for SinkStream<I, O>
+impl<I, O, T> Sink<T> for SinkStream<I, O>
 where
-    I: Stream,
-    O: Sink,
+    I: Unpin,
+    O: Sink<T> + Unpin,
 {
-    type SinkItem = O::SinkItem;
-    type SinkError = O::SinkError;
+    type Error = O::Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink).poll_ready(context)
+    }
 
     fn start_send(
-        &mut self,
-        item: Self::SinkItem,
-    ) -> StartSend<Self::SinkItem, Self::SinkError> {
-        self.sink.start_send(item)
+        mut self: Pin<&mut Self>,
+        item: T,
+    ) -> Result<(), Self::Error> {
+        Pin::new(&mut self.sink).start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink).poll_flush(context)
     }
 
-    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
-        self.sink.poll_complete()
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink).poll_close(context)
     }
 }
@@ -1,40 +1,29 @@
 use std::io;
+use std::task::{Context, Poll};
 use std::time::Duration;
 
-use futures::future::{Flatten, Future, FutureResult, IntoFuture, Join, Map};
-use tokio_core::reactor::{Handle, Timeout};
-use tokio_service::Service;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio::time;
+use tower_service::Service;
 
-pub struct SlowToUpperService {
-    handle: Handle,
-}
-
-impl SlowToUpperService {
-    pub fn new(handle: Handle) -> Self {
-        SlowToUpperService { handle }
-    }
-}
+pub struct SlowToUpperService;
 
-impl Service for SlowToUpperService {
-    type Request = (String, Duration);
+impl Service<(String, Duration)> for SlowToUpperService {
     type Response = String;
     type Error = io::Error;
-    type Future = Map<
-        Join<
-            Flatten<FutureResult<Timeout, Self::Error>>,
-            FutureResult<Self::Response, Self::Error>,
-        >,
-        fn(((), String)) -> Self::Response,
-    >;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
 
-    fn call(&self, request: Self::Request) -> Self::Future {
+    fn call(&mut self, request: (String, Duration)) -> Self::Future {
         let (data, delay) = request;
-        let delay_future = Timeout::new(delay, &self.handle)
-            .into_future()
-            .flatten();
 
-        delay_future
-            .join(Ok(data.to_uppercase()))
-            .map(|result| result.1)
+        time::sleep(delay).map(move |()| Ok(data.to_uppercase())).boxed()
     }
 }
@@ -1,15 +1,23 @@
-use futures::future::{FutureResult, IntoFuture};
-use tokio_service::Service;
+use std::task::{Context, Poll};
+
+use futures::future::{self, Ready};
+use tower_service::Service;
 
 pub struct ToUpperService;
 
-impl Service for ToUpperService {
-    type Request = String;
+impl Service<String> for ToUpperService {
     type Response = String;
     type Error = ();
-    type Future = FutureResult<Self::Response, Self::Error>;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
 
-    fn call(&self, request: Self::Request) -> Self::Future {
-        Ok(request.to_uppercase()).into_future()
+    fn call(&mut self, request: String) -> Self::Future {
+        future::ok(request.to_uppercase())
     }
 }
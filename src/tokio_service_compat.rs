@@ -0,0 +1,230 @@
+use std::future::Future;
+use std::marker::PhantomData;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::compat::{Compat, Compat01As03};
+use tower_service::Service;
+
+/// Adapts a `tokio_service::Service` so that it can be used by the servers.
+///
+/// Such services have no way to apply backpressure, so the adapter is always
+/// ready.
+#[derive(Clone, Debug)]
+pub struct FromTokioService<S> {
+    service: S,
+}
+
+impl<S> FromTokioService<S> {
+    pub fn new(service: S) -> Self {
+        FromTokioService { service }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S> Service<S::Request> for FromTokioService<S>
+where
+    S: tokio_service::Service,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Compat01As03<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        _context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: S::Request) -> Self::Future {
+        Compat01As03::new(self.service.call(request))
+    }
+}
+
+/// Adapts a service, like one of the clients, so that it can be used where a
+/// `tokio_service::Service` for requests of type `R` is expected.
+///
+/// Each call uses its own clone of the service, and waits for it to be ready
+/// before sending the request.
+pub struct IntoTokioService<S, R> {
+    service: S,
+    _request: PhantomData<fn(R)>,
+}
+
+impl<S, R> IntoTokioService<S, R> {
+    pub fn new(service: S) -> Self {
+        IntoTokioService {
+            service,
+            _request: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S, R> Clone for IntoTokioService<S, R>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        IntoTokioService::new(self.service.clone())
+    }
+}
+
+impl<S, R> tokio_service::Service for IntoTokioService<S, R>
+where
+    S: Service<R> + Clone,
+{
+    type Request = R;
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Compat<ReadyCall<S, R>>;
+
+    fn call(&self, request: R) -> Self::Future {
+        Compat::new(ReadyCall::new(self.service.clone(), request))
+    }
+}
+
+enum ReadyCallState<S, R>
+where
+    S: Service<R>,
+{
+    Waiting(S, R),
+    Calling(Pin<Box<S::Future>>),
+    Done,
+}
+
+/// Calls a service once it is ready.
+pub struct ReadyCall<S, R>
+where
+    S: Service<R>,
+{
+    state: ReadyCallState<S, R>,
+}
+
+impl<S, R> ReadyCall<S, R>
+where
+    S: Service<R>,
+{
+    pub fn new(service: S, request: R) -> Self {
+        ReadyCall {
+            state: ReadyCallState::Waiting(service, request),
+        }
+    }
+}
+
+// The response future is pinned in its own allocation.
+impl<S, R> Unpin for ReadyCall<S, R> where S: Service<R> {}
+
+impl<S, R> Future for ReadyCall<S, R>
+where
+    S: Service<R>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        loop {
+            match mem::replace(&mut self.state, ReadyCallState::Done) {
+                ReadyCallState::Waiting(mut service, request) => {
+                    match service.poll_ready(context) {
+                        Poll::Ready(Ok(())) => {
+                            let response = service.call(request);
+
+                            self.state =
+                                ReadyCallState::Calling(Box::pin(response));
+                        }
+                        Poll::Ready(Err(error)) => {
+                            return Poll::Ready(Err(error));
+                        }
+                        Poll::Pending => {
+                            self.state =
+                                ReadyCallState::Waiting(service, request);
+
+                            return Poll::Pending;
+                        }
+                    }
+                }
+                ReadyCallState::Calling(mut response) => {
+                    let result = response.as_mut().poll(context);
+
+                    if result.is_pending() {
+                        self.state = ReadyCallState::Calling(response);
+                    }
+
+                    return result;
+                }
+                ReadyCallState::Done => {
+                    panic!("ReadyCall polled after completion")
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::compat::Future01CompatExt;
+    use futures::future;
+    use tower::service_fn;
+
+    use super::*;
+    use crate::make_services::MakeServices;
+    use crate::memory::listener;
+    use crate::pipeline_client::PipelineClient;
+    use crate::pipeline_listening_server::PipelineListeningServer;
+    use crate::tests::common::ToUpperService;
+
+    struct OldToUpperService;
+
+    impl tokio_service::Service for OldToUpperService {
+        type Request = String;
+        type Response = String;
+        type Error = ();
+        type Future = Compat<future::Ready<Result<String, ()>>>;
+
+        fn call(&self, request: String) -> Self::Future {
+            Compat::new(future::ok(request.to_uppercase()))
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_tokio_services() {
+        let (connector, listener) = listener(1);
+        let services = MakeServices::new(service_fn(|()| {
+            future::ok::<_, ()>(FromTokioService::new(OldToUpperService))
+        }));
+
+        tokio::spawn(PipelineListeningServer::new(services, listener));
+
+        let client = PipelineClient::new(connector.connect().unwrap());
+
+        assert_eq!(client.call("request".to_owned()).await.unwrap(), "REQUEST");
+    }
+
+    #[tokio::test]
+    async fn exposes_clients_as_tokio_services() {
+        let (connector, listener) = listener(1);
+        let services = MakeServices::new(service_fn(|()| {
+            future::ok::<_, ()>(ToUpperService)
+        }));
+
+        tokio::spawn(PipelineListeningServer::new(services, listener));
+
+        let client = PipelineClient::new(connector.connect().unwrap());
+        let service = IntoTokioService::new(client);
+        let response =
+            tokio_service::Service::call(&service, "request".to_owned());
+
+        assert_eq!(response.compat().await.unwrap(), "REQUEST");
+    }
+}
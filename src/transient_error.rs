@@ -0,0 +1,18 @@
+use std::io;
+
+/// An error of a transport that tells whether it was caused by the
+/// connection, rather than by a message that couldn't be encoded or decoded.
+pub trait TransientError {
+    /// Whether sending the same message again might succeed.
+    fn is_transient(&self) -> bool;
+}
+
+impl TransientError for io::Error {
+    // Codecs report the messages they can't encode or decode as invalid.
+    fn is_transient(&self) -> bool {
+        !matches!(
+            self.kind(),
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput
+        )
+    }
+}
@@ -0,0 +1,35 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A response future that remembers where its response should be sent.
+pub struct AddressedResponse<F> {
+    address: SocketAddr,
+    response: Pin<Box<F>>,
+}
+
+impl<F> AddressedResponse<F> {
+    pub fn new(address: SocketAddr, response: F) -> Self {
+        AddressedResponse {
+            address,
+            response: Box::pin(response),
+        }
+    }
+}
+
+impl<F, T, E> Future for AddressedResponse<F>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = Result<(SocketAddr, T), E>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let response = ready!(self.response.as_mut().poll(context))?;
+
+        Poll::Ready(Ok((self.address, response)))
+    }
+}
@@ -0,0 +1,38 @@
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+
+use tower_service::Service;
+
+use super::addressed_response::AddressedResponse;
+
+/// Wraps a service so that each response is paired with the address of the
+/// client that sent the request.
+pub struct AddressedService<S> {
+    service: S,
+}
+
+impl<S> AddressedService<S> {
+    pub fn new(service: S) -> Self {
+        AddressedService { service }
+    }
+}
+
+impl<S, R> Service<(SocketAddr, R)> for AddressedService<S>
+where
+    S: Service<R>,
+{
+    type Response = (SocketAddr, S::Response);
+    type Error = S::Error;
+    type Future = AddressedResponse<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(context)
+    }
+
+    fn call(&mut self, (address, request): (SocketAddr, R)) -> Self::Future {
+        AddressedResponse::new(address, self.service.call(request))
+    }
+}
@@ -0,0 +1,56 @@
+use std::io;
+use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bytes::BytesMut;
+use tokio::net::UdpSocket;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The largest payload a UDP datagram can carry.
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Decodes the message in a datagram.
+///
+/// Unlike with streams, a datagram that doesn't contain a complete message
+/// can't be completed by later datagrams, so its message is simply missing.
+pub fn decode_datagram<C>(
+    codec: &mut C,
+    datagram: &[u8],
+) -> Result<Option<C::Item>, C::Error>
+where
+    C: Decoder,
+{
+    let mut buffer = BytesMut::from(datagram);
+
+    codec.decode(&mut buffer)
+}
+
+pub fn encode_datagram<C, M>(
+    codec: &mut C,
+    message: M,
+) -> Result<Vec<u8>, C::Error>
+where
+    C: Encoder<M>,
+{
+    let mut buffer = BytesMut::new();
+
+    codec.encode(message, &mut buffer)?;
+
+    Ok(buffer.to_vec())
+}
+
+/// The address to bind to for an ephemeral port that can reach the
+/// `peer_address`.
+pub fn ephemeral_address(peer_address: &SocketAddr) -> SocketAddr {
+    match *peer_address {
+        SocketAddr::V4(_) => (Ipv4Addr::new(0, 0, 0, 0), 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0).into(),
+    }
+}
+
+/// Binds a socket that is registered with the current runtime.
+pub fn bind(address: &SocketAddr) -> io::Result<UdpSocket> {
+    let socket = net::UdpSocket::bind(address)?;
+
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket)
+}
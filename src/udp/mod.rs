@@ -0,0 +1,14 @@
+mod addressed_response;
+mod addressed_service;
+mod datagram;
+mod retransmission_policy;
+mod udp_client_transport;
+mod udp_server_transport;
+
+mod multiplex_udp_client;
+mod multiplex_udp_server;
+
+pub use self::retransmission_policy::RetransmissionPolicy;
+
+pub use self::multiplex_udp_client::MultiplexUdpClient;
+pub use self::multiplex_udp_server::MultiplexUdpServer;
@@ -0,0 +1,343 @@
+use std::future::Future;
+use std::hash::Hash;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
+
+use super::super::{
+    client_error::ClientError,
+    message_with_id::MessageWithId,
+    multiplex_client::{MultiplexClient, MultiplexClientFuture},
+};
+use super::datagram::{bind, ephemeral_address};
+use super::retransmission_policy::RetransmissionPolicy;
+use super::udp_client_transport::{AbandonedRequests, UdpClientTransport};
+
+type Id<C> = <<C as Decoder>::Item as MessageWithId>::Id;
+
+type ErrorAlias<C, R> =
+    ClientError<<C as Decoder>::Error, <C as Encoder<R>>::Error>;
+
+/// A multiplexed client that sends each request in its own datagram.
+///
+/// Requests that aren't answered are retransmitted as configured by the
+/// `RetransmissionPolicy`, so the server might receive the same request more
+/// than once. The extra responses it sends back are discarded as unexpected
+/// responses, as long as the client uses the default
+/// `IdErrorPolicy::DropMessage`.
+pub struct MultiplexUdpClient<C>
+where
+    C: Decoder,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    client: MultiplexClient<UdpClientTransport<C>>,
+    abandoned_requests: AbandonedRequests<Id<C>>,
+}
+
+impl<C> MultiplexUdpClient<C>
+where
+    C: Decoder,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    pub fn connect(
+        address: &SocketAddr,
+        codec: C,
+        policy: RetransmissionPolicy,
+    ) -> io::Result<Self> {
+        let socket = bind(&ephemeral_address(address))?;
+        let transport =
+            UdpClientTransport::new(socket, *address, codec, policy);
+        let abandoned_requests = transport.abandoned_requests();
+
+        Ok(MultiplexUdpClient {
+            client: MultiplexClient::with_timeout(transport, policy.timeout()),
+            abandoned_requests,
+        })
+    }
+
+    pub fn call<R>(&self, request: R) -> MultiplexUdpClientFuture<C, R>
+    where
+        C: Encoder<R>,
+        R: MessageWithId<Id = <C::Item as MessageWithId>::Id>,
+    {
+        MultiplexUdpClientFuture {
+            id: Some(request.id()),
+            response: Box::pin(self.client.call(request)),
+            abandoned_requests: self.abandoned_requests.clone(),
+        }
+    }
+}
+
+impl<C, R> Service<R> for MultiplexUdpClient<C>
+where
+    C: Decoder + Encoder<R>,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = <C::Item as MessageWithId>::Id>,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    type Response = C::Item;
+    type Error = ErrorAlias<C, R>;
+    type Future = MultiplexUdpClientFuture<C, R>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.client.poll_ready::<R>(context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        MultiplexUdpClient::call(self, request)
+    }
+}
+
+/// The response to a call made through a `MultiplexUdpClient`.
+///
+/// If the future is dropped or fails before the response arrives, its
+/// request is no longer retransmitted. Requests that were never sent, like
+/// those with a duplicate ID, leave the retransmissions alone.
+pub struct MultiplexUdpClientFuture<C, R>
+where
+    C: Decoder + Encoder<R>,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = Id<C>>,
+    Id<C>: Eq + Hash,
+{
+    id: Option<Id<C>>,
+    response: Pin<Box<MultiplexClientFuture<UdpClientTransport<C>, R>>>,
+    abandoned_requests: AbandonedRequests<Id<C>>,
+}
+
+// The response future is pinned in its own allocation.
+impl<C, R> Unpin for MultiplexUdpClientFuture<C, R>
+where
+    C: Decoder + Encoder<R>,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = Id<C>>,
+    Id<C>: Eq + Hash,
+{
+}
+
+impl<C, R> Future for MultiplexUdpClientFuture<C, R>
+where
+    C: Decoder + Encoder<R>,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = Id<C>>,
+    Id<C>: Eq + Hash,
+{
+    type Output = Result<C::Item, ErrorAlias<C, R>>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        let result = ready!(self.response.as_mut().poll(context));
+
+        // A request that wasn't registered or sent isn't retransmitted, but
+        // another request with the same ID might be.
+        match result {
+            Ok(_)
+            | Err(ClientError::DuplicateRequestId)
+            | Err(ClientError::SendError(_)) => self.id = None,
+            Err(_) => {}
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+impl<C, R> Drop for MultiplexUdpClientFuture<C, R>
+where
+    C: Decoder + Encoder<R>,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = Id<C>>,
+    Id<C>: Eq + Hash,
+{
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            let mut abandoned_requests = self.abandoned_requests
+                .lock()
+                .expect("a thread panicked while abandoning a UDP request");
+
+            abandoned_requests.push(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket as StdUdpSocket;
+    use std::str;
+    use std::thread;
+    use std::time::Duration;
+
+    use tokio::time;
+
+    use super::*;
+    use crate::tests::common::IdLineCodec;
+
+    #[tokio::test]
+    async fn retransmits_unanswered_requests() {
+        let server = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let mut buffer = [0; 100];
+            let (size, _) = server.recv_from(&mut buffer).unwrap();
+            let lost_request = buffer[..size].to_vec();
+
+            let (size, client) = server.recv_from(&mut buffer).unwrap();
+            let request = &buffer[..size];
+
+            assert_eq!(request, &lost_request[..]);
+
+            let response = str::from_utf8(request).unwrap().to_uppercase();
+
+            server.send_to(response.as_bytes(), client).unwrap();
+        });
+
+        let client = MultiplexUdpClient::connect(
+            &address,
+            IdLineCodec,
+            quick_retransmission(),
+        ).unwrap();
+
+        let response = client.call((7, "request".to_owned())).await;
+
+        assert_eq!(response.unwrap(), (7, "REQUEST".to_owned()));
+
+        server_thread.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_after_the_last_retransmission() {
+        let server = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        server
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let mut buffer = [0; 100];
+
+            (0..).take_while(|_| server.recv(&mut buffer).is_ok()).count()
+        });
+
+        let client = MultiplexUdpClient::connect(
+            &address,
+            IdLineCodec,
+            quick_retransmission(),
+        ).unwrap();
+
+        match client.call((7, "request".to_owned())).await {
+            Err(ClientError::Timeout) => {}
+            _ => panic!("unanswered request did not time out"),
+        }
+
+        assert_eq!(server_thread.join().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn stops_retransmitting_abandoned_requests() {
+        let server = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        server
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let mut buffer = [0; 100];
+            let mut requests = Vec::new();
+
+            while let Ok(size) = server.recv(&mut buffer) {
+                requests.push(buffer[..size].to_vec());
+            }
+
+            requests
+        });
+
+        let client = MultiplexUdpClient::connect(
+            &address,
+            IdLineCodec,
+            quick_retransmission(),
+        ).unwrap();
+
+        let abandoned_call = client.call((7, "abandoned".to_owned()));
+
+        assert!(time::timeout(Duration::from_millis(5), abandoned_call)
+            .await
+            .is_err());
+
+        assert!(client.call((8, "request".to_owned())).await.is_err());
+
+        let requests = server_thread.join().unwrap();
+        let abandoned_requests = requests
+            .iter()
+            .filter(|request| request.starts_with(b"7 "))
+            .count();
+
+        assert_eq!(abandoned_requests, 1);
+        assert_eq!(requests.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn duplicate_requests_leave_the_original_retransmitting() {
+        let server = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        server
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let mut buffer = [0; 100];
+            let (size, _) = server.recv_from(&mut buffer).unwrap();
+            let lost_request = buffer[..size].to_vec();
+
+            let (size, client) = server.recv_from(&mut buffer).unwrap();
+            let request = &buffer[..size];
+
+            assert_eq!(request, &lost_request[..]);
+
+            let response = str::from_utf8(request).unwrap().to_uppercase();
+
+            server.send_to(response.as_bytes(), client).unwrap();
+        });
+
+        let client = MultiplexUdpClient::connect(
+            &address,
+            IdLineCodec,
+            quick_retransmission(),
+        ).unwrap();
+
+        let mut call = client.call((7, "request".to_owned()));
+
+        assert!(time::timeout(Duration::from_millis(5), &mut call)
+            .await
+            .is_err());
+
+        match client.call((7, "duplicate".to_owned())).await {
+            Err(ClientError::DuplicateRequestId) => {}
+            _ => panic!("duplicate request ID was not detected"),
+        }
+
+        assert_eq!(call.await.unwrap(), (7, "REQUEST".to_owned()));
+
+        server_thread.join().unwrap();
+    }
+
+    fn quick_retransmission() -> RetransmissionPolicy {
+        RetransmissionPolicy {
+            interval: Duration::from_millis(20),
+            max_retransmissions: 2,
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::FutureExt;
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
+
+use super::super::{
+    connection_limits::ConnectionLimits, generic_server::ServerErrorAlias,
+    multiplex_server::MultiplexServer,
+    shutdown_handle::ShutdownHandle,
+};
+use super::addressed_service::AddressedService;
+use super::datagram::bind;
+use super::udp_server_transport::{PeerErrorHandler, UdpServerTransport};
+
+/// A server that receives each request in its own datagram, and sends the
+/// response back to the address it came from.
+///
+/// Clients might retransmit requests, so the service should be prepared to
+/// handle the same request more than once.
+pub struct MultiplexUdpServer<S, C>
+where
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response>,
+{
+    server: MultiplexServer<AddressedService<S>, UdpServerTransport<C>>,
+    local_address: SocketAddr,
+    peer_errors: PeerErrorHandler,
+}
+
+impl<S, C> MultiplexUdpServer<S, C>
+where
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response>,
+{
+    pub fn bind(
+        service: S,
+        address: &SocketAddr,
+        codec: C,
+    ) -> io::Result<Self> {
+        let socket = bind(address)?;
+        let local_address = socket.local_addr()?;
+        let transport = UdpServerTransport::new(socket, codec);
+        let peer_errors = transport.peer_error_handler();
+        let service = AddressedService::new(service);
+
+        Ok(MultiplexUdpServer {
+            server: MultiplexServer::new(service, transport),
+            local_address,
+            peer_errors,
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.server.set_connection_limits(limits);
+    }
+
+    /// Reports the errors sending a response to or receiving a request from
+    /// a single client to the `handler`.
+    ///
+    /// The handler receives the address of the client, if it's known. The
+    /// datagram is dropped, but the server keeps running. By default, the
+    /// errors are silently discarded.
+    pub fn on_peer_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(Option<SocketAddr>, io::Error) + Send + 'static,
+    {
+        *self.peer_errors
+            .lock()
+            .expect("a thread panicked while reporting a UDP peer error") =
+            Box::new(handler);
+    }
+}
+
+impl<S, C> Future for MultiplexUdpServer<S, C>
+where
+    S: Service<C::Item>,
+    C: Decoder + Encoder<S::Response>,
+{
+    type Output = Result<
+        (),
+        ServerErrorAlias<AddressedService<S>, UdpServerTransport<C>>,
+    >;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.server.poll_unpin(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket as StdUdpSocket;
+    use std::sync::mpsc as std_mpsc;
+    use std::thread;
+
+    use futures::channel::oneshot;
+    use futures::future::{self, Either};
+    use tokio::runtime::Builder;
+
+    use super::*;
+    use crate::tests::common::{LineCodec, ToUpperService};
+
+    #[test]
+    fn responds_to_each_sender() {
+        let (address_tx, address_rx) = std_mpsc::channel();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let runtime = Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(async move {
+                let address = "127.0.0.1:0".parse().unwrap();
+                let server = MultiplexUdpServer::bind(
+                    ToUpperService,
+                    &address,
+                    LineCodec,
+                ).unwrap();
+
+                address_tx.send(server.local_address()).unwrap();
+
+                match future::select(server, stop_rx).await {
+                    Either::Left((result, _)) => assert!(result.is_ok()),
+                    Either::Right((stopped, _)) => assert!(stopped.is_ok()),
+                }
+            });
+        });
+
+        let address = address_rx.recv().unwrap();
+        let first_client = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let second_client = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+
+        first_client.send_to(b"first\n", address).unwrap();
+        second_client.send_to(b"second\n", address).unwrap();
+
+        let mut buffer = [0; 100];
+        let size = second_client.recv(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..size], b"SECOND\n");
+
+        let size = first_client.recv(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..size], b"FIRST\n");
+
+        stop_tx.send(()).unwrap();
+        server_thread.join().unwrap();
+    }
+}
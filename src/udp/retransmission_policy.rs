@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+/// How a UDP client retransmits requests that weren't answered.
+///
+/// A request is sent again every `interval` until a response arrives, at most
+/// `max_retransmissions` times. If there is still no response one `interval`
+/// after the last retransmission, the call fails with `ClientError::Timeout`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetransmissionPolicy {
+    pub interval: Duration,
+    pub max_retransmissions: u32,
+}
+
+impl RetransmissionPolicy {
+    /// How long a call waits for a response, including all retransmissions.
+    ///
+    /// The timeout saturates at `Duration::MAX` instead of overflowing.
+    pub fn timeout(&self) -> Duration {
+        self.max_retransmissions
+            .checked_add(1)
+            .and_then(|intervals| self.interval.checked_mul(intervals))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+impl Default for RetransmissionPolicy {
+    fn default() -> Self {
+        RetransmissionPolicy {
+            interval: Duration::from_secs(1),
+            max_retransmissions: 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_includes_every_retransmission() {
+        let policy = RetransmissionPolicy {
+            interval: Duration::from_millis(20),
+            max_retransmissions: 2,
+        };
+
+        assert_eq!(policy.timeout(), Duration::from_millis(60));
+    }
+
+    #[test]
+    fn timeout_saturates_instead_of_overflowing() {
+        let many_retransmissions = RetransmissionPolicy {
+            interval: Duration::from_secs(1),
+            max_retransmissions: u32::MAX,
+        };
+        let long_interval = RetransmissionPolicy {
+            interval: Duration::MAX,
+            max_retransmissions: 1,
+        };
+
+        assert_eq!(many_retransmissions.timeout(), Duration::MAX);
+        assert_eq!(long_interval.timeout(), Duration::MAX);
+    }
+}
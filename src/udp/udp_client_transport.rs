@@ -0,0 +1,238 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use futures::{Sink, Stream};
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+use tokio::time::{self, Instant, Sleep};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::super::message_with_id::MessageWithId;
+use super::datagram::{decode_datagram, encode_datagram, MAX_DATAGRAM_SIZE};
+use super::retransmission_policy::RetransmissionPolicy;
+
+type Id<C> = <<C as Decoder>::Item as MessageWithId>::Id;
+
+/// The IDs of the requests whose calls were abandoned, and which should no
+/// longer be retransmitted.
+pub type AbandonedRequests<I> = Arc<Mutex<Vec<I>>>;
+
+struct PendingRequest {
+    datagram: Vec<u8>,
+    retransmissions: u32,
+    timer: Pin<Box<Sleep>>,
+}
+
+/// Sends requests to a single server, retransmitting them until they are
+/// answered.
+///
+/// Retransmissions are driven while the responses are polled, so they only
+/// happen while some call is waiting for its response. Requests whose calls
+/// are abandoned are no longer retransmitted. Datagrams that don't come from
+/// the server or that can't be decoded are discarded.
+pub struct UdpClientTransport<C>
+where
+    C: Decoder,
+    C::Item: MessageWithId,
+    Id<C>: Eq + Hash,
+{
+    socket: UdpSocket,
+    server_address: SocketAddr,
+    codec: C,
+    policy: RetransmissionPolicy,
+    pending_requests: HashMap<Id<C>, PendingRequest>,
+    abandoned_requests: AbandonedRequests<Id<C>>,
+    outgoing: VecDeque<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl<C> UdpClientTransport<C>
+where
+    C: Decoder,
+    C::Item: MessageWithId,
+    Id<C>: Eq + Hash,
+{
+    pub fn new(
+        socket: UdpSocket,
+        server_address: SocketAddr,
+        codec: C,
+        policy: RetransmissionPolicy,
+    ) -> Self {
+        UdpClientTransport {
+            socket,
+            server_address,
+            codec,
+            policy,
+            pending_requests: HashMap::new(),
+            abandoned_requests: Arc::new(Mutex::new(Vec::new())),
+            outgoing: VecDeque::new(),
+            buffer: vec![0; MAX_DATAGRAM_SIZE],
+        }
+    }
+
+    pub fn abandoned_requests(&self) -> AbandonedRequests<Id<C>> {
+        self.abandoned_requests.clone()
+    }
+
+    fn forget_abandoned_requests(&mut self) {
+        let abandoned_ids = mem::take(&mut *lock(&self.abandoned_requests));
+
+        for id in abandoned_ids {
+            self.pending_requests.remove(&id);
+        }
+    }
+
+    fn retransmit(&mut self, context: &mut Context) {
+        self.forget_abandoned_requests();
+
+        let policy = self.policy;
+        let outgoing = &mut self.outgoing;
+
+        // Requests that ran out of retransmissions are forgotten, and their
+        // calls eventually time out.
+        self.pending_requests.retain(|_, request| loop {
+            if request.timer.as_mut().poll(context).is_pending() {
+                return true;
+            }
+
+            if request.retransmissions == policy.max_retransmissions {
+                return false;
+            }
+
+            outgoing.push_back(request.datagram.clone());
+            request.retransmissions += 1;
+            request.timer.as_mut().reset(Instant::now() + policy.interval);
+        });
+    }
+
+    fn flush(&mut self, context: &mut Context) -> Poll<io::Result<()>> {
+        while let Some(datagram) = self.outgoing.front() {
+            ready!(self.socket.poll_send_to(
+                context,
+                datagram,
+                self.server_address
+            ))?;
+
+            self.outgoing.pop_front();
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+// The codec is never pinned.
+impl<C> Unpin for UdpClientTransport<C>
+where
+    C: Decoder,
+    C::Item: MessageWithId,
+    Id<C>: Eq + Hash,
+{
+}
+
+impl<C> Stream for UdpClientTransport<C>
+where
+    C: Decoder,
+    C::Item: MessageWithId,
+    Id<C>: Eq + Hash,
+{
+    type Item = Result<C::Item, C::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        this.retransmit(context);
+
+        if let Poll::Ready(Err(error)) = this.flush(context) {
+            return Poll::Ready(Some(Err(error.into())));
+        }
+
+        loop {
+            let mut buffer = ReadBuf::new(&mut this.buffer);
+            let address =
+                ready!(this.socket.poll_recv_from(context, &mut buffer))?;
+
+            if address != this.server_address {
+                continue;
+            }
+
+            if let Ok(Some(response)) =
+                decode_datagram(&mut this.codec, buffer.filled())
+            {
+                this.pending_requests.remove(&response.id());
+
+                return Poll::Ready(Some(Ok(response)));
+            }
+        }
+    }
+}
+
+impl<C, R> Sink<R> for UdpClientTransport<C>
+where
+    C: Decoder + Encoder<R>,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = Id<C>>,
+    Id<C>: Eq + Hash,
+{
+    type Error = <C as Encoder<R>>::Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.flush(context).map_err(Into::into)
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        request: R,
+    ) -> Result<(), Self::Error> {
+        let id = request.id();
+        let datagram = encode_datagram(&mut self.codec, request)?;
+        let timer = Box::pin(time::sleep(self.policy.interval));
+
+        // An earlier request with the same ID might have been abandoned.
+        lock(&self.abandoned_requests)
+            .retain(|abandoned_id| *abandoned_id != id);
+
+        self.outgoing.push_back(datagram.clone());
+        self.pending_requests.insert(
+            id,
+            PendingRequest {
+                datagram,
+                retransmissions: 0,
+                timer,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.flush(context).map_err(Into::into)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.flush(context).map_err(Into::into)
+    }
+}
+
+fn lock<I>(ids: &AbandonedRequests<I>) -> MutexGuard<'_, Vec<I>> {
+    ids.lock()
+        .expect("a thread panicked while holding the abandoned requests")
+}
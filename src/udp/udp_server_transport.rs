@@ -0,0 +1,209 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::{Sink, Stream};
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::datagram::{decode_datagram, encode_datagram, MAX_DATAGRAM_SIZE};
+
+/// The handler of the errors caused by single datagrams, shared by the
+/// transport and its server.
+pub type PeerErrorHandler =
+    Arc<Mutex<Box<dyn FnMut(Option<SocketAddr>, io::Error) + Send>>>;
+
+/// Receives requests from and sends responses to many clients through a
+/// single socket.
+///
+/// Datagrams that can't be decoded are discarded, and errors receiving or
+/// sending a single datagram are reported to the `PeerErrorHandler`, so that
+/// a single bad client can't stop the server.
+pub struct UdpServerTransport<C>
+where
+    C: Decoder,
+{
+    socket: UdpSocket,
+    codec: C,
+    buffer: Vec<u8>,
+    outgoing: Option<(SocketAddr, Vec<u8>)>,
+    peer_errors: PeerErrorHandler,
+}
+
+impl<C> UdpServerTransport<C>
+where
+    C: Decoder,
+{
+    pub fn new(socket: UdpSocket, codec: C) -> Self {
+        UdpServerTransport {
+            socket,
+            codec,
+            buffer: vec![0; MAX_DATAGRAM_SIZE],
+            outgoing: None,
+            peer_errors: Arc::new(Mutex::new(Box::new(|_, _| {}))),
+        }
+    }
+
+    pub fn peer_error_handler(&self) -> PeerErrorHandler {
+        self.peer_errors.clone()
+    }
+
+    /// Sends the outgoing response, dropping it if it can't be sent to its
+    /// client.
+    fn flush(&mut self, context: &mut Context) -> Poll<io::Result<()>> {
+        if let Some((address, ref datagram)) = self.outgoing {
+            let result =
+                ready!(self.socket.poll_send_to(context, datagram, address));
+
+            if let Err(error) = result {
+                self.report(Some(address), error);
+            }
+        }
+
+        self.outgoing = None;
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn report(&self, address: Option<SocketAddr>, error: io::Error) {
+        let mut handler = self.peer_errors
+            .lock()
+            .expect("a thread panicked while reporting a UDP peer error");
+
+        handler(address, error);
+    }
+}
+
+// The codec is never pinned.
+impl<C> Unpin for UdpServerTransport<C> where C: Decoder {}
+
+impl<C> Stream for UdpServerTransport<C>
+where
+    C: Decoder,
+{
+    type Item = Result<(SocketAddr, C::Item), C::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            let mut buffer = ReadBuf::new(&mut this.buffer);
+            let result =
+                ready!(this.socket.poll_recv_from(context, &mut buffer));
+            let address = match result {
+                Ok(address) => address,
+                Err(error) if is_caused_by_peer(&error) => {
+                    this.report(None, error);
+                    continue;
+                }
+                Err(error) => return Poll::Ready(Some(Err(error.into()))),
+            };
+
+            if let Ok(Some(request)) =
+                decode_datagram(&mut this.codec, buffer.filled())
+            {
+                return Poll::Ready(Some(Ok((address, request))));
+            }
+        }
+    }
+}
+
+impl<C, M> Sink<(SocketAddr, M)> for UdpServerTransport<C>
+where
+    C: Decoder + Encoder<M>,
+{
+    type Error = <C as Encoder<M>>::Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.flush(context).map_err(Into::into)
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (address, response): (SocketAddr, M),
+    ) -> Result<(), Self::Error> {
+        let datagram = encode_datagram(&mut self.codec, response)?;
+
+        self.outgoing = Some((address, datagram));
+
+        Ok(())
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.flush(context).map_err(Into::into)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.flush(context).map_err(Into::into)
+    }
+}
+
+/// Whether a receive error was caused by a datagram exchanged with a single
+/// peer, such as the ICMP error that some platforms report after a response
+/// couldn't be delivered, instead of a problem with the socket itself.
+fn is_caused_by_peer(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket as StdUdpSocket;
+
+    use futures::SinkExt;
+
+    use super::*;
+    use super::super::datagram::bind;
+    use crate::tests::common::LineCodec;
+
+    #[tokio::test]
+    async fn reports_undeliverable_responses_and_keeps_sending() {
+        let socket = bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let mut transport = UdpServerTransport::new(socket, LineCodec);
+
+        let reported_errors = Arc::new(Mutex::new(Vec::new()));
+        let errors = reported_errors.clone();
+
+        *transport.peer_error_handler().lock().unwrap() =
+            Box::new(move |address, _| errors.lock().unwrap().push(address));
+
+        let unreachable = "127.0.0.1:0".parse().unwrap();
+        let client = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let client_address = client.local_addr().unwrap();
+
+        transport
+            .send((unreachable, "lost response".to_owned()))
+            .await
+            .unwrap();
+        transport
+            .send((client_address, "response".to_owned()))
+            .await
+            .unwrap();
+
+        let mut buffer = [0; 100];
+        let size = client.recv(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..size], b"response\n");
+        assert_eq!(*reported_errors.lock().unwrap(), vec![Some(unreachable)]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{FutureExt, TryStream};
+use tokio::net::UnixListener;
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
+
+use super::{
+    incoming_transports::IncomingTransports,
+    stale_socket::remove_stale_socket,
+    super::{
+        codec_listener_aliases::{ErrorAlias, FutureAlias, ResponseAlias},
+        connection_limits::ConnectionLimits,
+        generic_listening_server::GenericListeningServer,
+        shutdown_handle::ShutdownHandle,
+        stream_of_future_results::StreamOfFutureResults,
+    },
+};
+
+pub struct GenericUnixListenerServer<S, C, H>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, C>>,
+{
+    server: GenericListeningServer<S, IncomingTransports<C>, H>,
+}
+
+impl<S, C, H> GenericUnixListenerServer<S, C, H>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, C>>,
+{
+    pub fn listen<P>(services: S, path: P, codec: C) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        remove_stale_socket(path.as_ref())?;
+
+        let listener = UnixListener::bind(path)?;
+        let transports = IncomingTransports::new(codec, listener);
+
+        Ok(GenericUnixListenerServer {
+            server: GenericListeningServer::new(services, transports),
+        })
+    }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.server.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, C>) + Send + 'static,
+    {
+        self.server.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.server.fail_fast();
+    }
+}
+
+impl<S, C, H> Future for GenericUnixListenerServer<S, C, H>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+    H: StreamOfFutureResults<FutureAlias<S, C>>,
+{
+    type Output = Result<(), ErrorAlias<S, C>>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.server.poll_unpin(context)
+    }
+}
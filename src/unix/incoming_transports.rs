@@ -0,0 +1,59 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use tokio::net::{UnixListener, UnixStream};
+use tokio_util::codec::{Decoder, Framed};
+
+use super::super::accept_backoff::AcceptBackoff;
+
+/// The transports of the connections accepted by a listener.
+///
+/// Accept errors are produced without ending the stream, and accepting is
+/// paused for a while after those that don't belong to a single connection.
+pub struct IncomingTransports<C>
+where
+    C: Clone + Decoder,
+{
+    codec: C,
+    listener: UnixListener,
+    backoff: AcceptBackoff,
+}
+
+impl<C> IncomingTransports<C>
+where
+    C: Clone + Decoder,
+{
+    pub fn new(codec: C, listener: UnixListener) -> Self {
+        IncomingTransports {
+            codec,
+            listener,
+            backoff: AcceptBackoff::default(),
+        }
+    }
+}
+
+impl<C> Stream for IncomingTransports<C>
+where
+    C: Clone + Decoder + Unpin,
+{
+    type Item = io::Result<Framed<UnixStream, C>>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        ready!(self.backoff.poll_ready(context));
+
+        let result = ready!(self.listener.poll_accept(context));
+
+        self.backoff.record(&result);
+
+        let transport = result.map(|(connection, _address)| {
+            Framed::new(connection, self.codec.clone())
+        });
+
+        Poll::Ready(Some(transport))
+    }
+}
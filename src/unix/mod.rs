@@ -0,0 +1,15 @@
+mod incoming_transports;
+mod stale_socket;
+
+mod multiplex_unix_client;
+mod pipeline_unix_client;
+
+mod generic_unix_listener_server;
+mod multiplex_unix_listener_server;
+mod pipeline_unix_listener_server;
+
+pub use self::multiplex_unix_client::MultiplexUnixClient;
+pub use self::pipeline_unix_client::PipelineUnixClient;
+
+pub use self::multiplex_unix_listener_server::MultiplexUnixListenerServer;
+pub use self::pipeline_unix_listener_server::PipelineUnixListenerServer;
@@ -0,0 +1,80 @@
+use std::hash::Hash;
+use std::io;
+use std::path::Path;
+use std::task::{Context, Poll};
+
+use tokio::net::UnixStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tower_service::Service;
+
+use super::super::{
+    client_error::ClientError,
+    message_with_id::MessageWithId,
+    multiplex_client::{MultiplexClient, MultiplexClientFuture},
+   
+};
+
+pub struct MultiplexUnixClient<C>
+where
+    C: Decoder + Unpin,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    client: MultiplexClient<Framed<UnixStream, C>>,
+}
+
+impl<C> MultiplexUnixClient<C>
+where
+    C: Decoder + Unpin,
+    C::Item: MessageWithId,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    pub async fn connect<P>(path: P, codec: C) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let connection = UnixStream::connect(path).await?;
+
+        Ok(Self::with_connection(connection, codec))
+    }
+
+    pub fn with_connection(connection: UnixStream, codec: C) -> Self {
+        MultiplexUnixClient {
+            client: MultiplexClient::new(Framed::new(connection, codec)),
+        }
+    }
+
+    pub fn call<R>(
+        &self,
+        request: R,
+    ) -> MultiplexClientFuture<Framed<UnixStream, C>, R>
+    where
+        C: Encoder<R>,
+        R: MessageWithId<Id = <C::Item as MessageWithId>::Id>,
+    {
+        self.client.call(request)
+    }
+}
+
+impl<C, R> Service<R> for MultiplexUnixClient<C>
+where
+    C: Decoder + Encoder<R> + Unpin,
+    C::Item: MessageWithId,
+    R: MessageWithId<Id = <C::Item as MessageWithId>::Id>,
+    <C::Item as MessageWithId>::Id: Eq + Hash,
+{
+    type Response = C::Item;
+    type Error = ClientError<<C as Decoder>::Error, <C as Encoder<R>>::Error>;
+    type Future = MultiplexClientFuture<Framed<UnixStream, C>, R>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.client.poll_ready::<R>(context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        MultiplexUnixClient::call(self, request)
+    }
+}
@@ -0,0 +1,82 @@
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{stream::FuturesUnordered, FutureExt, TryStream};
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
+
+use super::super::codec_listener_aliases::{
+    ErrorAlias, FutureAlias, ResponseAlias,
+};
+use super::super::connection_limits::ConnectionLimits;
+use super::super::shutdown_handle::ShutdownHandle;
+use super::generic_unix_listener_server::GenericUnixListenerServer;
+
+pub struct MultiplexUnixListenerServer<S, C>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+{
+    listener:
+        GenericUnixListenerServer<S, C, FuturesUnordered<FutureAlias<S, C>>>,
+}
+
+impl<S, C> MultiplexUnixListenerServer<S, C>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+{
+    /// Listens for connections on the Unix socket at `path`.
+    ///
+    /// A socket file left behind by a server that is no longer running is
+    /// removed first.
+    pub fn listen<P>(services: S, path: P, codec: C) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let listener =
+            GenericUnixListenerServer::listen(services, path, codec)?;
+
+        Ok(MultiplexUnixListenerServer { listener })
+    }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.listener.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.listener.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, C>) + Send + 'static,
+    {
+        self.listener.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.listener.fail_fast();
+    }
+}
+
+impl<S, C> Future for MultiplexUnixListenerServer<S, C>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+{
+    type Output = Result<(), ErrorAlias<S, C>>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.listener.poll_unpin(context)
+    }
+}
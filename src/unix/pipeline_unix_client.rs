@@ -0,0 +1,70 @@
+use std::io;
+use std::path::Path;
+use std::task::{Context, Poll};
+
+use tokio::net::UnixStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tower_service::Service;
+
+use super::super::{
+    client_error::ClientError,
+    pipeline_client::{PipelineClient, PipelineClientFuture},
+   
+};
+
+pub struct PipelineUnixClient<C>
+where
+    C: Decoder + Unpin,
+{
+    client: PipelineClient<Framed<UnixStream, C>>,
+}
+
+impl<C> PipelineUnixClient<C>
+where
+    C: Decoder + Unpin,
+{
+    pub async fn connect<P>(path: P, codec: C) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let connection = UnixStream::connect(path).await?;
+
+        Ok(Self::with_connection(connection, codec))
+    }
+
+    pub fn with_connection(connection: UnixStream, codec: C) -> Self {
+        PipelineUnixClient {
+            client: PipelineClient::new(Framed::new(connection, codec)),
+        }
+    }
+
+    pub fn call<R>(
+        &self,
+        request: R,
+    ) -> PipelineClientFuture<Framed<UnixStream, C>, R>
+    where
+        C: Encoder<R>,
+    {
+        self.client.call(request)
+    }
+}
+
+impl<C, R> Service<R> for PipelineUnixClient<C>
+where
+    C: Decoder + Encoder<R> + Unpin,
+{
+    type Response = C::Item;
+    type Error = ClientError<<C as Decoder>::Error, <C as Encoder<R>>::Error>;
+    type Future = PipelineClientFuture<Framed<UnixStream, C>, R>;
+
+    fn poll_ready(
+        &mut self,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.client.poll_ready::<R>(context)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        PipelineUnixClient::call(self, request)
+    }
+}
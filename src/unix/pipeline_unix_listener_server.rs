@@ -0,0 +1,159 @@
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{stream::FuturesOrdered, FutureExt, TryStream};
+use tokio_util::codec::{Decoder, Encoder};
+use tower_service::Service;
+
+use super::super::codec_listener_aliases::{
+    ErrorAlias, FutureAlias, ResponseAlias,
+};
+use super::super::connection_limits::ConnectionLimits;
+use super::super::shutdown_handle::ShutdownHandle;
+use super::generic_unix_listener_server::GenericUnixListenerServer;
+
+pub struct PipelineUnixListenerServer<S, C>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+{
+    listener:
+        GenericUnixListenerServer<S, C, FuturesOrdered<FutureAlias<S, C>>>,
+}
+
+impl<S, C> PipelineUnixListenerServer<S, C>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+{
+    /// Listens for connections on the Unix socket at `path`.
+    ///
+    /// A socket file left behind by a server that is no longer running is
+    /// removed first.
+    pub fn listen<P>(services: S, path: P, codec: C) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let listener =
+            GenericUnixListenerServer::listen(services, path, codec)?;
+
+        Ok(PipelineUnixListenerServer { listener })
+    }
+
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.listener.shutdown_handle()
+    }
+
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.listener.set_connection_limits(limits);
+    }
+
+    pub fn on_connection_error<F>(&mut self, handler: F)
+    where
+        F: FnMut(ErrorAlias<S, C>) + Send + 'static,
+    {
+        self.listener.on_connection_error(handler);
+    }
+
+    pub fn fail_fast(&mut self) {
+        self.listener.fail_fast();
+    }
+}
+
+impl<S, C> Future for PipelineUnixListenerServer<S, C>
+where
+    S: TryStream + Unpin,
+    S::Ok: Service<C::Item>,
+    C: Clone + Decoder + Encoder<ResponseAlias<S, C>> + Unpin,
+{
+    type Output = Result<(), ErrorAlias<S, C>>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Self::Output> {
+        self.listener.poll_unpin(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::process;
+
+    use futures::stream;
+
+    use super::*;
+    use super::super::pipeline_unix_client::PipelineUnixClient;
+    use crate::tests::common::{LineCodec, ToUpperService};
+
+    #[tokio::test]
+    async fn serves_unix_clients() {
+        let path = socket_path("serves_unix_clients");
+
+        assert_eq!(call_server_at(&path).await, "REQUEST");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn removes_stale_socket_files() {
+        let path = socket_path("removes_stale_socket_files");
+
+        drop(UnixListener::bind(&path).unwrap());
+
+        assert!(path.exists());
+        assert_eq!(call_server_at(&path).await, "REQUEST");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_sockets_that_are_in_use() {
+        let path = socket_path("keeps_sockets_that_are_in_use");
+        let _active_listener = UnixListener::bind(&path).unwrap();
+
+        let services = stream::iter(vec![Ok::<_, ()>(ToUpperService)]);
+        let result =
+            PipelineUnixListenerServer::listen(services, &path, LineCodec);
+
+        match result {
+            Err(ref error) if error.kind() == io::ErrorKind::AddrInUse => {}
+            _ => panic!("server took over a socket that was still in use"),
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    async fn call_server_at(path: &PathBuf) -> String {
+        let services = stream::iter(vec![Ok::<_, ()>(ToUpperService)]);
+        let server =
+            PipelineUnixListenerServer::listen(services, path, LineCodec)
+                .unwrap();
+
+        tokio::spawn(server);
+
+        let client =
+            PipelineUnixClient::connect(path, LineCodec).await.unwrap();
+
+        client.call("request".to_owned()).await.unwrap()
+    }
+
+    fn socket_path(test_name: &str) -> PathBuf {
+        let file_name =
+            format!("async-protocol-{}-{}.sock", process::id(), test_name);
+        let path = env::temp_dir().join(file_name);
+
+        let _ = fs::remove_file(&path);
+
+        path
+    }
+}
@@ -0,0 +1,30 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Removes the socket file at `path` if no server is listening on it anymore.
+///
+/// A server that exits without cleaning up leaves its socket file behind,
+/// which would make binding to the same path fail. Files that aren't sockets
+/// and sockets that still accept connections are left untouched.
+pub fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let is_socket = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata.file_type().is_socket(),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => false,
+        Err(error) => return Err(error),
+    };
+
+    if !is_socket {
+        return Ok(());
+    }
+
+    match UnixStream::connect(path) {
+        Ok(_) => Ok(()),
+        Err(ref error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+            fs::remove_file(path)
+        }
+        Err(error) => Err(error),
+    }
+}